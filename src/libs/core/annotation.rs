@@ -0,0 +1,150 @@
+//Dimensions and leader notes that stay attached to geometry. An annotation only stores references
+//to points, they are looked up in the current world space objects every time it is drawn so the
+//dimension follows the geometry when it moves.
+use super::*;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum PointReference{
+    //a fixed position in world space, not attached to anything
+    Fixed(Point),
+    //the world position of the Point_object hanging off the node
+    Node(NodeId),
+    //a vertex of the object hanging off the node, indexing its get_points. Only stable while the
+    //object keeps the same topology.
+    Vertex(NodeId, usize),
+}
+impl PointReference{
+    //nodes and objects are the world space objects and the node each came from, in step. None
+    //once the referenced node or vertex is gone.
+    pub fn resolve(&self, nodes: &Vec<NodeId>, objects: &Vec<coordinate_object>) -> Option<Point>{
+        let object_of = |id: &NodeId| nodes.iter().position(|node| node == id).map(|index| &objects[index]);
+        match self{
+            Self::Fixed(point) => Some(*point),
+            Self::Node(id) => match object_of(id)?{
+                coordinate_object::Point_object(point) => Some(*point),
+                _ => None,
+            },
+            Self::Vertex(id, index) => object_of(id)?.get_points().get(*index).copied(),
+        }
+    }
+}
+
+#[derive(Clone,Debug)]
+pub enum Annotation{
+    //Distance between two points drawn offset from them. With along set only the distance in that
+    //direction counts (horizontal and vertical dimensions), otherwise it is the straight distance.
+    LinearDimension{from: PointReference, to: PointReference, offset: Direction, along: Option<Direction>},
+    //angle at the vertex between the arms, drawn as an arc of the given radius
+    AngularDimension{vertex: PointReference, arm_one: PointReference, arm_two: PointReference, radius: f64},
+    //circle through three points on the arc
+    RadialDimension{arc: [PointReference; 3]},
+    DiameterDimension{arc: [PointReference; 3]},
+    //note with a line and arrow pointing at the anchor, the text sits at anchor + offset
+    Leader{anchor: PointReference, offset: Direction, text: String},
+}
+
+//An annotation placed in world space, ready to draw
+#[derive(Clone,Debug)]
+pub struct ResolvedAnnotation{
+    pub lines: Vec<Line>,
+    //arrow tips and the direction each arrow points in
+    pub arrows: Vec<(Point, Direction)>,
+    pub label: String,
+    pub label_anchor: Point,
+}
+
+impl Annotation{
+    //None when a referenced point is gone or the points are degenerate for the dimension
+    pub fn resolve(&self, nodes: &Vec<NodeId>, objects: &Vec<coordinate_object>) -> Option<ResolvedAnnotation>{
+        let resolve = |reference: &PointReference| reference.resolve(nodes, objects);
+        match self{
+            Self::LinearDimension{from, to, offset, along} => {
+                let (from, to) = (resolve(from)?, resolve(to)?);
+                let start = from + *offset;
+                let end = match along.and_then(|direction| direction.normalize()){
+                    Some(unit) => start + unit * (to - from).dot(&unit),
+                    None => to + *offset,
+                };
+                let span = end - start;
+                let outward = span.normalize()?;
+                Some(ResolvedAnnotation{
+                    lines: vec![Line::new(from, start), Line::new(to, end), Line::new(start, end)],
+                    arrows: vec![(start, -outward), (end, outward)],
+                    label: format!("{:.3}", span.length()),
+                    label_anchor: start + span * 0.5,
+                })
+            },
+            Self::AngularDimension{vertex, arm_one, arm_two, radius} => {
+                let vertex = resolve(vertex)?;
+                let unit_one = (resolve(arm_one)? - vertex).normalize()?;
+                let unit_two = (resolve(arm_two)? - vertex).normalize()?;
+                let angle = unit_one.dot(&unit_two).clamp(-1.0, 1.0).acos();
+                //straight angles have no plane of their own, the arc is bent round a perpendicular
+                let towards_two = if angle.sin() > 1e-6{
+                    (unit_two - unit_one * unit_one.dot(&unit_two)).normalize()?
+                }else{
+                    Direction::from_vector(any_perpendicular(&unit_one.to_vector()))
+                };
+                let along_arc = |fraction: f64| vertex + (unit_one * (fraction * angle).cos() + towards_two * (fraction * angle).sin()) * *radius;
+                let tangent_at = |fraction: f64| towards_two * (fraction * angle).cos() - unit_one * (fraction * angle).sin();
+                let segments = 24;
+                let mut lines: Vec<Line> = (0..segments)
+                    .map(|i| Line::new(along_arc(i as f64 / segments as f64), along_arc((i + 1) as f64 / segments as f64)))
+                    .collect();
+                lines.push(Line::new(vertex, along_arc(0.0)));
+                lines.push(Line::new(vertex, along_arc(1.0)));
+                Some(ResolvedAnnotation{
+                    lines: lines,
+                    arrows: vec![(along_arc(0.0), -tangent_at(0.0)), (along_arc(1.0), tangent_at(1.0))],
+                    label: format!("{:.2}°", angle.to_degrees()),
+                    label_anchor: along_arc(0.5),
+                })
+            },
+            Self::RadialDimension{arc} | Self::DiameterDimension{arc} => {
+                let points = vec![resolve(&arc[0])?, resolve(&arc[1])?, resolve(&arc[2])?];
+                let (centre, radius, _, _) = fit_circle(&points)?;
+                //drawn towards the first point on the arc
+                let outward = (points[0] - centre).normalize()?;
+                let on_arc = centre + outward * radius;
+                if let Self::RadialDimension{..} = self{
+                    Some(ResolvedAnnotation{
+                        lines: vec![Line::new(centre, on_arc)],
+                        arrows: vec![(on_arc, outward)],
+                        label: format!("R{:.3}", radius),
+                        label_anchor: centre + outward * (radius * 0.5),
+                    })
+                }else{
+                    let opposite = centre - outward * radius;
+                    Some(ResolvedAnnotation{
+                        lines: vec![Line::new(opposite, on_arc)],
+                        arrows: vec![(on_arc, outward), (opposite, -outward)],
+                        label: format!("DIA {:.3}", radius * 2.0),
+                        label_anchor: centre + outward * (radius * 0.5),
+                    })
+                }
+            },
+            Self::Leader{anchor, offset, text} => {
+                let anchor = resolve(anchor)?;
+                let label_anchor = anchor + *offset;
+                Some(ResolvedAnnotation{
+                    lines: vec![Line::new(label_anchor, anchor)],
+                    arrows: vec![(anchor, -*offset)],
+                    label: text.clone(),
+                    label_anchor: label_anchor,
+                })
+            },
+        }
+    }
+}
+
+impl Camera{
+    pub fn draw_annotation(&self, pixel_buffer: &mut Vec<[u8;4]>, annotation: &ResolvedAnnotation, colour: [u8;4]){
+        for line in annotation.lines.iter(){
+            self.draw_line(pixel_buffer, line, colour);
+        }
+        for (tip, direction) in annotation.arrows.iter(){
+            self.draw_arrowhead(pixel_buffer, tip, direction, colour);
+        }
+        self.draw_label(pixel_buffer, &annotation.label, &annotation.label_anchor, colour);
+    }
+}
@@ -0,0 +1,278 @@
+//Parts as definitions and instances. A definition holds the geometry once, in its own coordinates.
+//An instance places a definition in the scene: it owns a scene node whose transform is the
+//instance's pose and joints attach to that node. The node carries a copy of the definition's
+//geometry so the rest of the scene does not have to know about definitions, the copies are
+//refreshed whenever the definition changes.
+use super::*;
+
+pub type DefinitionId = usize;
+pub type InstanceId = usize;
+
+#[derive(Clone,Debug)]
+pub enum AssemblyError{
+    DefinitionNotFound(DefinitionId),
+    InstanceNotFound(InstanceId),
+    //a definition cannot be removed while instances still use it
+    DefinitionInUse(DefinitionId, Vec<InstanceId>),
+    Scene(SceneGraphError),
+}
+impl From<SceneGraphError> for AssemblyError{
+    fn from(error: SceneGraphError) -> Self{
+        AssemblyError::Scene(error)
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct PartDefinition{
+    pub name: String,
+    geometry: PerspectiveObject,
+}
+impl PartDefinition{
+    pub fn get_geometry(&self) -> &PerspectiveObject{
+        &self.geometry
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct PartInstance{
+    pub name: String,
+    definition: DefinitionId,
+    node: NodeId,
+}
+impl PartInstance{
+    pub fn get_definition(&self) -> DefinitionId{
+        self.definition
+    }
+    pub fn get_node(&self) -> NodeId{
+        self.node
+    }
+}
+
+//a joint seen as an edge between the two instances it connects
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct AssemblyConnection{
+    pub joint: usize,
+    pub parent: InstanceId,
+    pub child: InstanceId,
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct Assembly{
+    //removed entries leave a None behind so ids stay stable, like the scene graph
+    definitions: Vec<Option<PartDefinition>>,
+    instances: Vec<Option<PartInstance>>,
+}
+
+impl Assembly{
+    pub fn new() -> Self{
+        Assembly::default()
+    }
+
+    pub fn add_definition(&mut self, name: &str, geometry: PerspectiveObject) -> DefinitionId{
+        self.definitions.push(Some(PartDefinition{
+            name: name.to_string(),
+            geometry: geometry,
+        }));
+        self.definitions.len() - 1
+    }
+    pub fn get_definition(&self, id: DefinitionId) -> Result<&PartDefinition, AssemblyError>{
+        match self.definitions.get(id){
+            Some(Some(definition)) => Ok(definition),
+            _ => Err(AssemblyError::DefinitionNotFound(id)),
+        }
+    }
+    pub fn definition_ids(&self) -> Vec<DefinitionId>{
+        (0..self.definitions.len()).filter(|id| self.definitions[*id].is_some()).collect()
+    }
+
+    pub fn get_instance(&self, id: InstanceId) -> Result<&PartInstance, AssemblyError>{
+        match self.instances.get(id){
+            Some(Some(instance)) => Ok(instance),
+            _ => Err(AssemblyError::InstanceNotFound(id)),
+        }
+    }
+    pub fn instance_ids(&self) -> Vec<InstanceId>{
+        (0..self.instances.len()).filter(|id| self.instances[*id].is_some()).collect()
+    }
+    pub fn instances_of(&self, definition: DefinitionId) -> Vec<InstanceId>{
+        self.instance_ids().into_iter()
+            .filter(|id| self.instances[*id].as_ref().unwrap().definition == definition)
+            .collect()
+    }
+    pub fn instance_at_node(&self, node: NodeId) -> Option<InstanceId>{
+        self.instance_ids().into_iter().find(|id| self.instances[*id].as_ref().unwrap().node == node)
+    }
+
+    //Adds a scene node for the instance, under parent (or at the top level) with the given pose,
+    //holding a copy of the definition's geometry
+    pub fn instantiate(&mut self, scene: &mut SceneGraph, definition: DefinitionId, name: &str, parent: Option<NodeId>, pose: Transform) -> Result<InstanceId, AssemblyError>{
+        let geometry = self.get_definition(definition)?.geometry.clone();
+        let node = scene.add_node(name, parent, pose, Some(coordinate_object::Perspective_object(geometry)))?;
+        self.instances.push(Some(PartInstance{
+            name: name.to_string(),
+            definition: definition,
+            node: node,
+        }));
+        Ok(self.instances.len() - 1)
+    }
+
+    //Removes the instance and its scene node. Anything parented under the node in the scene goes
+    //with it, instances among them included.
+    pub fn remove_instance(&mut self, scene: &mut SceneGraph, id: InstanceId) -> Result<(), AssemblyError>{
+        let node = self.get_instance(id)?.node;
+        scene.remove_subtree(node)?;
+        for slot in self.instances.iter_mut(){
+            if slot.as_ref().map_or(false, |instance| scene.get_node(instance.node).is_err()){
+                *slot = None;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_definition(&mut self, id: DefinitionId) -> Result<PartDefinition, AssemblyError>{
+        self.get_definition(id)?;
+        let users = self.instances_of(id);
+        if !users.is_empty(){
+            return Err(AssemblyError::DefinitionInUse(id, users));
+        }
+        Ok(self.definitions[id].take().unwrap())
+    }
+
+    //Changes the definition's geometry and pushes the result out to every instance of it. Returns
+    //the instances that were updated.
+    pub fn edit_definition(&mut self, scene: &mut SceneGraph, id: DefinitionId, edit: impl FnOnce(&mut PerspectiveObject)) -> Result<Vec<InstanceId>, AssemblyError>{
+        match self.definitions.get_mut(id){
+            Some(Some(definition)) => edit(&mut definition.geometry),
+            _ => return Err(AssemblyError::DefinitionNotFound(id)),
+        }
+        let geometry = self.definitions[id].as_ref().unwrap().geometry.clone();
+        let users = self.instances_of(id);
+        for instance in users.iter(){
+            let node = self.instances[*instance].as_ref().unwrap().node;
+            scene.set_object(node, Some(coordinate_object::Perspective_object(geometry.clone())))?;
+        }
+        Ok(users)
+    }
+
+    //every joint between two instances, joints touching nodes that are not instances are left out
+    pub fn connections(&self, joints: &Vec<Joint>) -> Vec<AssemblyConnection>{
+        let mut connections = vec![];
+        for (index, joint) in joints.iter().enumerate(){
+            if let (Some(parent), Some(child)) = (self.instance_at_node(joint.get_parent()), self.instance_at_node(joint.get_child())){
+                connections.push(AssemblyConnection{
+                    joint: index,
+                    parent: parent,
+                    child: child,
+                });
+            }
+        }
+        connections
+    }
+
+    //the connections the instance takes part in, as parent or child
+    pub fn connections_of(&self, joints: &Vec<Joint>, id: InstanceId) -> Vec<AssemblyConnection>{
+        self.connections(joints).into_iter().filter(|connection| connection.parent == id || connection.child == id).collect()
+    }
+
+    //Groups of instances held together by joints (directly or through other instances). An
+    //instance with no joints is a group of its own.
+    pub fn connected_groups(&self, joints: &Vec<Joint>) -> Vec<Vec<InstanceId>>{
+        let connections = self.connections(joints);
+        let mut group_of: Vec<Option<usize>> = vec![None; self.instances.len()];
+        let mut groups: Vec<Vec<InstanceId>> = vec![];
+        for start in self.instance_ids(){
+            if group_of[start].is_some(){
+                continue;
+            }
+            let mut group = vec![];
+            let mut pending = vec![start];
+            group_of[start] = Some(groups.len());
+            while let Some(current) = pending.pop(){
+                group.push(current);
+                for connection in connections.iter(){
+                    let other = if connection.parent == current {connection.child} else if connection.child == current {connection.parent} else {continue};
+                    if group_of[other].is_none(){
+                        group_of[other] = Some(groups.len());
+                        pending.push(other);
+                    }
+                }
+            }
+            group.sort();
+            groups.push(group);
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_shapes::prism;
+
+    fn unit_cube() -> PerspectiveObject{
+        prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0)
+    }
+    fn moved(x: f64) -> Transform{
+        Transform::translation(Direction::new(x, 0.0, 0.0))
+    }
+    fn revolute(parent: NodeId, child: NodeId) -> Joint{
+        Joint::new("", JointType::Revolute, parent, Transform::identity(), child, Transform::identity()).unwrap()
+    }
+    fn side_count(scene: &SceneGraph, node: NodeId) -> usize{
+        match scene.get_node(node).unwrap().get_object(){
+            Some(coordinate_object::Perspective_object(object)) => object.get_sides().len(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn editing_a_definition_updates_every_instance(){
+        let mut scene = SceneGraph::new();
+        let mut assembly = Assembly::new();
+        let block = assembly.add_definition("block", unit_cube());
+        let other = assembly.add_definition("other", unit_cube());
+        let first = assembly.instantiate(&mut scene, block, "first", None, moved(0.0)).unwrap();
+        let second = assembly.instantiate(&mut scene, block, "second", None, moved(3.0)).unwrap();
+        let untouched = assembly.instantiate(&mut scene, other, "untouched", None, moved(6.0)).unwrap();
+
+        let edge = Line::new(Point::new(1.0, 0.0, 0.0), Point::new(1.0, 0.0, 1.0));
+        let updated = assembly.edit_definition(&mut scene, block, |geometry| geometry.chamfer_edges(&vec![edge], 0.1, 0.1).unwrap()).unwrap();
+        assert_eq!(updated, vec![first, second]);
+        assert_eq!(assembly.get_definition(block).unwrap().get_geometry().get_sides().len(), 7);
+        for instance in [first, second]{
+            assert_eq!(side_count(&scene, assembly.get_instance(instance).unwrap().get_node()), 7);
+        }
+        assert_eq!(side_count(&scene, assembly.get_instance(untouched).unwrap().get_node()), 6);
+        //the copies keep their own poses
+        let second_world = scene.world_object(assembly.get_instance(second).unwrap().get_node()).unwrap().unwrap();
+        assert!(second_world.get_points().iter().all(|point| point.xyz().x >= 3.0 - 1e-9));
+    }
+
+    #[test]
+    fn definitions_in_use_are_kept(){
+        let mut scene = SceneGraph::new();
+        let mut assembly = Assembly::new();
+        let block = assembly.add_definition("block", unit_cube());
+        let instance = assembly.instantiate(&mut scene, block, "block", None, moved(0.0)).unwrap();
+        assert!(matches!(assembly.remove_definition(block), Err(AssemblyError::DefinitionInUse(id, users)) if id == block && users == vec![instance]));
+        assembly.remove_instance(&mut scene, instance).unwrap();
+        assert!(assembly.remove_definition(block).is_ok());
+        assert!(matches!(assembly.get_definition(block), Err(AssemblyError::DefinitionNotFound(_))));
+    }
+
+    #[test]
+    fn joints_split_instances_into_groups(){
+        let mut scene = SceneGraph::new();
+        let mut assembly = Assembly::new();
+        let block = assembly.add_definition("block", unit_cube());
+        let instances: Vec<InstanceId> = (0..5).map(|index| assembly.instantiate(&mut scene, block, "", None, moved(2.0 * index as f64)).unwrap()).collect();
+        let node = |instance: usize| assembly.get_instance(instances[instance]).unwrap().get_node();
+        //0 - 1 - 3 held together through 1, 2 and 4 on a joint of their own, plus one to a node
+        //that is not an instance
+        let loose = scene.add_node("loose", None, Transform::identity(), None).unwrap();
+        let joints = vec![revolute(node(1), node(0)), revolute(node(1), node(3)), revolute(node(4), node(2)), revolute(loose, node(4))];
+        assert_eq!(assembly.connections(&joints).len(), 3);
+        assert_eq!(assembly.connected_groups(&joints), vec![vec![0, 1, 3], vec![2, 4]]);
+        assert_eq!(assembly.connected_groups(&vec![]).len(), 5);
+    }
+}
@@ -0,0 +1,359 @@
+//Bounding volumes for anything made of points. Axis aligned boxes are cheap and used for culling
+//and broad checks, oriented boxes hug rotated parts much more tightly.
+use super::*;
+use std::cell::RefCell;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct BoundingBox{
+    pub min: na::Vector3<f64>,
+    pub max: na::Vector3<f64>,
+}
+impl BoundingBox{
+    pub fn new(min: na::Vector3<f64>, max: na::Vector3<f64>) -> Self{
+        BoundingBox{
+            min: min,
+            max: max,
+        }
+    }
+    pub fn from_points(points: &Vec<Point>) -> Option<Self>{
+        let first = points.first()?.xyz();
+        let mut bounds = BoundingBox::new(first, first);
+        for point in points.iter(){
+            bounds.expand_to(&point.xyz());
+        }
+        Some(bounds)
+    }
+    pub fn expand_to(&mut self, position: &na::Vector3<f64>){
+        self.min = self.min.inf(position);
+        self.max = self.max.sup(position);
+    }
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox{
+        BoundingBox::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+    pub fn centre(&self) -> na::Vector3<f64>{
+        (self.min + self.max) / 2.0
+    }
+    pub fn size(&self) -> na::Vector3<f64>{
+        self.max - self.min
+    }
+    pub fn volume(&self) -> f64{
+        let size = self.size();
+        size.x * size.y * size.z
+    }
+    pub fn surface_area(&self) -> f64{
+        let size = self.size();
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+    pub fn contains(&self, position: &na::Vector3<f64>) -> bool{
+        (0..3).all(|axis| self.min[axis] <= position[axis] && position[axis] <= self.max[axis])
+    }
+    pub fn intersects(&self, other: &BoundingBox) -> bool{
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+    //distance from the point to the closest point of the box, 0 inside
+    pub fn distance_to(&self, position: &na::Vector3<f64>) -> f64{
+        let clamped = position.sup(&self.min).inf(&self.max);
+        (position - clamped).norm()
+    }
+    //slab test, returns the entry and exit distances along the ray (entry is 0 if the origin is
+    //inside). The direction does not need to be normalised, distances are in multiples of it.
+    pub fn ray_intersection(&self, origin: &na::Vector3<f64>, direction: &na::Vector3<f64>) -> Option<(f64, f64)>{
+        let mut entry: f64 = 0.0;
+        let mut exit: f64 = f64::INFINITY;
+        for axis in 0..3{
+            if direction[axis].abs() < f64::EPSILON{
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis]{
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / direction[axis];
+            let mut near = (self.min[axis] - origin[axis]) * inverse;
+            let mut far = (self.max[axis] - origin[axis]) * inverse;
+            if near > far{
+                std::mem::swap(&mut near, &mut far);
+            }
+            entry = entry.max(near);
+            exit = exit.min(far);
+            if entry > exit{
+                return None;
+            }
+        }
+        Some((entry, exit))
+    }
+    pub fn corners(&self) -> [na::Vector3<f64>; 8]{
+        let mut corners = [na::Vector3::<f64>::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate(){
+            *corner = na::Vector3::new(
+                if i & 1 == 0 {self.min.x} else {self.max.x},
+                if i & 2 == 0 {self.min.y} else {self.max.y},
+                if i & 4 == 0 {self.min.z} else {self.max.z},
+            );
+        }
+        corners
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct OrientedBoundingBox{
+    pub centre: na::Vector3<f64>,
+    //columns are the (orthonormal) box axes
+    pub axes: na::Matrix3<f64>,
+    pub half_extents: na::Vector3<f64>,
+}
+impl OrientedBoundingBox{
+    pub fn volume(&self) -> f64{
+        8.0 * self.half_extents.x * self.half_extents.y * self.half_extents.z
+    }
+    pub fn corners(&self) -> [na::Vector3<f64>; 8]{
+        let mut corners = [na::Vector3::<f64>::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate(){
+            let signs = na::Vector3::new(
+                if i & 1 == 0 {-1.0} else {1.0},
+                if i & 2 == 0 {-1.0} else {1.0},
+                if i & 4 == 0 {-1.0} else {1.0},
+            );
+            *corner = self.centre + self.axes * signs.component_mul(&self.half_extents);
+        }
+        corners
+    }
+    pub fn to_axis_aligned(&self) -> BoundingBox{
+        let extent = self.axes.abs() * self.half_extents;
+        BoundingBox::new(self.centre - extent, self.centre + extent)
+    }
+    pub fn contains(&self, position: &na::Vector3<f64>) -> bool{
+        let local = self.axes.transpose() * (position - self.centre);
+        (0..3).all(|axis| local[axis].abs() <= self.half_extents[axis])
+    }
+
+    //An approximation of the minimum volume box: the smallest over a set of candidate directions
+    //for one of its axes. For each candidate the points are flattened onto the perpendicular plane
+    //and the smallest enclosing rectangle is found exactly with rotating calipers. Candidates are the
+    //given face normals, the principal axes of the points and the world axes, so the box is the true
+    //minimum whenever one of its faces lies flush with a face of the shape (the usual case for
+    //machined parts) and can be larger otherwise.
+    pub fn from_points(points: &Vec<Point>, face_normals: &Vec<na::Vector3<f64>>) -> Option<Self>{
+        let positions: Vec<na::Vector3<f64>> = points.iter().map(|point| point.xyz()).collect();
+        if positions.is_empty(){
+            return None;
+        }
+        let mut candidates: Vec<na::Vector3<f64>> = vec![
+            na::Vector3::x(), na::Vector3::y(), na::Vector3::z(),
+        ];
+        let principal = principal_axes(&positions);
+        for i in 0..3{
+            candidates.push(principal.column(i).into_owned());
+        }
+        for normal in face_normals.iter(){
+            if let Some(unit) = normal.try_normalize(f64::EPSILON){
+                candidates.push(unit);
+            }
+        }
+
+        let mut best: Option<OrientedBoundingBox> = None;
+        for direction in candidates.iter(){
+            let candidate = fit_box_around_direction(&positions, direction);
+            let better = match best{
+                Some(current) => candidate.volume() < current.volume() - 1e-12,
+                None => true,
+            };
+            if better{
+                best = Some(candidate);
+            }
+        }
+        best
+    }
+}
+
+fn principal_axes(positions: &Vec<na::Vector3<f64>>) -> na::Matrix3<f64>{
+    let mean = positions.iter().fold(na::Vector3::<f64>::zeros(), |sum, position| sum + position) / positions.len() as f64;
+    let mut covariance = na::Matrix3::<f64>::zeros();
+    for position in positions.iter(){
+        let offset = position - mean;
+        covariance += offset * offset.transpose();
+    }
+    covariance.symmetric_eigen().eigenvectors
+}
+
+//any unit vector perpendicular to the given one
+pub fn any_perpendicular(direction: &na::Vector3<f64>) -> na::Vector3<f64>{
+    let helper = if direction.x.abs() < 0.9 {na::Vector3::x()} else {na::Vector3::y()};
+    direction.cross(&helper).normalize()
+}
+
+fn fit_box_around_direction(positions: &Vec<na::Vector3<f64>>, direction: &na::Vector3<f64>) -> OrientedBoundingBox{
+    let axis_u = any_perpendicular(direction);
+    let axis_v = direction.cross(&axis_u);
+    let flat: Vec<na::Vector2<f64>> = positions.iter()
+        .map(|position| na::Vector2::new(position.dot(&axis_u), position.dot(&axis_v)))
+        .collect();
+    let (rectangle_u, rectangle_v) = minimum_area_rectangle(&flat);
+
+    let axis_one = axis_u * rectangle_u.x + axis_v * rectangle_u.y;
+    let axis_two = axis_u * rectangle_v.x + axis_v * rectangle_v.y;
+    let axes = na::Matrix3::from_columns(&[axis_one, axis_two, *direction]);
+
+    let mut low = na::Vector3::repeat(f64::INFINITY);
+    let mut high = na::Vector3::repeat(f64::NEG_INFINITY);
+    for position in positions.iter(){
+        let local = axes.transpose() * position;
+        low = low.inf(&local);
+        high = high.sup(&local);
+    }
+    OrientedBoundingBox{
+        centre: axes * ((low + high) / 2.0),
+        axes: axes,
+        half_extents: (high - low) / 2.0,
+    }
+}
+
+//Andrew's monotone chain, counter clockwise without collinear points
+pub fn convex_hull_2d(points: &Vec<na::Vector2<f64>>) -> Vec<na::Vector2<f64>>{
+    let mut sorted = points.clone();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3{
+        return sorted;
+    }
+    let turn = |o: &na::Vector2<f64>, a: &na::Vector2<f64>, b: &na::Vector2<f64>| (a - o).perp(&(b - o));
+    let build_chain = |ordered: &mut dyn Iterator<Item = &na::Vector2<f64>>| -> Vec<na::Vector2<f64>>{
+        let mut chain: Vec<na::Vector2<f64>> = vec![];
+        for point in ordered{
+            while chain.len() >= 2 && turn(&chain[chain.len() - 2], &chain[chain.len() - 1], point) <= 0.0{
+                chain.pop();
+            }
+            chain.push(*point);
+        }
+        chain.pop();
+        chain
+    };
+    let mut hull = build_chain(&mut sorted.iter());
+    hull.extend(build_chain(&mut sorted.iter().rev()));
+    hull
+}
+
+//Returns the two unit axes of the smallest area rectangle enclosing the points. One of its sides
+//is always flush with a hull edge, so rotating calipers walk the hull edges once while keeping the
+//furthest point ahead, above and behind each edge, all of which only ever move forwards.
+fn minimum_area_rectangle(points: &Vec<na::Vector2<f64>>) -> (na::Vector2<f64>, na::Vector2<f64>){
+    let hull = convex_hull_2d(points);
+    let count = hull.len();
+    let mut best = (na::Vector2::x(), na::Vector2::y());
+    if count < 3{
+        if count == 2{
+            if let Some(axis_one) = (hull[1] - hull[0]).try_normalize(f64::EPSILON){
+                best = (axis_one, na::Vector2::new(-axis_one.y, axis_one.x));
+            }
+        }
+        return best;
+    }
+    let at = |index: usize| hull[index % count];
+    //moves the index on while the next hull point is further along the axis
+    let advance = |index: &mut usize, axis: &na::Vector2<f64>, further: &dyn Fn(f64, f64) -> bool|{
+        for _ in 0..count{
+            if further(at(*index + 1).dot(axis), at(*index).dot(axis)){
+                *index += 1;
+            }else{
+                break;
+            }
+        }
+    };
+    let (mut ahead, mut above, mut behind) = (0, 0, 0);
+    let mut best_area = f64::INFINITY;
+    for i in 0..count{
+        let axis_one = match (at(i + 1) - at(i)).try_normalize(f64::EPSILON){
+            Some(axis) => axis,
+            None => continue,
+        };
+        //into the hull, which is anticlockwise
+        let axis_two = na::Vector2::new(-axis_one.y, axis_one.x);
+        ahead = ahead.max(i);
+        advance(&mut ahead, &axis_one, &|next, current| next > current);
+        if i == 0{
+            above = ahead;
+        }
+        advance(&mut above, &axis_two, &|next, current| next > current);
+        if i == 0{
+            behind = above;
+        }
+        advance(&mut behind, &axis_one, &|next, current| next < current);
+
+        let area = (at(ahead) - at(behind)).dot(&axis_one) * (at(above) - at(i)).dot(&axis_two);
+        if area < best_area{
+            best_area = area;
+            best = (axis_one, axis_two);
+        }
+    }
+    best
+}
+
+//Cached bounds for objects that are expensive to bound. Anything that moves the object or
+//changes its points must call invalidate.
+#[derive(Clone,Debug,Default)]
+pub struct BoundsCache{
+    axis_aligned: RefCell<Option<Option<BoundingBox>>>,
+    oriented: RefCell<Option<Option<OrientedBoundingBox>>>,
+}
+impl BoundsCache{
+    pub fn invalidate(&self){
+        *self.axis_aligned.borrow_mut() = None;
+        *self.oriented.borrow_mut() = None;
+    }
+    pub fn axis_aligned(&self, compute: impl FnOnce() -> Option<BoundingBox>) -> Option<BoundingBox>{
+        let mut cached = self.axis_aligned.borrow_mut();
+        *cached.get_or_insert_with(compute)
+    }
+    pub fn oriented(&self, compute: impl FnOnce() -> Option<OrientedBoundingBox>) -> Option<OrientedBoundingBox>{
+        let mut cached = self.oriented.borrow_mut();
+        *cached.get_or_insert_with(compute)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn rectangle_area(points: &Vec<na::Vector2<f64>>, axes: (na::Vector2<f64>, na::Vector2<f64>)) -> f64{
+        let extent = |axis: &na::Vector2<f64>| {
+            let dots: Vec<f64> = points.iter().map(|point| point.dot(axis)).collect();
+            dots.iter().cloned().fold(f64::NEG_INFINITY, f64::max) - dots.iter().cloned().fold(f64::INFINITY, f64::min)
+        };
+        extent(&axes.0) * extent(&axes.1)
+    }
+
+    #[test]
+    fn calipers_match_trying_every_hull_edge(){
+        let mut seed: u64 = 11;
+        let mut random = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+        for _ in 0..50{
+            let points: Vec<na::Vector2<f64>> = (0..30).map(|_| na::Vector2::new(random() * 4.0, random())).collect();
+            let hull = convex_hull_2d(&points);
+            let brute_force = (0..hull.len()).map(|i| {
+                let axis = (hull[(i + 1) % hull.len()] - hull[i]).normalize();
+                rectangle_area(&points, (axis, na::Vector2::new(-axis.y, axis.x)))
+            }).fold(f64::INFINITY, f64::min);
+            assert!((rectangle_area(&points, minimum_area_rectangle(&points)) - brute_force).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn turned_box_is_fitted_exactly(){
+        let turn = na::Rotation3::from_axis_angle(&na::Unit::new_normalize(na::Vector3::new(1.0, 2.0, 3.0)), 0.7).into_inner();
+        let mut points = vec![];
+        for index in 0..8{
+            let corner = na::Vector3::new(
+                if index & 1 == 0 {-1.0} else {1.0},
+                if index & 2 == 0 {-2.0} else {2.0},
+                if index & 4 == 0 {-0.5} else {0.5},
+            );
+            points.push(Point::from_xyz(turn * corner + na::Vector3::new(5.0, 0.0, -1.0)));
+        }
+        let normals = vec![turn.column(0).into_owned()];
+        let fitted = OrientedBoundingBox::from_points(&points, &normals).unwrap();
+        assert!((fitted.volume() - 8.0).abs() < 1e-9);
+        assert!((fitted.centre - na::Vector3::new(5.0, 0.0, -1.0)).norm() < 1e-9);
+    }
+}
@@ -0,0 +1,455 @@
+//Bounding volume hierarchy over a list of objects (WorldSpace keeps one over all_independents). The
+//tree stores indices into the list it was built from, so it has to be rebuilt when objects are
+//added or removed and refit when they move.
+use super::*;
+
+//leaves hold a few objects, splitting all the way down to one object costs more than it saves
+const MAX_LEAF_OBJECTS: usize = 4;
+//once refitting has grown the root this much the tree is considered worn out
+const REBUILD_GROWTH: f64 = 2.0;
+
+#[derive(Clone,Debug)]
+enum BvhNodeKind{
+    Leaf(Vec<usize>),
+    Branch(usize, usize),
+}
+
+#[derive(Clone,Debug)]
+struct BvhNode{
+    bounds: BoundingBox,
+    parent: Option<usize>,
+    kind: BvhNodeKind,
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct Bvh{
+    nodes: Vec<BvhNode>,
+    root: Option<usize>,
+    object_bounds: Vec<Option<BoundingBox>>,
+    //the leaf each object sits in, objects without bounds (no points) are left out of the tree
+    leaf_of_object: Vec<Option<usize>>,
+    built_surface_area: f64,
+}
+
+//Convex volume made of planes whose normals point inwards, a position is inside when
+//normal.dot(position) + offset >= 0 for every plane
+#[derive(Clone,Debug)]
+pub struct Frustum{
+    pub planes: Vec<(na::Vector3<f64>, f64)>,
+}
+impl Frustum{
+    pub fn contains(&self, position: &na::Vector3<f64>) -> bool{
+        self.planes.iter().all(|(normal, offset)| normal.dot(position) + offset >= 0.0)
+    }
+    //conservative, boxes straddling a corner of the frustum can be reported as intersecting
+    pub fn intersects_box(&self, bounds: &BoundingBox) -> bool{
+        for (normal, offset) in self.planes.iter(){
+            let furthest_inside = na::Vector3::new(
+                if normal.x >= 0.0 {bounds.max.x} else {bounds.min.x},
+                if normal.y >= 0.0 {bounds.max.y} else {bounds.min.y},
+                if normal.z >= 0.0 {bounds.max.z} else {bounds.min.z},
+            );
+            if normal.dot(&furthest_inside) + offset < 0.0{
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Camera{
+    //The volume the camera can see, taken straight from the rows of screen_projection so culling
+    //agrees with what draw_line and project_to_screen put on screen. A world position p lands at
+    //(row0.p / row2.p, row1.p / row2.p) with depth row2.p, each screen edge and depth limit is a
+    //plane in those rows.
+    pub fn view_frustum(&self) -> Frustum{
+        let projection = self.screen_projection();
+        let (across, down, depth) = (
+            projection.row(0).transpose(),
+            projection.row(1).transpose(),
+            projection.row(2).transpose(),
+        );
+        let (width, height) = (self.screen_x as f64, self.screen_y as f64);
+        let world_planes: Vec<na::Vector4<f64>> = vec![
+            depth - na::Vector4::new(0.0, 0.0, 0.0, self.min_depth_difference),
+            na::Vector4::new(0.0, 0.0, 0.0, self.max_depth_difference) - depth,
+            across,
+            depth * width - across,
+            down,
+            depth * height - down,
+        ];
+        let mut planes = vec![];
+        for world_plane in world_planes.iter(){
+            let normal = na::Vector3::new(world_plane.x, world_plane.y, world_plane.z);
+            let length = normal.norm();
+            if length > f64::EPSILON{
+                planes.push((normal / length, world_plane.w / length));
+            }
+        }
+        Frustum{
+            planes: planes,
+        }
+    }
+}
+
+impl Bvh{
+    pub fn new() -> Self{
+        Bvh::default()
+    }
+
+    pub fn build<T: Point_Construct>(objects: &Vec<T>) -> Self{
+        Bvh::from_bounds(objects.iter().map(|object| object.axis_aligned_bounds()).collect())
+    }
+
+    pub fn from_bounds(object_bounds: Vec<Option<BoundingBox>>) -> Self{
+        let mut bvh = Bvh{
+            nodes: vec![],
+            root: None,
+            leaf_of_object: vec![None; object_bounds.len()],
+            object_bounds: object_bounds,
+            built_surface_area: 0.0,
+        };
+        let mut indices: Vec<usize> = (0..bvh.object_bounds.len())
+            .filter(|index| bvh.object_bounds[*index].is_some())
+            .collect();
+        if !indices.is_empty(){
+            let root = bvh.build_node(&mut indices, None);
+            bvh.root = Some(root);
+            bvh.built_surface_area = bvh.nodes[root].bounds.surface_area();
+        }
+        bvh
+    }
+
+    //number of objects the tree was built over
+    pub fn len(&self) -> usize{
+        self.object_bounds.len()
+    }
+
+    pub fn root_bounds(&self) -> Option<BoundingBox>{
+        self.root.map(|root| self.nodes[root].bounds)
+    }
+
+    fn bounds_of(&self, indices: &[usize]) -> BoundingBox{
+        let mut bounds = self.object_bounds[indices[0]].unwrap();
+        for index in indices.iter(){
+            bounds = bounds.union(&self.object_bounds[*index].unwrap());
+        }
+        bounds
+    }
+
+    //median split along the longest axis of the object centres
+    fn build_node(&mut self, indices: &mut [usize], parent: Option<usize>) -> usize{
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode{
+            bounds: self.bounds_of(indices),
+            parent: parent,
+            kind: BvhNodeKind::Leaf(vec![]),
+        });
+        self.fill_node(node_index, indices);
+        node_index
+    }
+
+    //turns an existing node into a leaf over the objects, or a branch splitting them when there
+    //are too many for one leaf
+    fn fill_node(&mut self, node_index: usize, indices: &mut [usize]){
+        self.nodes[node_index].bounds = self.bounds_of(indices);
+        if indices.len() <= MAX_LEAF_OBJECTS{
+            for index in indices.iter(){
+                self.leaf_of_object[*index] = Some(node_index);
+            }
+            self.nodes[node_index].kind = BvhNodeKind::Leaf(indices.to_vec());
+            return;
+        }
+
+        let mut centres = BoundingBox::new(self.object_bounds[indices[0]].unwrap().centre(), self.object_bounds[indices[0]].unwrap().centre());
+        for index in indices.iter(){
+            centres.expand_to(&self.object_bounds[*index].unwrap().centre());
+        }
+        let spread = centres.size();
+        let axis = if spread.x >= spread.y && spread.x >= spread.z {0} else if spread.y >= spread.z {1} else {2};
+        let middle = indices.len() / 2;
+        let object_bounds = &self.object_bounds;
+        indices.select_nth_unstable_by(middle, |a, b| {
+            object_bounds[*a].unwrap().centre()[axis].total_cmp(&object_bounds[*b].unwrap().centre()[axis])
+        });
+        let (left_indices, right_indices) = indices.split_at_mut(middle);
+        let left = self.build_node(left_indices, Some(node_index));
+        let right = self.build_node(right_indices, Some(node_index));
+        self.nodes[node_index].kind = BvhNodeKind::Branch(left, right);
+    }
+
+    //Recomputes every bound without changing the tree shape, rebuilding instead if the number of
+    //objects changed or the tree has degraded too far
+    pub fn refit<T: Point_Construct>(&mut self, objects: &Vec<T>){
+        if objects.len() != self.object_bounds.len(){
+            *self = Bvh::build(objects);
+            return;
+        }
+        for (index, object) in objects.iter().enumerate(){
+            self.update_object(index, object.axis_aligned_bounds());
+        }
+        if self.needs_rebuild(){
+            *self = Bvh::from_bounds(self.object_bounds.clone());
+        }
+    }
+
+    //Incremental refit for a single object that moved, only its leaf and the leaf's ancestors are
+    //touched
+    pub fn update_object(&mut self, index: usize, bounds: Option<BoundingBox>){
+        let had_bounds = self.object_bounds[index].is_some();
+        self.object_bounds[index] = bounds;
+        if had_bounds != bounds.is_some(){
+            //the object enters or leaves the tree, not something a refit can express
+            *self = Bvh::from_bounds(self.object_bounds.clone());
+            return;
+        }
+        self.refit_upwards(self.leaf_of_object[index]);
+    }
+
+    //recomputes the bounds of the node and every node above it
+    fn refit_upwards(&mut self, node: Option<usize>){
+        let mut current = node;
+        while let Some(node_index) = current{
+            let new_bounds = match &self.nodes[node_index].kind{
+                BvhNodeKind::Leaf(objects) => self.bounds_of(objects),
+                BvhNodeKind::Branch(left, right) => self.nodes[*left].bounds.union(&self.nodes[*right].bounds),
+            };
+            self.nodes[node_index].bounds = new_bounds;
+            current = self.nodes[node_index].parent;
+        }
+    }
+
+    //Adds one object at the end of the list without rebuilding: it goes down to the leaf whose
+    //bounds grow the least, which is split if it now holds too many objects. Returns its index.
+    pub fn insert_object(&mut self, bounds: Option<BoundingBox>) -> usize{
+        let index = self.object_bounds.len();
+        self.object_bounds.push(bounds);
+        self.leaf_of_object.push(None);
+        let bounds = match bounds{
+            Some(bounds) => bounds,
+            None => return index,
+        };
+        let mut current = match self.root{
+            Some(root) => root,
+            None => {
+                let root = self.build_node(&mut [index], None);
+                self.root = Some(root);
+                self.built_surface_area = bounds.surface_area();
+                return index;
+            },
+        };
+        while let BvhNodeKind::Branch(left, right) = self.nodes[current].kind{
+            let growth = |node: usize| self.nodes[node].bounds.union(&bounds).surface_area() - self.nodes[node].bounds.surface_area();
+            current = if growth(left) <= growth(right) {left} else {right};
+        }
+        let mut objects = match &self.nodes[current].kind{
+            BvhNodeKind::Leaf(objects) => objects.clone(),
+            BvhNodeKind::Branch(_, _) => unreachable!(),
+        };
+        objects.push(index);
+        self.fill_node(current, &mut objects);
+        self.refit_upwards(self.nodes[current].parent);
+        index
+    }
+
+    pub fn needs_rebuild(&self) -> bool{
+        match self.root_bounds(){
+            Some(bounds) => bounds.surface_area() > self.built_surface_area.max(f64::EPSILON) * REBUILD_GROWTH,
+            None => false,
+        }
+    }
+
+    //walks the tree, descending into nodes whose bounds pass the test and collecting the objects
+    //in passing leaves whose own bounds also pass
+    fn collect(&self, test: impl Fn(&BoundingBox) -> bool) -> Vec<usize>{
+        let mut found = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node_index) = stack.pop(){
+            let node = &self.nodes[node_index];
+            if !test(&node.bounds){
+                continue;
+            }
+            match &node.kind{
+                BvhNodeKind::Leaf(objects) => {
+                    for object in objects.iter(){
+                        if test(&self.object_bounds[*object].unwrap()){
+                            found.push(*object);
+                        }
+                    }
+                },
+                BvhNodeKind::Branch(left, right) => {
+                    stack.push(*left);
+                    stack.push(*right);
+                },
+            }
+        }
+        found.sort();
+        found
+    }
+
+    pub fn query_box(&self, bounds: &BoundingBox) -> Vec<usize>{
+        self.collect(|node_bounds| node_bounds.intersects(bounds))
+    }
+
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<usize>{
+        self.collect(|node_bounds| frustum.intersects_box(node_bounds))
+    }
+
+    //objects whose bounds the ray passes through within max_distance, nearest entry first. Only
+    //the bounds are tested, callers check the actual geometry of the candidates.
+    pub fn query_ray(&self, origin: &na::Vector3<f64>, direction: &na::Vector3<f64>, max_distance: f64) -> Vec<(usize, f64)>{
+        let hits_ray = |bounds: &BoundingBox| match bounds.ray_intersection(origin, direction){
+            Some((entry, _)) => entry <= max_distance,
+            None => false,
+        };
+        let mut hits: Vec<(usize, f64)> = self.collect(hits_ray).into_iter()
+            .map(|object| (object, self.object_bounds[object].unwrap().ray_intersection(origin, direction).unwrap().0))
+            .collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits
+    }
+
+    //Branch and bound nearest neighbour. exact_distance gives the true distance from the position
+    //to an object and must never be smaller than the distance to that object's bounds.
+    pub fn nearest(&self, position: &na::Vector3<f64>, exact_distance: impl Fn(usize) -> f64) -> Option<(usize, f64)>{
+        let mut best: Option<(usize, f64)> = None;
+        let mut stack: Vec<(usize, f64)> = self.root.into_iter()
+            .map(|root| (root, self.nodes[root].bounds.distance_to(position)))
+            .collect();
+        while let Some((node_index, lower_bound)) = stack.pop(){
+            if let Some((_, best_distance)) = best{
+                if lower_bound >= best_distance{
+                    continue;
+                }
+            }
+            match &self.nodes[node_index].kind{
+                BvhNodeKind::Leaf(objects) => {
+                    for object in objects.iter(){
+                        let distance = exact_distance(*object);
+                        if best.map_or(true, |(_, best_distance)| distance < best_distance){
+                            best = Some((*object, distance));
+                        }
+                    }
+                },
+                BvhNodeKind::Branch(left, right) => {
+                    let left_distance = self.nodes[*left].bounds.distance_to(position);
+                    let right_distance = self.nodes[*right].bounds.distance_to(position);
+                    //closer child goes on top so it is searched first
+                    if left_distance < right_distance{
+                        stack.push((*right, right_distance));
+                        stack.push((*left, left_distance));
+                    }else{
+                        stack.push((*left, left_distance));
+                        stack.push((*right, right_distance));
+                    }
+                },
+            }
+        }
+        best
+    }
+
+    //nearest object measured to its points, good enough for points and lines and for picking
+    //vertices of larger objects
+    pub fn nearest_by_points<T: Point_Construct>(&self, objects: &Vec<T>, position: &na::Vector3<f64>) -> Option<(usize, f64)>{
+        self.nearest(position, |index| {
+            objects[index].get_points().iter()
+                .map(|point| (point.xyz() - position).norm())
+                .fold(f64::INFINITY, f64::min)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //small LCG so the layouts are the same every run, coordinates between -20 and 20
+    fn random_coordinates(seed: u64) -> impl FnMut() -> f64{
+        let mut state = seed;
+        move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 11) as f64 / (1u64 << 53) as f64) * 40.0 - 20.0
+        }
+    }
+    fn unit_box_at(corner: na::Vector3<f64>) -> BoundingBox{
+        BoundingBox::new(corner, corner + na::Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn inserted_objects_are_found_like_built_ones(){
+        let mut random_coordinate = random_coordinates(3);
+        let mut bounds = vec![];
+        let mut bvh = Bvh::new();
+        for i in 0..200{
+            let corner = na::Vector3::new(random_coordinate(), random_coordinate(), random_coordinate());
+            //a few objects without bounds stay out of the tree
+            let object_bounds = if i % 17 == 0 {None} else {Some(unit_box_at(corner))};
+            bounds.push(object_bounds);
+            assert_eq!(bvh.insert_object(object_bounds), i);
+        }
+        let built = Bvh::from_bounds(bounds.clone());
+        for _ in 0..100{
+            let corner = na::Vector3::new(random_coordinate(), random_coordinate(), random_coordinate());
+            let query = BoundingBox::new(corner, corner + na::Vector3::new(5.0, 5.0, 5.0));
+            let every: Vec<usize> = (0..200).filter(|i| bounds[*i].map_or(false, |object_bounds| object_bounds.intersects(&query))).collect();
+            assert_eq!(bvh.query_box(&query), every);
+            assert_eq!(built.query_box(&query), every);
+        }
+    }
+
+    #[test]
+    fn rays_find_boxes_nearest_first(){
+        //a row of boxes along x with gaps between them, and one off to the side
+        let mut bounds: Vec<Option<BoundingBox>> = (0..6).map(|index| Some(unit_box_at(na::Vector3::new(3.0 * index as f64, 0.0, 0.0)))).collect();
+        bounds.push(Some(unit_box_at(na::Vector3::new(4.0, 5.0, 0.0))));
+        //reversed so the tree order is not the distance order
+        bounds.reverse();
+        let bvh = Bvh::from_bounds(bounds);
+        let origin = na::Vector3::new(-2.0, 0.5, 0.5);
+        //the fourth box starts at 11, past the furthest distance asked for
+        let hits = bvh.query_ray(&origin, &na::Vector3::x(), 10.0);
+        assert_eq!(hits.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![6, 5, 4]);
+        for ((_, entry), expected) in hits.iter().zip([2.0, 5.0, 8.0].iter()){
+            assert!((entry - expected).abs() < 1e-12);
+        }
+        assert_eq!(bvh.query_ray(&origin, &na::Vector3::x(), 100.0).len(), 6);
+        //pointing away finds nothing
+        assert!(bvh.query_ray(&origin, &-na::Vector3::x(), 100.0).is_empty());
+    }
+
+    #[test]
+    fn frustum_query_matches_every_box_it_takes_in(){
+        let mut random_coordinate = random_coordinates(11);
+        let bounds: Vec<Option<BoundingBox>> = (0..150).map(|_| Some(unit_box_at(na::Vector3::new(random_coordinate(), random_coordinate(), random_coordinate())))).collect();
+        let bvh = Bvh::from_bounds(bounds.clone());
+        //the box from -5 to 5 on every axis as six inward facing planes
+        let frustum = Frustum{
+            planes: vec![
+                (na::Vector3::x(), 5.0), (-na::Vector3::x(), 5.0),
+                (na::Vector3::y(), 5.0), (-na::Vector3::y(), 5.0),
+                (na::Vector3::z(), 5.0), (-na::Vector3::z(), 5.0),
+            ],
+        };
+        let query = BoundingBox::new(na::Vector3::new(-5.0, -5.0, -5.0), na::Vector3::new(5.0, 5.0, 5.0));
+        let every: Vec<usize> = (0..bounds.len()).filter(|i| bounds[*i].unwrap().intersects(&query)).collect();
+        assert!(!every.is_empty());
+        assert_eq!(bvh.query_frustum(&frustum), every);
+    }
+
+    #[test]
+    fn nearest_object_is_the_closest_by_brute_force(){
+        let mut random_coordinate = random_coordinates(7);
+        let points: Vec<Point> = (0..120).map(|_| Point::from_xyz(na::Vector3::new(random_coordinate(), random_coordinate(), random_coordinate()))).collect();
+        let bvh = Bvh::build(&points);
+        for _ in 0..50{
+            let position = na::Vector3::new(random_coordinate(), random_coordinate(), random_coordinate());
+            let distances: Vec<f64> = points.iter().map(|point| (point.xyz() - position).norm()).collect();
+            let closest = (0..points.len()).min_by(|one, two| distances[*one].total_cmp(&distances[*two])).unwrap();
+            let (found, distance) = bvh.nearest_by_points(&points, &position).unwrap();
+            assert_eq!(found, closest);
+            assert!((distance - distances[closest]).abs() < 1e-12);
+            assert_eq!(bvh.nearest(&position, |index| distances[index]).map(|(index, _)| index), Some(closest));
+        }
+        assert!(Bvh::new().nearest(&na::Vector3::zeros(), |_| 0.0).is_none());
+    }
+}
@@ -0,0 +1,525 @@
+//Collision checks between parts. The broad phase puts every part's world bounds in a Bvh and only
+//parts whose boxes overlap go on to the narrow phase, GJK to tell whether two convex pieces meet
+//and EPA to find how far they go into one another. A convex part is one piece, so two of them get
+//their true depth. A part that is not convex is checked side by side instead, each side split into
+//convex pieces, so the notch of an L shaped part stays empty. Its depth is then how far the other
+//part has to move to clear the deepest side it crosses, and a part wholly inside it (no sides
+//crossing) is caught by a point of it lying inside the solid.
+use super::*;
+
+const MAX_ITERATIONS: usize = 64;
+
+//a part's corner points and convex pieces in its node's local coordinates, posed with the node's
+//world transform
+#[derive(Clone,Debug)]
+pub struct CollisionBody{
+    pub node: NodeId,
+    points: Vec<Point>,
+    //the whole part for convex parts, otherwise the convex pieces of its sides
+    pieces: Vec<Vec<Point>>,
+    convex: bool,
+    //kept for telling whether a point is inside a part that is not convex
+    sides: Vec<Surface>,
+}
+impl CollisionBody{
+    //None for objects without points
+    pub fn new(node: NodeId, object: &PerspectiveObject) -> Option<Self>{
+        let points = object.get_points();
+        if points.is_empty(){
+            return None;
+        }
+        let sides = object.get_sides();
+        let convex = is_convex(&sides, &points);
+        let pieces = if convex{
+            vec![points.clone()]
+        }else{
+            sides.iter().flat_map(|side| convex_pieces(side)).collect()
+        };
+        Some(CollisionBody{
+            node: node,
+            points: points,
+            pieces: pieces,
+            convex: convex,
+            sides: sides,
+        })
+    }
+    pub fn world_points(&self, world: &Transform) -> Vec<na::Vector3<f64>>{
+        self.points.iter().map(|point| world.apply_point(point).xyz()).collect()
+    }
+    fn world_pieces(&self, world: &Transform) -> Vec<Vec<na::Vector3<f64>>>{
+        self.pieces.iter().map(|piece| piece.iter().map(|point| world.apply_point(point).xyz()).collect()).collect()
+    }
+    //Whether the position is inside the closed part, by the number of sides a ray from it crosses.
+    //The ray leans off every axis so it does not run along the edges of boxy parts.
+    fn contains(&self, world: &Transform, position: &na::Vector3<f64>) -> bool{
+        let to_local = match world.inverse(){
+            Some(inverse) => inverse,
+            None => return false,
+        };
+        let ray = Ray::new(to_local.apply_point(&Point::from_xyz(*position)), Direction::new(0.5377, 0.3188, 0.7807));
+        let crossings = self.sides.iter().filter(|side| ray_surface_intersection(&ray, side).is_some()).count();
+        crossings % 2 == 1
+    }
+}
+
+//every corner on or behind the plane of every side, whichever way the sides are wound
+pub(crate) fn is_convex(sides: &Vec<Surface>, points: &Vec<Point>) -> bool{
+    sides.iter().all(|side| {
+        let corners = side.get_key_points();
+        let normal = side.normal();
+        if corners.is_empty() || normal.norm() < 0.5{
+            return true;
+        }
+        let heights: Vec<f64> = points.iter().map(|point| normal.dot(&(point.xyz() - corners[0].xyz()))).collect();
+        heights.iter().all(|height| *height <= model_tolerance()) || heights.iter().all(|height| *height >= -model_tolerance())
+    })
+}
+
+//The side as convex polygons: itself when it is convex, otherwise the triangles ear clipping cuts
+//it into
+fn convex_pieces(side: &Surface) -> Vec<Vec<Point>>{
+    let corners = side.get_key_points().clone();
+    let normal = side.normal();
+    if corners.len() <= 3 || normal.norm() < 0.5{
+        return vec![corners];
+    }
+    //how far the corner turns, positive where it turns the same way as the winding
+    let turn = |before: &Point, corner: &Point, after: &Point| (*corner - *before).to_vector().cross(&(*after - *corner).to_vector()).dot(&normal);
+    let count = corners.len();
+    if (0..count).all(|i| turn(&corners[(i + count - 1) % count], &corners[i], &corners[(i + 1) % count]) >= -model_tolerance()){
+        return vec![corners];
+    }
+    let mut remaining = corners;
+    let mut triangles = vec![];
+    while remaining.len() > 3{
+        let count = remaining.len();
+        let ear = (0..count).find(|i| {
+            let (before, corner, after) = (remaining[(i + count - 1) % count], remaining[*i], remaining[(i + 1) % count]);
+            if turn(&before, &corner, &after) <= model_tolerance(){
+                return false;
+            }
+            let triangle = Surface::new(vec![before, corner, after]);
+            !remaining.iter().any(|other| !other.coincident(&before) && !other.coincident(&corner) && !other.coincident(&after) && polygon_contains(&triangle, other))
+        });
+        //only a self intersecting outline has no ear left
+        let ear = match ear{
+            Some(ear) => ear,
+            None => break,
+        };
+        triangles.push(vec![remaining[(ear + count - 1) % count], remaining[ear], remaining[(ear + 1) % count]]);
+        remaining.remove(ear);
+    }
+    triangles.push(remaining);
+    triangles
+}
+
+//two parts that overlap
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Contact{
+    pub first: NodeId,
+    pub second: NodeId,
+    //how far the second part has to move along the normal to come clear, 0 when only touching
+    pub depth: f64,
+    //unit length, world coordinates
+    pub normal: na::Vector3<f64>,
+    //roughly the middle of the overlap
+    pub point: na::Vector3<f64>,
+}
+impl Contact{
+    //more than touching
+    pub fn is_penetrating(&self) -> bool{
+        self.depth > model_tolerance()
+    }
+}
+
+//the first configuration in a sweep where parts go into one another
+#[derive(Clone,Debug)]
+pub struct SweepCollision{
+    //position of the configuration in the sweep
+    pub step: usize,
+    pub values: Vec<f64>,
+    pub contacts: Vec<Contact>,
+}
+
+fn support(points: &Vec<na::Vector3<f64>>, direction: &na::Vector3<f64>) -> na::Vector3<f64>{
+    *points.iter().max_by(|one, two| one.dot(direction).total_cmp(&two.dot(direction))).unwrap()
+}
+//middle of the points furthest along the direction, the centre of a face lying across it
+fn support_centre(points: &Vec<na::Vector3<f64>>, direction: &na::Vector3<f64>) -> na::Vector3<f64>{
+    let furthest = support(points, direction).dot(direction);
+    let feature: Vec<&na::Vector3<f64>> = points.iter().filter(|point| point.dot(direction) >= furthest - model_tolerance()).collect();
+    feature.iter().cloned().sum::<na::Vector3<f64>>() / feature.len() as f64
+}
+//furthest point of first - second along the direction
+fn minkowski_support(first: &Vec<na::Vector3<f64>>, second: &Vec<na::Vector3<f64>>, direction: &na::Vector3<f64>) -> na::Vector3<f64>{
+    support(first, direction) - support(second, &-direction)
+}
+
+//Narrows the simplex (newest point first) to the part nearest the origin and sets the direction to
+//search next. True once the simplex holds the origin.
+fn next_simplex(simplex: &mut Vec<na::Vector3<f64>>, direction: &mut na::Vector3<f64>) -> bool{
+    let triple = |one: &na::Vector3<f64>, two: &na::Vector3<f64>| one.cross(two).cross(one);
+    let a = simplex[0];
+    let origin = -a;
+    match simplex.len(){
+        2 => {
+            let ab = simplex[1] - a;
+            if ab.dot(&origin) > 0.0{
+                *direction = triple(&ab, &origin);
+            }else{
+                *simplex = vec![a];
+                *direction = origin;
+            }
+        },
+        3 => {
+            let (b, c) = (simplex[1], simplex[2]);
+            let (ab, ac) = (b - a, c - a);
+            let normal = ab.cross(&ac);
+            if normal.cross(&ac).dot(&origin) > 0.0{
+                if ac.dot(&origin) > 0.0{
+                    *simplex = vec![a, c];
+                    *direction = triple(&ac, &origin);
+                }else{
+                    *simplex = vec![a, b];
+                    return next_simplex(simplex, direction);
+                }
+            }else if ab.cross(&normal).dot(&origin) > 0.0{
+                *simplex = vec![a, b];
+                return next_simplex(simplex, direction);
+            }else if normal.dot(&origin) > 0.0{
+                *direction = normal;
+            }else{
+                *simplex = vec![a, c, b];
+                *direction = -normal;
+            }
+        },
+        _ => {
+            let (b, c, d) = (simplex[1], simplex[2], simplex[3]);
+            let (ab, ac, ad) = (b - a, c - a, d - a);
+            for (one, two, three) in [(b, c, ab.cross(&ac)), (c, d, ac.cross(&ad)), (d, b, ad.cross(&ab))]{
+                if three.dot(&origin) > 0.0{
+                    *simplex = vec![a, one, two];
+                    return next_simplex(simplex, direction);
+                }
+            }
+            return true;
+        },
+    }
+    //the origin lies on the simplex itself
+    direction.norm_squared() <= f64::EPSILON * f64::EPSILON
+}
+
+//GJK, the simplex of first - second holding the origin when the hulls meet
+fn gjk(first: &Vec<na::Vector3<f64>>, second: &Vec<na::Vector3<f64>>) -> Option<Vec<na::Vector3<f64>>>{
+    let start = first[0] - second[0];
+    let mut direction = if start.norm() > f64::EPSILON {-start} else {na::Vector3::x()};
+    let mut simplex = vec![minkowski_support(first, second, &-direction)];
+    direction = -simplex[0];
+    for _ in 0..MAX_ITERATIONS{
+        if direction.norm_squared() <= f64::EPSILON * f64::EPSILON{
+            return Some(simplex);
+        }
+        let point = minkowski_support(first, second, &direction);
+        if point.dot(&direction) < 0.0{
+            return None;
+        }
+        simplex.insert(0, point);
+        if next_simplex(&mut simplex, &mut direction){
+            return Some(simplex);
+        }
+    }
+    //cycling on a boundary, the hulls are as good as touching
+    Some(simplex)
+}
+
+//distance from the point to the line, plane or point the simplex spans
+fn distance_to_span(simplex: &Vec<na::Vector3<f64>>, point: &na::Vector3<f64>) -> f64{
+    let offset = point - simplex[0];
+    match simplex.len(){
+        1 => offset.norm(),
+        2 => {
+            let along = simplex[1] - simplex[0];
+            match along.try_normalize(f64::EPSILON){
+                Some(unit) => (offset - unit * offset.dot(&unit)).norm(),
+                None => offset.norm(),
+            }
+        },
+        _ => {
+            let normal = (simplex[1] - simplex[0]).cross(&(simplex[2] - simplex[0]));
+            match normal.try_normalize(f64::EPSILON){
+                Some(unit) => offset.dot(&unit).abs(),
+                None => offset.norm(),
+            }
+        },
+    }
+}
+
+//Grows a simplex that ended on a point, edge or face (parts only touching) into a tetrahedron. None
+//when first - second is flat, where the depth is zero.
+fn complete_tetrahedron(first: &Vec<na::Vector3<f64>>, second: &Vec<na::Vector3<f64>>, simplex: &mut Vec<na::Vector3<f64>>) -> Option<()>{
+    while simplex.len() < 4{
+        let mut directions = vec![na::Vector3::x(), na::Vector3::y(), na::Vector3::z()];
+        if simplex.len() == 3{
+            directions.push((simplex[1] - simplex[0]).cross(&(simplex[2] - simplex[0])));
+        }
+        let best = directions.iter().flat_map(|direction| [*direction, -direction])
+            .map(|direction| minkowski_support(first, second, &direction))
+            .max_by(|one, two| distance_to_span(simplex, one).total_cmp(&distance_to_span(simplex, two)))?;
+        if distance_to_span(simplex, &best) <= model_tolerance(){
+            return None;
+        }
+        simplex.push(best);
+    }
+    Some(())
+}
+
+//a face of the EPA polytope with its distance from the origin
+struct PolytopeFace{
+    corners: [usize; 3],
+    normal: na::Vector3<f64>,
+    distance: f64,
+}
+//The normal is turned to point away from inside, a point within the polytope. The origin cannot be
+//used for that as it may sit on the face when the parts only just meet.
+fn polytope_face(vertices: &Vec<na::Vector3<f64>>, corners: [usize; 3], inside: &na::Vector3<f64>) -> Option<PolytopeFace>{
+    let [a, b, c] = corners;
+    let normal = (vertices[b] - vertices[a]).cross(&(vertices[c] - vertices[a])).try_normalize(f64::EPSILON)?;
+    Some(if normal.dot(&(vertices[a] - inside)) < 0.0{
+        PolytopeFace{corners: [a, c, b], normal: -normal, distance: -normal.dot(&vertices[a])}
+    }else{
+        PolytopeFace{corners: corners, normal: normal, distance: normal.dot(&vertices[a])}
+    })
+}
+
+//EPA, the depth and the way out of the face of first - second nearest the origin
+fn epa(first: &Vec<na::Vector3<f64>>, second: &Vec<na::Vector3<f64>>, simplex: Vec<na::Vector3<f64>>) -> (f64, na::Vector3<f64>){
+    let mut vertices = simplex;
+    //the polytope only grows so the first tetrahedron's centre stays inside
+    let inside = vertices.iter().sum::<na::Vector3<f64>>() / 4.0;
+    let mut faces: Vec<PolytopeFace> = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]].into_iter()
+        .filter_map(|corners| polytope_face(&vertices, corners, &inside))
+        .collect();
+    let mut best = (0.0, na::Vector3::x());
+    for _ in 0..MAX_ITERATIONS{
+        let nearest = match faces.iter().min_by(|one, two| one.distance.total_cmp(&two.distance)){
+            Some(face) => face,
+            None => break,
+        };
+        best = (nearest.distance, nearest.normal);
+        let point = minkowski_support(first, second, &nearest.normal);
+        if point.dot(&nearest.normal) - nearest.distance <= model_tolerance(){
+            break;
+        }
+        //faces the new point sees are replaced by a fan from their outline to it
+        let mut outline: Vec<(usize, usize)> = vec![];
+        faces.retain(|face| {
+            if face.normal.dot(&(point - vertices[face.corners[0]])) <= 0.0{
+                return true;
+            }
+            for edge in 0..3{
+                let (from, to) = (face.corners[edge], face.corners[(edge + 1) % 3]);
+                match outline.iter().position(|other| *other == (to, from)){
+                    Some(shared) => {outline.remove(shared);},
+                    None => outline.push((from, to)),
+                }
+            }
+            false
+        });
+        vertices.push(point);
+        let newest = vertices.len() - 1;
+        for (from, to) in outline.into_iter(){
+            if let Some(face) = polytope_face(&vertices, [from, to, newest], &inside){
+                faces.push(face);
+            }
+        }
+    }
+    best
+}
+
+//whether two convex pieces meet, with the depth and way out when they do
+fn piece_contact(first: &Vec<na::Vector3<f64>>, second: &Vec<na::Vector3<f64>>) -> Option<(f64, na::Vector3<f64>)>{
+    let mut simplex = gjk(first, second)?;
+    Some(match complete_tetrahedron(first, second, &mut simplex){
+        Some(()) => epa(first, second, simplex),
+        None => (0.0, (support(first, &na::Vector3::x()) - support(second, &na::Vector3::x())).try_normalize(f64::EPSILON).unwrap_or(na::Vector3::x())),
+    })
+}
+
+//Whether the two parts meet, and if so by how much: the deepest of their pieces' contacts. Each
+//body is posed by its node's world transform.
+pub fn check_contact(first: &CollisionBody, first_world: &Transform, second: &CollisionBody, second_world: &Transform) -> Option<Contact>{
+    let first_pieces = first.world_pieces(first_world);
+    let second_pieces = second.world_pieces(second_world);
+    let piece_bounds = |piece: &Vec<na::Vector3<f64>>| BoundingBox::from_points(&piece.iter().map(|point| Point::from_xyz(*point)).collect());
+    let second_bounds: Vec<Option<BoundingBox>> = second_pieces.iter().map(piece_bounds).collect();
+
+    let mut deepest: Option<(f64, na::Vector3<f64>, usize, usize)> = None;
+    for (one, first_piece) in first_pieces.iter().enumerate(){
+        let first_bounds = piece_bounds(first_piece);
+        for (two, second_piece) in second_pieces.iter().enumerate(){
+            let near = match (first_bounds, second_bounds[two]){
+                (Some(one), Some(two)) => one.intersects(&two),
+                _ => false,
+            };
+            if !near{
+                continue;
+            }
+            if let Some((depth, normal)) = piece_contact(first_piece, second_piece){
+                if deepest.map_or(true, |(best, ..)| depth > best){
+                    deepest = Some((depth, normal, one, two));
+                }
+            }
+        }
+    }
+    let (depth, normal, first_piece, second_piece) = match deepest{
+        Some(found) => found,
+        None => return contained_contact(first, first_world, second, second_world),
+    };
+    Some(Contact{
+        first: first.node,
+        second: second.node,
+        depth: depth,
+        normal: normal,
+        point: (support_centre(&first_pieces[first_piece], &normal) + support_centre(&second_pieces[second_piece], &-normal)) / 2.0,
+    })
+}
+
+//No pieces meet, but one part may still sit wholly inside a part that is not convex (a convex one
+//is a single solid piece and would have met it). The depth is then taken from the two hulls.
+fn contained_contact(first: &CollisionBody, first_world: &Transform, second: &CollisionBody, second_world: &Transform) -> Option<Contact>{
+    let first_points = first.world_points(first_world);
+    let second_points = second.world_points(second_world);
+    //a ray grazing an edge can be miscounted, so the points vote
+    let mostly_inside = |body: &CollisionBody, world: &Transform, points: &Vec<na::Vector3<f64>>| {
+        !body.convex && 2 * points.iter().filter(|point| body.contains(world, point)).count() > points.len()
+    };
+    let inside = mostly_inside(second, second_world, &first_points) || mostly_inside(first, first_world, &second_points);
+    if !inside{
+        return None;
+    }
+    let (depth, normal) = piece_contact(&first_points, &second_points)?;
+    Some(Contact{
+        first: first.node,
+        second: second.node,
+        depth: depth,
+        normal: normal,
+        point: (support_centre(&first_points, &normal) + support_centre(&second_points, &-normal)) / 2.0,
+    })
+}
+
+//Every pair of bodies that meet, touching included, in the given poses. Pairs in ignored
+//(either way round) are left out, like parts pinned to one another that always overlap at the
+//joint. Bodies whose node has no pose are skipped.
+pub fn find_contacts(bodies: &[CollisionBody], pose: impl Fn(NodeId) -> Option<Transform>, ignored: &[(NodeId, NodeId)]) -> Vec<Contact>{
+    let worlds: Vec<Option<Transform>> = bodies.iter().map(|body| pose(body.node)).collect();
+    let bounds: Vec<Option<BoundingBox>> = bodies.iter().zip(worlds.iter())
+        .map(|(body, world)| world.and_then(|world| BoundingBox::from_points(&body.points.iter().map(|point| world.apply_point(point)).collect())))
+        .collect();
+    let broad_phase = Bvh::from_bounds(bounds.clone());
+
+    let mut contacts = vec![];
+    for (index, body) in bodies.iter().enumerate(){
+        let (world, bounds) = match (worlds[index], bounds[index]){
+            (Some(world), Some(bounds)) => (world, bounds),
+            _ => continue,
+        };
+        for other in broad_phase.query_box(&bounds).into_iter().filter(|other| *other > index){
+            let pair = (body.node, bodies[other].node);
+            if pair.0 == pair.1 || ignored.iter().any(|skip| *skip == pair || *skip == (pair.1, pair.0)){
+                continue;
+            }
+            if let Some(contact) = check_contact(body, &world, &bodies[other], &worlds[other].unwrap()){
+                contacts.push(contact);
+            }
+        }
+    }
+    contacts
+}
+
+//Poses the bodies for each configuration in turn and stops at the first where any two go into one
+//another. Nodes the tree does not move are posed by base. None if the whole sweep is clear.
+pub fn first_collision(tree: &KinematicTree, joints: &Vec<Joint>, bodies: &[CollisionBody], configurations: &[Vec<f64>], ignored: &[(NodeId, NodeId)], base: impl Fn(NodeId) -> Option<Transform>) -> Result<Option<SweepCollision>, KinematicsError>{
+    for (step, values) in configurations.iter().enumerate(){
+        let world = tree.forward(joints, values, &base)?;
+        let contacts: Vec<Contact> = find_contacts(bodies, |node| world.get(&node).cloned().or_else(|| base(node)), ignored)
+            .into_iter().filter(|contact| contact.is_penetrating()).collect();
+        if !contacts.is_empty(){
+            return Ok(Some(SweepCollision{
+                step: step,
+                values: values.clone(),
+                contacts: contacts,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_shapes::prism;
+
+    fn cube(min: f64, max: f64) -> PerspectiveObject{
+        prism(&[(min, min), (max, min), (max, max), (min, max)], min, max)
+    }
+
+    fn moved(x: f64, y: f64, z: f64) -> Transform{
+        Transform::translation(Direction::new(x, y, z))
+    }
+
+    #[test]
+    fn overlapping_cubes_report_depth_and_normal(){
+        let body = CollisionBody::new(1, &cube(0.0, 1.0)).unwrap();
+        let other = CollisionBody::new(2, &cube(0.0, 1.0)).unwrap();
+        let contact = check_contact(&body, &Transform::identity(), &other, &moved(0.8, 0.1, 0.05)).unwrap();
+        assert!((contact.depth - 0.2).abs() < 1e-9);
+        //the second cube comes clear by moving on along x
+        assert!((contact.normal - na::Vector3::x()).norm() < 1e-9);
+        assert!(contact.is_penetrating());
+        assert!((contact.point.x - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn touching_cubes_have_no_depth(){
+        let body = CollisionBody::new(1, &cube(0.0, 1.0)).unwrap();
+        let other = CollisionBody::new(2, &cube(0.0, 1.0)).unwrap();
+        let contact = check_contact(&body, &Transform::identity(), &other, &moved(1.0, 0.3, 0.0)).unwrap();
+        assert!(contact.depth.abs() <= model_tolerance());
+        assert!(!contact.is_penetrating());
+    }
+
+    #[test]
+    fn separated_cubes_do_not_meet(){
+        let body = CollisionBody::new(1, &cube(0.0, 1.0)).unwrap();
+        let other = CollisionBody::new(2, &cube(0.0, 1.0)).unwrap();
+        assert!(check_contact(&body, &Transform::identity(), &other, &moved(1.5, 0.0, 0.0)).is_none());
+        assert!(check_contact(&body, &Transform::identity(), &other, &moved(0.9, 1.2, -0.5)).is_none());
+        assert!(find_contacts(&[body, other], |node| Some(moved(2.0 * node as f64, 0.0, 0.0)), &[]).is_empty());
+    }
+
+    #[test]
+    fn notch_of_an_l_shaped_part_is_empty(){
+        let l_shape = CollisionBody::new(1, &prism(&[(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)], 0.0, 1.0)).unwrap();
+        let small = CollisionBody::new(2, &cube(0.0, 0.4)).unwrap();
+        //in the notch, inside the L's hull but clear of the L itself
+        assert!(check_contact(&l_shape, &Transform::identity(), &small, &moved(1.3, 1.3, 0.3)).is_none());
+        //through the inner corner
+        assert!(check_contact(&l_shape, &Transform::identity(), &small, &moved(0.8, 0.8, 0.3)).unwrap().is_penetrating());
+        //wholly inside the L, no sides cross
+        assert!(check_contact(&l_shape, &Transform::identity(), &small, &moved(0.2, 0.2, 0.3)).unwrap().is_penetrating());
+        assert!(check_contact(&small, &moved(1.5, 0.2, 0.3), &l_shape, &Transform::identity()).unwrap().is_penetrating());
+    }
+
+    #[test]
+    fn gap_of_a_u_shaped_part_is_empty(){
+        //tall enough that a ray from the gap runs through an arm before leaving
+        let u_shape = CollisionBody::new(1, &prism(&[(0.0, 0.0), (3.0, 0.0), (3.0, 2.0), (2.0, 2.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)], 0.0, 3.0)).unwrap();
+        let small = CollisionBody::new(2, &cube(0.0, 0.3)).unwrap();
+        assert!(check_contact(&u_shape, &Transform::identity(), &small, &moved(1.35, 1.35, 0.3)).is_none());
+        assert!(check_contact(&small, &moved(1.35, 1.35, 0.3), &u_shape, &Transform::identity()).is_none());
+        //wholly inside an arm
+        assert!(check_contact(&u_shape, &Transform::identity(), &small, &moved(2.35, 1.35, 0.3)).unwrap().is_penetrating());
+        //across the inside of an arm
+        assert!(check_contact(&u_shape, &Transform::identity(), &small, &moved(1.85, 1.35, 0.3)).unwrap().is_penetrating());
+    }
+}
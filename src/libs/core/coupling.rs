@@ -0,0 +1,180 @@
+//Couplings tie one joint value to others, like meshing gears or a lead screw. Every kind is a
+//linear relation
+//
+//    follower = offset + sum of ratio * leader
+//
+//so the solvers treat followers as worked out from their leaders rather than as free values.
+use super::*;
+use std::f64::consts::PI;
+
+//one degree of freedom of one joint
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct JointValue{
+    pub joint: usize,
+    pub dof: usize,
+}
+impl JointValue{
+    pub fn new(joint: usize, dof: usize) -> Self{
+        JointValue{
+            joint: joint,
+            dof: dof,
+        }
+    }
+    //position in the combined value vector, None if the joint or degree of freedom does not exist
+    pub fn index(&self, joints: &Vec<Joint>) -> Option<usize>{
+        if self.dof >= joints.get(self.joint)?.degrees_of_freedom(){
+            return None;
+        }
+        Some(joints[..self.joint].iter().map(|joint| joint.degrees_of_freedom()).sum::<usize>() + self.dof)
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum CouplingKind{
+    Gear,
+    Belt,
+    RackAndPinion,
+    Screw,
+    Linear,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct JointCoupling{
+    pub name: String,
+    pub kind: CouplingKind,
+    pub follower: JointValue,
+    pub leaders: Vec<(JointValue, f64)>,
+    pub offset: f64,
+}
+impl JointCoupling{
+    //Meshing gears turn opposite ways, ratio is driver teeth over driven teeth
+    pub fn gear(name: &str, driver: JointValue, driven: JointValue, ratio: f64) -> Self{
+        JointCoupling::with_kind(name, CouplingKind::Gear, driven, vec![(driver, -ratio)], 0.0)
+    }
+    //belts and chains keep the direction, ratio is driver pulley over driven pulley
+    pub fn belt(name: &str, driver: JointValue, driven: JointValue, ratio: f64) -> Self{
+        JointCoupling::with_kind(name, CouplingKind::Belt, driven, vec![(driver, ratio)], 0.0)
+    }
+    //the rack moves the pinion's pitch radius for every radian the pinion turns
+    pub fn rack_and_pinion(name: &str, pinion: JointValue, rack: JointValue, pitch_radius: f64) -> Self{
+        JointCoupling::with_kind(name, CouplingKind::RackAndPinion, rack, vec![(pinion, pitch_radius)], 0.0)
+    }
+    //the nut moves one pitch for every turn of the screw
+    pub fn screw(name: &str, screw: JointValue, nut: JointValue, pitch: f64) -> Self{
+        JointCoupling::with_kind(name, CouplingKind::Screw, nut, vec![(screw, pitch / (2.0 * PI))], 0.0)
+    }
+    pub fn linear(name: &str, follower: JointValue, leaders: Vec<(JointValue, f64)>, offset: f64) -> Self{
+        JointCoupling::with_kind(name, CouplingKind::Linear, follower, leaders, offset)
+    }
+    fn with_kind(name: &str, kind: CouplingKind, follower: JointValue, leaders: Vec<(JointValue, f64)>, offset: f64) -> Self{
+        JointCoupling{
+            name: name.to_string(),
+            kind: kind,
+            follower: follower,
+            leaders: leaders,
+            offset: offset,
+        }
+    }
+}
+
+//a coupling with its values as positions in the combined value vector
+#[derive(Clone,Debug,PartialEq)]
+pub struct ResolvedCoupling{
+    pub follower: usize,
+    pub leaders: Vec<(usize, f64)>,
+    pub offset: f64,
+}
+
+//Resolves the couplings against the joints and orders them so every leader is worked out before
+//anything following it. A coupling naming a value that does not exist, following itself, following
+//a value another coupling already sets or taking part in a ring of couplings is refused.
+pub fn resolve_couplings(joints: &Vec<Joint>, couplings: &[JointCoupling]) -> Result<Vec<ResolvedCoupling>, KinematicsError>{
+    let mut resolved = vec![];
+    for (index, coupling) in couplings.iter().enumerate(){
+        let invalid = KinematicsError::InvalidCoupling(index);
+        let follower = coupling.follower.index(joints).ok_or(invalid.clone())?;
+        let mut leaders = vec![];
+        for (leader, ratio) in coupling.leaders.iter(){
+            let leader = leader.index(joints).ok_or(invalid.clone())?;
+            if leader == follower{
+                return Err(invalid);
+            }
+            leaders.push((leader, *ratio));
+        }
+        if resolved.iter().any(|(_, other): &(usize, ResolvedCoupling)| other.follower == follower){
+            return Err(invalid);
+        }
+        resolved.push((index, ResolvedCoupling{
+            follower: follower,
+            leaders: leaders,
+            offset: coupling.offset,
+        }));
+    }
+
+    let mut ordered: Vec<ResolvedCoupling> = vec![];
+    while !resolved.is_empty(){
+        let ready = resolved.iter().position(|(_, coupling)| coupling.leaders.iter()
+            .all(|(leader, _)| !resolved.iter().any(|(_, other)| other.follower == *leader)));
+        match ready{
+            Some(position) => ordered.push(resolved.remove(position).1),
+            //everything left waits on something else that is left
+            None => return Err(KinematicsError::InvalidCoupling(resolved[0].0)),
+        }
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //three revolute joints in a chain, one value each
+    fn chain() -> Vec<Joint>{
+        (0..3).map(|index| Joint::new("", JointType::Revolute, index, Transform::identity(), index + 1, Transform::identity()).unwrap()).collect()
+    }
+    fn value(joint: usize) -> JointValue{
+        JointValue::new(joint, 0)
+    }
+
+    #[test]
+    fn chained_couplings_put_leaders_first(){
+        let joints = chain();
+        //given follower first, the second coupling sets the leader of the first
+        let couplings = vec![JointCoupling::belt("", value(1), value(2), 2.0), JointCoupling::belt("", value(0), value(1), 2.0)];
+        let resolved = resolve_couplings(&joints, &couplings).unwrap();
+        assert_eq!(resolved.iter().map(|coupling| coupling.follower).collect::<Vec<usize>>(), vec![1, 2]);
+
+        let tree = KinematicTree::new(&joints).with_couplings(&joints, &couplings).unwrap();
+        let mut values = vec![1.0, 0.0, 0.0];
+        tree.apply_couplings(&mut values);
+        assert_eq!(values, vec![1.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn rings_of_couplings_are_refused(){
+        let joints = chain();
+        let couplings = vec![
+            JointCoupling::gear("", value(0), value(1), 1.0),
+            JointCoupling::gear("", value(1), value(2), 1.0),
+            JointCoupling::gear("", value(2), value(0), 1.0),
+        ];
+        assert!(matches!(resolve_couplings(&joints, &couplings), Err(KinematicsError::InvalidCoupling(_))));
+    }
+
+    #[test]
+    fn a_value_follows_one_coupling_only(){
+        let joints = chain();
+        let couplings = vec![JointCoupling::gear("", value(0), value(2), 1.0), JointCoupling::belt("", value(1), value(2), 1.0)];
+        assert!(matches!(resolve_couplings(&joints, &couplings), Err(KinematicsError::InvalidCoupling(1))));
+    }
+
+    #[test]
+    fn a_value_cannot_lead_itself(){
+        let joints = chain();
+        let couplings = vec![JointCoupling::linear("", value(1), vec![(value(0), 1.0), (value(1), 0.5)], 0.0)];
+        assert!(matches!(resolve_couplings(&joints, &couplings), Err(KinematicsError::InvalidCoupling(0))));
+        //nor name a joint that is not there
+        let couplings = vec![JointCoupling::gear("", value(0), value(3), 1.0)];
+        assert!(matches!(resolve_couplings(&joints, &couplings), Err(KinematicsError::InvalidCoupling(0))));
+    }
+}
@@ -0,0 +1,637 @@
+//Edge treatments (fillets and chamfers) for PerspectiveObjects. An edge is any pair of consecutive
+//key points that is shared by exactly two sides of the object. Edges are selected with a world
+//space Line whose end points are the two key points (within the model tolerance), the direction of
+//the line does not matter. The work itself happens on the local sides.
+use super::*;
+
+#[derive(Clone,Debug)]
+pub enum EdgeTreatmentError{
+    //no two consecutive key points of any side match the line
+    EdgeNotFound(Line),
+    //the edge does not belong to exactly two sides so there is no "inside" to trim into
+    NonManifoldEdge(Line),
+    //the two sides meeting at the edge are (almost) coplanar, nothing to round off
+    FlatEdge(Line),
+    //the sides meet in a valley rather than a ridge, there is no material at the edge to remove
+    ConcaveEdge(Line),
+    InvalidSize(f64),
+}
+
+#[derive(Clone,Copy,Debug)]
+pub enum EdgeTreatment{
+    //constant radius fillet, approximated by `segments` flat strips
+    Fillet{radius: f64, segments: usize},
+    //distance_one is measured along the first side found for the edge and distance_two along the
+    //second, equal distances give the usual 45 degree chamfer on a right angled edge
+    Chamfer{distance_one: f64, distance_two: f64},
+}
+
+impl PerspectiveObject{
+    //every edge shared by two or more sides, each reported once, in world space
+    pub fn get_edges(&self) -> Vec<Line>{
+        let pose = self.pose();
+        self.get_local_edges().iter().map(|edge| edge.transformed(&pose)).collect()
+    }
+
+    pub fn get_local_edges(&self) -> Vec<Line>{
+        let mut edges: Vec<Line> = vec![];
+        for side in self.sides.iter(){
+            let count = side.key_points.len();
+            for i in 0..count{
+                let start = side.key_points[i];
+                let end = side.key_points[(i + 1) % count];
+                let candidate = Line::new(start, end);
+                let already_found = edges.iter().any(|edge| same_edge(edge, &candidate));
+                if !already_found && self.sides_with_edge(&candidate).len() >= 2{
+                    edges.push(candidate);
+                }
+            }
+        }
+        edges
+    }
+
+    //closed means every edge of every side is shared with exactly one other side, i.e. the sides
+    //enclose a volume without gaps
+    pub fn is_closed(&self) -> bool{
+        for side in self.sides.iter(){
+            let count = side.key_points.len();
+            for i in 0..count{
+                let edge = Line::new(side.key_points[i], side.key_points[(i + 1) % count]);
+                if self.sides_with_edge(&edge).len() != 2{
+                    return false;
+                }
+            }
+        }
+        !self.sides.is_empty()
+    }
+
+    pub fn fillet_edges(&mut self, edges: &Vec<Line>, radius: f64, segments: usize) -> Result<(), EdgeTreatmentError>{
+        if radius <= 0.0 || !radius.is_finite(){
+            return Err(EdgeTreatmentError::InvalidSize(radius));
+        }
+        self.treat_edges(edges, EdgeTreatment::Fillet{radius: radius, segments: segments.max(1)})
+    }
+
+    pub fn chamfer_edges(&mut self, edges: &Vec<Line>, distance_one: f64, distance_two: f64) -> Result<(), EdgeTreatmentError>{
+        for distance in [distance_one, distance_two]{
+            if distance <= 0.0 || !distance.is_finite(){
+                return Err(EdgeTreatmentError::InvalidSize(distance));
+            }
+        }
+        self.treat_edges(edges, EdgeTreatment::Chamfer{distance_one: distance_one, distance_two: distance_two})
+    }
+
+    //Edges are treated one after the other on a copy so a failure part way through leaves the
+    //object untouched. Every selection is first resolved to the two sides meeting at it: treating
+    //an edge moves the corners it shares with the others, so each one is found again between its
+    //two sides, on its original line, just before it is treated.
+    pub fn treat_edges(&mut self, edges: &Vec<Line>, treatment: EdgeTreatment) -> Result<(), EdgeTreatmentError>{
+        let to_local = match self.pose().inverse(){
+            Some(inverse) => inverse,
+            None => Transform::identity(),
+        };
+        let mut selections = vec![];
+        for edge in edges.iter(){
+            let local_edge = edge.transformed(&to_local);
+            let touching = self.sides_with_edge(&local_edge);
+            match touching.len(){
+                0 => return Err(EdgeTreatmentError::EdgeNotFound(*edge)),
+                2 => selections.push((local_edge, touching[0], touching[1])),
+                _ => return Err(EdgeTreatmentError::NonManifoldEdge(*edge)),
+            }
+        }
+        let mut working_sides = self.sides.clone();
+        for (original, first, second) in selections.into_iter(){
+            let edge = match shared_edge(&working_sides, first, second, &original){
+                Some(edge) => edge,
+                None => return Err(EdgeTreatmentError::EdgeNotFound(original.transformed(&self.pose()))),
+            };
+            working_sides = treat_edge(working_sides, edge, treatment)?;
+        }
+        self.sides = working_sides.into_iter().filter(|side| side.key_points.len() >= 3).collect();
+        self.bounds_cache.invalidate();
+        Ok(())
+    }
+
+    fn sides_with_edge(&self, edge: &Line) -> Vec<usize>{
+        sides_with_edge(&self.sides, edge)
+    }
+}
+
+fn same_edge(one: &Line, other: &Line) -> bool{
+    (one.point_a.coincident(&other.point_a) && one.point_b.coincident(&other.point_b)) ||
+    (one.point_a.coincident(&other.point_b) && one.point_b.coincident(&other.point_a))
+}
+
+//returns Some(true) if the side walks the edge from start to end, Some(false) if it walks it the
+//other way round and None if the edge is not on the side
+fn edge_direction_in_side(side: &Surface, edge: &Line) -> Option<bool>{
+    let count = side.key_points.len();
+    for i in 0..count{
+        let current = side.key_points[i];
+        let next = side.key_points[(i + 1) % count];
+        if current.coincident(&edge.point_a) && next.coincident(&edge.point_b){
+            return Some(true);
+        }
+        if current.coincident(&edge.point_b) && next.coincident(&edge.point_a){
+            return Some(false);
+        }
+    }
+    None
+}
+
+//The edge between the two sides that lies on the line of the original edge. Sides keep their
+//indices while edges are treated, only new strips are added at the end.
+fn shared_edge(sides: &Vec<Surface>, first: usize, second: usize, original: &Line) -> Option<Line>{
+    let start = original.point_a.xyz();
+    let direction = (original.point_b.xyz() - start).try_normalize(f64::EPSILON)?;
+    let on_line = |point: &Point| (point.xyz() - start).cross(&direction).norm() <= model_tolerance();
+    let side = &sides[first];
+    let count = side.key_points.len();
+    for i in 0..count{
+        let (current, next) = (side.key_points[i], side.key_points[(i + 1) % count]);
+        let candidate = Line::new(current, next);
+        if on_line(&current) && on_line(&next) && edge_direction_in_side(&sides[second], &candidate).is_some(){
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+fn sides_with_edge(sides: &Vec<Surface>, edge: &Line) -> Vec<usize>{
+    let mut found: Vec<usize> = vec![];
+    for (index, side) in sides.iter().enumerate(){
+        if edge_direction_in_side(side, edge).is_some(){
+            found.push(index);
+        }
+    }
+    found
+}
+
+//direction lying in the side, perpendicular to the edge and pointing into the side. Uses the
+//winding of the side so it also works for non convex sides.
+fn inward_direction(side: &Surface, edge: &Line, edge_direction: &na::Vector3<f64>) -> na::Vector3<f64>{
+    let normal = side.normal();
+    match edge_direction_in_side(side, edge){
+        Some(false) => edge_direction.cross(&normal),
+        _ => normal.cross(edge_direction),
+    }
+}
+
+//the cross section of the treatment as offsets from a point on the edge, running from the first
+//side to the second side
+fn profile_offsets(inward_one: &na::Vector3<f64>, inward_two: &na::Vector3<f64>, treatment: EdgeTreatment) -> Vec<na::Vector3<f64>>{
+    match treatment{
+        EdgeTreatment::Chamfer{distance_one, distance_two} => {
+            vec![inward_one * distance_one, inward_two * distance_two]
+        },
+        EdgeTreatment::Fillet{radius, segments} => {
+            //interior angle between the two sides at the edge
+            let interior_angle = inward_one.dot(inward_two).clamp(-1.0, 1.0).acos();
+            let tangent_distance = radius / (interior_angle / 2.0).tan();
+            let bisector = (inward_one + inward_two).normalize();
+            let arc_centre = bisector * (radius / (interior_angle / 2.0).sin());
+
+            let start = inward_one * tangent_distance;
+            let end = inward_two * tangent_distance;
+            let start_unit = (start - arc_centre) / radius;
+            let end_unit = (end - arc_centre) / radius;
+            let sweep = std::f64::consts::PI - interior_angle;
+            let towards_end = (end_unit - start_unit * start_unit.dot(&end_unit)).normalize();
+
+            let mut offsets = vec![];
+            for i in 0..=segments{
+                let angle = sweep * i as f64 / segments as f64;
+                offsets.push(arc_centre + (start_unit * angle.cos() + towards_end * angle.sin()) * radius);
+            }
+            //the ends are set exactly so they sit on the trimmed sides without rounding errors
+            offsets[0] = start;
+            offsets[segments] = end;
+            offsets
+        },
+    }
+}
+
+//the profile at one end of the edge, see corner_profile
+struct CornerProfile{
+    //one point per profile offset
+    points: Vec<Point>,
+    //points where the profile crosses from one cap to the next, between points i and i + 1
+    crossings: Vec<Vec<Point>>,
+    //each cap with the run of profile points lying on it, in profile order
+    cap_runs: Vec<(usize, Vec<Point>)>,
+}
+
+//How far along the edge (from the corner, along into_edge) the offset has to slide to reach the cap's
+//plane. None for caps the edge runs along.
+fn slide_to_cap(cap: &Surface, offset: &na::Vector3<f64>, into_edge: &na::Vector3<f64>) -> Option<f64>{
+    let cap_normal = cap.normal();
+    let along = cap_normal.dot(into_edge);
+    if along.abs() > 1e-9 {Some(-cap_normal.dot(offset) / along)} else {None}
+}
+
+//Slides the profile along the edge so that it lies on the cap sides at that end of the edge, this
+//keeps fillets and chamfers exact on caps that are not perpendicular to the edge. With several caps
+//every profile point lands on the one it reaches last going into the edge, which is the surface
+//of the part at a ridge corner, and the points where it passes from cap to cap are added.
+fn corner_profile(sides: &Vec<Surface>, corner: &na::Vector3<f64>, offsets: &Vec<na::Vector3<f64>>, into_edge: &na::Vector3<f64>, caps: &Vec<usize>) -> CornerProfile{
+    let slides = |offset: &na::Vector3<f64>| -> Vec<Option<f64>>{
+        caps.iter().map(|cap| slide_to_cap(&sides[*cap], offset, into_edge)).collect()
+    };
+    //the cap reached last and how far along it is
+    let active = |offset: &na::Vector3<f64>| -> Option<(usize, f64)>{
+        slides(offset).into_iter().enumerate()
+            .filter_map(|(position, slide)| slide.map(|slide| (position, slide)))
+            .max_by(|one, two| one.1.total_cmp(&two.1))
+    };
+    let place = |offset: &na::Vector3<f64>, slide: f64| Point::from_xyz(corner + offset + into_edge * slide);
+
+    let mut points = vec![];
+    let mut actives = vec![];
+    for offset in offsets.iter(){
+        let found = active(offset);
+        points.push(place(offset, found.map_or(0.0, |(_, slide)| slide)));
+        actives.push(found.map(|(position, _)| position));
+    }
+
+    //between two profile points the slides change linearly, so the cap reached last changes where
+    //two of them cross
+    let mut crossings = vec![vec![]; offsets.len().saturating_sub(1)];
+    for index in 0..offsets.len().saturating_sub(1){
+        let (from, to) = (offsets[index], offsets[index + 1]);
+        let (mut current, mut fraction) = match (actives[index], actives[index + 1]){
+            (Some(current), Some(last)) if current != last => (current, 0.0),
+            _ => continue,
+        };
+        let (slides_from, slides_to) = (slides(&from), slides(&to));
+        while current != actives[index + 1].unwrap(){
+            let mut next: Option<(usize, f64)> = None;
+            for other in 0..caps.len(){
+                let (Some(current_from), Some(current_to), Some(other_from), Some(other_to)) = (slides_from[current], slides_to[current], slides_from[other], slides_to[other]) else {continue};
+                let (gap_from, gap_to) = (other_from - current_from, other_to - current_to);
+                if other == current || gap_to <= gap_from{
+                    continue;
+                }
+                let crossing = -gap_from / (gap_to - gap_from);
+                if crossing > fraction - 1e-12 && crossing < 1.0 + 1e-12 && next.map_or(true, |(_, earliest)| crossing < earliest){
+                    next = Some((other, crossing));
+                }
+            }
+            let (other, crossing) = match next{
+                Some(next) => next,
+                None => break,
+            };
+            let offset = from + (to - from) * crossing;
+            let slide = slides_from[current].unwrap() + (slides_to[current].unwrap() - slides_from[current].unwrap()) * crossing;
+            crossings[index].push((current, other, place(&offset, slide)));
+            current = other;
+            fraction = crossing;
+        }
+    }
+
+    let mut cap_runs: Vec<(usize, Vec<Point>)> = caps.iter().map(|cap| (*cap, vec![])).collect();
+    for index in 0..points.len(){
+        if let Some(position) = actives[index]{
+            cap_runs[position].1.push(points[index]);
+        }
+        if let Some(between) = crossings.get(index){
+            for (from, to, point) in between.iter(){
+                cap_runs[*from].1.push(*point);
+                cap_runs[*to].1.push(*point);
+            }
+        }
+    }
+    CornerProfile{
+        points: points,
+        crossings: crossings.into_iter().map(|between| between.into_iter().map(|(_, _, point)| point).collect()).collect(),
+        cap_runs: cap_runs,
+    }
+}
+
+fn replace_point(side: &mut Surface, old_point: Point, new_points: &Vec<Point>){
+    let mut new_key_points = vec![];
+    for point in side.key_points.iter(){
+        if point.coincident(&old_point){
+            new_key_points.extend(new_points.iter().cloned());
+        }else{
+            new_key_points.push(*point);
+        }
+    }
+    side.key_points = new_key_points;
+}
+
+//volume of the sides, negative when they are wound inwards
+fn signed_volume(sides: &Vec<Surface>) -> f64{
+    let mut volume = 0.0;
+    for side in sides.iter(){
+        let points = &side.key_points;
+        for i in 1..points.len().saturating_sub(1){
+            volume += points[0].xyz().dot(&points[i].xyz().cross(&points[i + 1].xyz())) / 6.0;
+        }
+    }
+    volume
+}
+
+fn treat_edge(mut sides: Vec<Surface>, edge: Line, treatment: EdgeTreatment) -> Result<Vec<Surface>, EdgeTreatmentError>{
+    let touching = sides_with_edge(&sides, &edge);
+    if touching.is_empty(){
+        return Err(EdgeTreatmentError::EdgeNotFound(edge));
+    }
+    if touching.len() != 2{
+        return Err(EdgeTreatmentError::NonManifoldEdge(edge));
+    }
+    let (first, second) = (touching[0], touching[1]);
+
+    let start = edge.point_a.xyz();
+    let end = edge.point_b.xyz();
+    let edge_direction = match (end - start).try_normalize(f64::EPSILON){
+        Some(direction) => direction,
+        None => return Err(EdgeTreatmentError::FlatEdge(edge)),
+    };
+    let inward_one = inward_direction(&sides[first], &edge, &edge_direction);
+    let inward_two = inward_direction(&sides[second], &edge, &edge_direction);
+    if inward_one.cross(&inward_two).norm() < 1e-9{
+        return Err(EdgeTreatmentError::FlatEdge(edge));
+    }
+    //At a ridge the second side falls away behind the first one's outward normal. Sides may be wound
+    //either way, the sign of the volume they enclose says which.
+    let winding = if signed_volume(&sides) < 0.0 {-1.0} else {1.0};
+    if (sides[first].normal() * winding).dot(&inward_two) > 1e-9{
+        return Err(EdgeTreatmentError::ConcaveEdge(edge));
+    }
+    let offsets = profile_offsets(&inward_one, &inward_two, treatment);
+    let last = offsets.len() - 1;
+
+    //the profile has to end inside both sides, otherwise the new strips would cross their far edges
+    let (size_one, size_two) = match treatment{
+        EdgeTreatment::Chamfer{distance_one, distance_two} => (distance_one, distance_two),
+        EdgeTreatment::Fillet{radius, ..} => (radius, radius),
+    };
+    for (side, inward, offset, size) in [(first, inward_one, offsets[0], size_one), (second, inward_two, offsets[last], size_two)]{
+        let depth = sides[side].key_points.iter().map(|point| (point.xyz() - start).dot(&inward)).fold(0.0, f64::max);
+        if offset.norm() >= depth - model_tolerance(){
+            return Err(EdgeTreatmentError::InvalidSize(size));
+        }
+    }
+
+    //the new strips face away from the material, which sits along the bisector of the sides, or
+    //towards it when the sides are wound inwards
+    let outward = -(inward_one + inward_two) * winding;
+
+    //A convex part is simply cut by the plane of every strip. Unlike the corner profiles below this
+    //also finds the strips of edges treated before when the new profile runs onto them.
+    let points: Vec<Point> = sides.iter().flat_map(|side| side.key_points.iter().cloned()).collect();
+    if is_convex(&sides, &points){
+        for i in 0..last{
+            let mut normal = (offsets[i + 1] - offsets[i]).cross(&edge_direction).normalize();
+            if normal.dot(&(inward_one + inward_two)) > 0.0{
+                normal = -normal;
+            }
+            sides = cut_convex(sides, &(start + offsets[i]), &normal, winding);
+        }
+        return Ok(sides);
+    }
+
+    //caps are the other sides meeting the edge at one of its end points
+    let caps_at = |corner: Point| -> Vec<usize>{
+        (0..sides.len())
+            .filter(|index| *index != first && *index != second && sides[*index].key_points.iter().any(|point| point.coincident(&corner)))
+            .collect()
+    };
+    let start_corner = corner_profile(&sides, &start, &offsets, &edge_direction, &caps_at(edge.point_a));
+    let end_corner = corner_profile(&sides, &end, &offsets, &-edge_direction, &caps_at(edge.point_b));
+
+    //Every cap replaces its corner with its part of the profile, ordered to keep its winding: the
+    //end of the run nearer the point before the corner goes first
+    for (corner, profile) in [(edge.point_a, &start_corner), (edge.point_b, &end_corner)]{
+        for (cap_index, run) in profile.cap_runs.iter(){
+            let (first_point, last_point) = match (run.first(), run.last()){
+                (Some(first_point), Some(last_point)) => (first_point.xyz(), last_point.xyz()),
+                _ => continue,
+            };
+            let cap_side = &sides[*cap_index];
+            let count = cap_side.key_points.len();
+            let corner_index = cap_side.key_points.iter().position(|point| point.coincident(&corner)).unwrap();
+            let previous = cap_side.key_points[(corner_index + count - 1) % count].xyz();
+            let next = cap_side.key_points[(corner_index + 1) % count].xyz();
+            let mut ordered = run.clone();
+            if (last_point - previous).norm() + (first_point - next).norm() < (first_point - previous).norm() + (last_point - next).norm(){
+                ordered.reverse();
+            }
+            replace_point(&mut sides[*cap_index], corner, &ordered);
+        }
+    }
+    let (start_profile, end_profile) = (&start_corner.points, &end_corner.points);
+
+    replace_point(&mut sides[first], edge.point_a, &vec![start_profile[0]]);
+    replace_point(&mut sides[first], edge.point_b, &vec![end_profile[0]]);
+    replace_point(&mut sides[second], edge.point_a, &vec![start_profile[last]]);
+    replace_point(&mut sides[second], edge.point_b, &vec![end_profile[last]]);
+
+    for i in 0..last{
+        //points where the caps change are corners of the strip as well
+        let mut corners = vec![start_profile[i], end_profile[i]];
+        corners.extend(end_corner.crossings[i].iter().cloned());
+        corners.extend([end_profile[i + 1], start_profile[i + 1]]);
+        corners.extend(start_corner.crossings[i].iter().rev().cloned());
+        let mut strip = Surface::new(corners);
+        if strip.normal().dot(&outward) < 0.0{
+            strip.key_points.reverse();
+        }
+        sides.push(strip);
+    }
+    //Where treated edges meet, profile points can land on corners the sides already have. The
+    //repeated points are dropped, a side can never lose all of its area this way because the sizes
+    //are checked against the sides above.
+    for side in sides.iter_mut(){
+        remove_repeated_points(side);
+    }
+    Ok(sides)
+}
+
+//Keeps the part of a convex set of sides behind the plane, whose normal points away from the
+//material, and closes it with a new side wound like the others. Sides cut away completely are left
+//empty so the indices of the rest do not change.
+fn cut_convex(mut sides: Vec<Surface>, on_plane: &na::Vector3<f64>, normal: &na::Vector3<f64>, winding: f64) -> Vec<Surface>{
+    let tolerance = model_tolerance();
+    let height = |point: &Point| normal.dot(&(point.xyz() - on_plane));
+    if sides.iter().all(|side| side.key_points.iter().all(|point| height(point) <= tolerance)){
+        return sides;
+    }
+    let mut cut_points: Vec<Point> = vec![];
+    for side in sides.iter_mut(){
+        let count = side.key_points.len();
+        let mut corners = vec![];
+        for i in 0..count{
+            let (current, next) = (side.key_points[i], side.key_points[(i + 1) % count]);
+            let (current_height, next_height) = (height(&current), height(&next));
+            if current_height <= tolerance{
+                corners.push(current);
+                if current_height >= -tolerance{
+                    cut_points.push(current);
+                }
+            }
+            if (current_height < -tolerance && next_height > tolerance) || (current_height > tolerance && next_height < -tolerance){
+                //always measured from the point below so both sides of the edge get the same point
+                let (below, above) = if current_height < 0.0 {(current, next)} else {(next, current)};
+                let (below_height, above_height) = (height(&below), height(&above));
+                let crossing = Point::from_xyz(below.xyz() + (above.xyz() - below.xyz()) * (below_height / (below_height - above_height)));
+                corners.push(crossing);
+                cut_points.push(crossing);
+            }
+        }
+        side.key_points = corners;
+        remove_repeated_points(side);
+        if side.key_points.len() < 3{
+            side.key_points.clear();
+        }
+    }
+
+    let mut cap_points: Vec<Point> = vec![];
+    for point in cut_points.into_iter(){
+        if !cap_points.iter().any(|other| other.coincident(&point)){
+            cap_points.push(point);
+        }
+    }
+    if cap_points.len() >= 3{
+        //the cut of a convex part is convex, so its corners are in order of their angle around the middle
+        let middle = cap_points.iter().map(|point| point.xyz()).sum::<na::Vector3<f64>>() / cap_points.len() as f64;
+        let across = (cap_points[0].xyz() - middle).normalize();
+        let up = normal.cross(&across);
+        let angle = |point: &Point| {
+            let offset = point.xyz() - middle;
+            offset.dot(&up).atan2(offset.dot(&across))
+        };
+        cap_points.sort_by(|one, two| angle(one).total_cmp(&angle(two)));
+        let mut cap = Surface::new(cap_points);
+        if cap.normal().dot(normal) * winding < 0.0{
+            cap.key_points.reverse();
+        }
+        sides.push(cap);
+    }
+    sides
+}
+
+fn remove_repeated_points(side: &mut Surface){
+    let mut kept: Vec<Point> = vec![];
+    for point in side.key_points.iter(){
+        if !kept.last().map_or(false, |last| last.coincident(point)){
+            kept.push(*point);
+        }
+    }
+    while kept.len() > 1 && kept[0].coincident(&kept[kept.len() - 1]){
+        kept.pop();
+    }
+    side.key_points = kept;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_shapes::prism;
+
+    fn point(x: f64, y: f64, z: f64) -> Point{
+        Point::from_xyz(na::Vector3::new(x, y, z))
+    }
+    fn octahedron() -> PerspectiveObject{
+        let mut sides = vec![];
+        for signs in 0..8{
+            let sign = |bit: usize| if signs & bit == 0 {1.0} else {-1.0};
+            let (x, y, z) = (sign(1), sign(2), sign(4));
+            let mut corners = vec![point(x, 0.0, 0.0), point(0.0, y, 0.0), point(0.0, 0.0, z)];
+            if x * y * z < 0.0{
+                corners.reverse();
+            }
+            sides.push(Surface::new(corners));
+        }
+        PerspectiveObject::new(sides)
+    }
+    fn volume(object: &PerspectiveObject) -> f64{
+        object.mass_properties(1.0).unwrap().volume
+    }
+
+    #[test]
+    fn chamfer_keeps_a_box_closed(){
+        let mut cube = prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        cube.chamfer_edges(&vec![Line::new(point(1.0, 0.0, 0.0), point(1.0, 0.0, 1.0))], 0.1, 0.1).unwrap();
+        assert!(cube.is_closed());
+        assert!((volume(&cube) - (1.0 - 0.005)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn concave_edges_are_refused(){
+        let mut l_shape = prism(&[(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)], 0.0, 1.0);
+        let inner = Line::new(point(1.0, 1.0, 0.0), point(1.0, 1.0, 1.0));
+        assert!(matches!(l_shape.fillet_edges(&vec![inner], 0.1, 4), Err(EdgeTreatmentError::ConcaveEdge(_))));
+        let outer = Line::new(point(2.0, 0.0, 0.0), point(2.0, 0.0, 1.0));
+        assert!(l_shape.fillet_edges(&vec![outer], 0.1, 4).is_ok());
+    }
+
+    #[test]
+    fn inward_wound_solids_are_not_taken_as_concave(){
+        let cube = prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        let mut inverted = PerspectiveObject::new(cube.get_sides().into_iter().map(|side| {
+            let mut corners = side.get_key_points().clone();
+            corners.reverse();
+            Surface::new(corners)
+        }).collect());
+        assert!(inverted.chamfer_edges(&vec![Line::new(point(1.0, 0.0, 0.0), point(1.0, 0.0, 1.0))], 0.1, 0.1).is_ok());
+        assert!((volume(&inverted) - (1.0 - 0.005)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn corners_with_several_caps_stay_closed(){
+        //four sides meet at every corner of an octahedron, so each end of an edge has two caps
+        let mut solid = octahedron();
+        let before = volume(&solid);
+        solid.chamfer_edges(&vec![Line::new(point(1.0, 0.0, 0.0), point(0.0, 1.0, 0.0))], 0.2, 0.2).unwrap();
+        assert!(solid.is_closed());
+        let after = volume(&solid);
+        assert!(after < before - 1e-3 && after > before - 0.1);
+
+        let mut rounded = octahedron();
+        rounded.fillet_edges(&vec![Line::new(point(1.0, 0.0, 0.0), point(0.0, 1.0, 0.0))], 0.2, 4).unwrap();
+        assert!(rounded.is_closed());
+        assert!(volume(&rounded) < before && volume(&rounded) > after);
+    }
+
+    #[test]
+    fn edges_meeting_at_a_corner_are_both_found(){
+        let mut cube = prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        let edges = vec![
+            Line::new(point(1.0, 0.0, 0.0), point(1.0, 0.0, 1.0)),
+            Line::new(point(0.0, 0.0, 0.0), point(1.0, 0.0, 0.0)),
+        ];
+        cube.chamfer_edges(&edges, 0.1, 0.1).unwrap();
+        assert!(cube.is_closed());
+    }
+
+    #[test]
+    fn every_edge_of_a_box_can_be_broken(){
+        let mut cube = prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        let edges = cube.get_edges();
+        assert_eq!(edges.len(), 12);
+        cube.chamfer_edges(&edges, 0.1, 0.1).unwrap();
+        assert!(cube.is_closed());
+        //every edge takes off a prism of d * d / 2, where two of them meet at a corner they overlap
+        //by d^3 / 3 and all three by d^3 / 4, which leaves 1 - 6 d^2 + 6 d^3
+        assert!((volume(&cube) - (1.0 - 0.06 + 0.006)).abs() < 1e-9);
+
+        let mut rounded = prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        rounded.fillet_edges(&edges, 0.1, 4).unwrap();
+        assert!(rounded.is_closed());
+        assert!(volume(&rounded) < 1.0 && volume(&rounded) > volume(&cube));
+    }
+
+    #[test]
+    fn sizes_larger_than_the_sides_are_refused(){
+        let mut cube = prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        let edge = Line::new(point(1.0, 0.0, 0.0), point(1.0, 0.0, 1.0));
+        assert!(matches!(cube.chamfer_edges(&vec![edge], 0.1, 1.5), Err(EdgeTreatmentError::InvalidSize(_))));
+        assert!(matches!(cube.fillet_edges(&vec![edge], 2.0, 4), Err(EdgeTreatmentError::InvalidSize(_))));
+        //two chamfers on one side that would cross each other
+        let opposite = Line::new(point(0.0, 0.0, 0.0), point(0.0, 0.0, 1.0));
+        assert!(matches!(cube.chamfer_edges(&vec![edge, opposite], 0.6, 0.6), Err(EdgeTreatmentError::InvalidSize(_))));
+        assert!((volume(&cube) - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,476 @@
+//Inverse kinematics for serial chains: joint values that bring a tool point (or whole tool frame)
+//on the end part to a target. Two solvers share the same settings and report:
+//
+//    damped least squares    dq = W J^T (J W J^T + damping^2 I)^-1 e
+//    cyclic coordinate descent, one degree of freedom at a time from the tool back to the base
+//
+//where J is the chain's Jacobian, e the weighted target error and W the joint weights. Only the
+//joints on the chain from the base to the end part move, every other value is left as given. A
+//coupled value on the chain is moved through its leaders, which may sit off the chain.
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Clone,Copy,Debug)]
+pub enum IkTarget{
+    //only the tool point has to get there
+    Position(Point),
+    //the tool frame has to match in position and orientation
+    Pose(Transform),
+}
+impl IkTarget{
+    fn position(&self) -> na::Vector3<f64>{
+        match self{
+            Self::Position(point) => point.xyz(),
+            Self::Pose(pose) => pose.translation_part().to_vector(),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum IkMethod{
+    //higher damping is steadier near singular poses but slower to converge
+    DampedLeastSquares{damping: f64},
+    CyclicCoordinateDescent,
+}
+
+#[derive(Clone,Debug)]
+pub struct IkSettings{
+    pub method: IkMethod,
+    pub max_iterations: usize,
+    pub position_tolerance: f64,
+    //radians
+    pub orientation_tolerance: f64,
+    //how much each half of a pose error counts, only used for Pose targets
+    pub position_weight: f64,
+    pub orientation_weight: f64,
+    //How freely each degree of freedom moves, indexed like the combined value vector. 0 holds the
+    //value still, empty means 1 for all.
+    pub joint_weights: Vec<f64>,
+    //Lowest and highest value for each degree of freedom, indexed like the combined value vector.
+    //Values are kept inside after every step, coupled values included, empty means no limits.
+    pub limits: Vec<Option<(f64, f64)>>,
+}
+impl Default for IkSettings{
+    fn default() -> Self{
+        IkSettings{
+            method: IkMethod::DampedLeastSquares{damping: 0.1},
+            max_iterations: 200,
+            position_tolerance: model_tolerance() * 10.0,
+            orientation_tolerance: 1e-4,
+            position_weight: 1.0,
+            orientation_weight: 1.0,
+            joint_weights: vec![],
+            limits: vec![],
+        }
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct IkSolution{
+    //the combined value vector with the chain's values solved
+    pub values: Vec<f64>,
+    pub iterations: usize,
+    //distance from the tool point to the target
+    pub position_error: f64,
+    //angle in radians between the tool frame and the target, 0 for Position targets
+    pub orientation_error: f64,
+}
+
+#[derive(Clone,Debug)]
+pub enum IkError{
+    Kinematics(KinematicsError),
+    //no joint moves the end part
+    NoChain(NodeId),
+    //every degree of freedom on the chain has zero weight
+    NothingToMove,
+    //the iterations ran out, holds the closest the solver got
+    NotConverged(IkSolution),
+}
+impl From<KinematicsError> for IkError{
+    fn from(error: KinematicsError) -> Self{
+        IkError::Kinematics(error)
+    }
+}
+
+//a degree of freedom on the chain, with its world axis for the current values
+struct ChainAxis{
+    //position in the combined value vector
+    index: usize,
+    rotation: bool,
+    origin: na::Vector3<f64>,
+    direction: na::Vector3<f64>,
+}
+
+//Solves for the tool on the end part reaching the target, starting from the values given. tool is
+//the tool frame in the end part's local coordinates and base gives the world transform of the
+//tree's roots as for KinematicTree::forward.
+pub fn solve_inverse_kinematics(tree: &KinematicTree, joints: &Vec<Joint>, start: &[f64], end: NodeId, tool: &Transform, target: &IkTarget, settings: &IkSettings, base: impl Fn(NodeId) -> Option<Transform>) -> Result<IkSolution, IkError>{
+    let chain = tree.chain_to(joints, end);
+    if chain.is_empty(){
+        return Err(IkError::NoChain(end));
+    }
+    let weight = |index: usize| settings.joint_weights.get(index).cloned().unwrap_or(1.0).max(0.0);
+    let chain_values: Vec<usize> = chain.iter().flat_map(|joint| tree.value_range(joints, *joint)).collect();
+    let (free, coupling) = free_values(tree, &chain_values, start.len());
+    if !free.iter().any(|index| weight(*index) > 0.0){
+        return Err(IkError::NothingToMove);
+    }
+
+    let mut values = start.to_vec();
+    clamp_to_limits(&mut values, &settings.limits);
+    tree.apply_couplings(&mut values);
+    let mut best: Option<IkSolution> = None;
+    for iteration in 0..=settings.max_iterations{
+        let world = tree.forward(joints, &values, &base)?;
+        let tool_world = world[&end].compose(tool);
+        let (position_error, orientation_error) = target_error(&tool_world, target);
+        let solution = IkSolution{
+            values: values.clone(),
+            iterations: iteration,
+            position_error: position_error.norm(),
+            orientation_error: orientation_error.norm(),
+        };
+        if solution.position_error <= settings.position_tolerance && solution.orientation_error <= settings.orientation_tolerance{
+            return Ok(solution);
+        }
+        //only kept for NotConverged, a converged iterate is always returned as it is
+        if best.as_ref().map_or(true, |best| weighted_error(&solution, settings) < weighted_error(best, settings)){
+            best = Some(solution);
+        }
+        if iteration == settings.max_iterations{
+            break;
+        }
+
+        let before = values.clone();
+        let axes = chain_axes(tree, joints, &values, &world, &chain);
+        match settings.method{
+            IkMethod::DampedLeastSquares{damping} => {
+                let steps = damped_least_squares_step(&axes, &free, &coupling, &tool_world, &position_error, &orientation_error, target, settings, damping, &weight);
+                for (index, step) in free.iter().zip(steps.iter()){
+                    values[*index] += step;
+                }
+            },
+            IkMethod::CyclicCoordinateDescent => {
+                cyclic_coordinate_descent_sweep(tree, joints, &mut values, &chain, &free, &coupling, end, tool, target, settings, &weight, &base)?;
+            },
+        }
+        settle(tree, &mut values, &before, &settings.limits);
+    }
+    Err(IkError::NotConverged(best.unwrap()))
+}
+
+//The values the solver moves for the chain's values: those that are not coupled and the leaders of
+//those that are. The matrix has how far each chain value moves per unit of each free value, one
+//row per chain value and one column per free value.
+fn free_values(tree: &KinematicTree, chain_values: &Vec<usize>, total: usize) -> (Vec<usize>, na::DMatrix<f64>){
+    let coupled = tree.coupled_values();
+    let mut reached = chain_values.clone();
+    //followers come after their leaders, so going backwards reaches leaders of leaders too
+    for coupling in tree.get_couplings().iter().rev(){
+        if reached.contains(&coupling.follower){
+            for (leader, _) in coupling.leaders.iter(){
+                if !reached.contains(leader){
+                    reached.push(*leader);
+                }
+            }
+        }
+    }
+    let free: Vec<usize> = reached.into_iter().filter(|index| !coupled.contains(index)).collect();
+
+    //couplings are linear, so moving one free value from zero shows its effect on everything
+    let mut still = vec![0.0; total];
+    tree.apply_couplings(&mut still);
+    let mut matrix = na::DMatrix::<f64>::zeros(chain_values.len(), free.len());
+    for (column, index) in free.iter().enumerate(){
+        let mut moved = vec![0.0; total];
+        moved[*index] = 1.0;
+        tree.apply_couplings(&mut moved);
+        for (row, value) in chain_values.iter().enumerate(){
+            matrix[(row, column)] = moved[*value] - still[*value];
+        }
+    }
+    (free, matrix)
+}
+
+fn clamp_to_limits(values: &mut Vec<f64>, limits: &Vec<Option<(f64, f64)>>){
+    for (value, limit) in values.iter_mut().zip(limits.iter()){
+        if let Some((lowest, highest)) = limit{
+            *value = value.clamp(*lowest, *highest);
+        }
+    }
+}
+
+//Clamps the values to the limits and sets the coupled values from their leaders. A coupled value
+//cannot be clamped without breaking its coupling, so while one is outside its range the move from
+//before is halved, and given up altogether if that does not help.
+fn settle(tree: &KinematicTree, values: &mut Vec<f64>, before: &[f64], limits: &Vec<Option<(f64, f64)>>){
+    let wanted = values.clone();
+    let mut scale = 1.0;
+    loop{
+        clamp_to_limits(values, limits);
+        tree.apply_couplings(values);
+        if tree.followers_beyond_limits(values, limits).is_empty(){
+            return;
+        }
+        scale *= 0.5;
+        if scale < 1.0 / 64.0{
+            *values = before.to_vec();
+            return;
+        }
+        *values = before.iter().zip(wanted.iter()).map(|(start, end)| start + (end - start) * scale).collect();
+    }
+}
+
+//Translation still to go and the rotation still to go as an axis times angle, both in world
+//coordinates. The rotation is zero for Position targets.
+fn target_error(tool_world: &Transform, target: &IkTarget) -> (na::Vector3<f64>, na::Vector3<f64>){
+    let position_error = target.position() - tool_world.translation_part().to_vector();
+    let orientation_error = match target{
+        IkTarget::Position(_) => na::Vector3::zeros(),
+        IkTarget::Pose(pose) => {
+            rotation_vector(&(pose.rotation_part() * tool_world.rotation_part().transpose()))
+        },
+    };
+    (position_error, orientation_error)
+}
+
+//The rotation as its axis times its angle. Taken from the skew part rather than the trace so small
+//angles keep their precision.
+pub(super) fn rotation_vector(rotation: &na::Matrix3<f64>) -> na::Vector3<f64>{
+    let skew = na::Vector3::new(rotation.m32 - rotation.m23, rotation.m13 - rotation.m31, rotation.m21 - rotation.m12) * 0.5;
+    let angle = skew.norm().atan2((rotation.trace() - 1.0) / 2.0);
+    if skew.norm() > f64::EPSILON{
+        skew * (angle / skew.norm())
+    }else{
+        //no rotation at all or exactly half a turn, where the skew part says nothing
+        na::Rotation3::from_matrix_unchecked(*rotation).scaled_axis()
+    }
+}
+
+fn weighted_error(solution: &IkSolution, settings: &IkSettings) -> f64{
+    settings.position_weight * solution.position_error + settings.orientation_weight * solution.orientation_error
+}
+
+//The world axis of every degree of freedom on the chain. A joint's axes are applied one after the
+//other, so each one is carried by the motion of the axes before it.
+fn chain_axes(tree: &KinematicTree, joints: &Vec<Joint>, values: &[f64], world: &HashMap<NodeId, Transform>, chain: &Vec<usize>) -> Vec<ChainAxis>{
+    let mut axes = vec![];
+    for joint_index in chain.iter(){
+        let joint = &joints[*joint_index];
+        let range = tree.value_range(joints, *joint_index);
+        let mut frame = world[&joint.get_parent()].compose(&joint.get_parent_frame());
+        for (axis, index) in joint.get_type().axes().iter().zip(range){
+            let (rotation, along) = match axis{
+                JointAxis::Rotation(along) => (true, *along),
+                JointAxis::Translation(along) => (false, *along),
+            };
+            let direction = frame.linear_part().column(along).into_owned();
+            axes.push(ChainAxis{
+                index: index,
+                rotation: rotation,
+                origin: frame.translation_part().to_vector(),
+                direction: direction.try_normalize(f64::EPSILON).unwrap_or(direction),
+            });
+            frame = frame.compose(&axis.motion(values[index]));
+        }
+    }
+    axes
+}
+
+fn damped_least_squares_step(axes: &Vec<ChainAxis>, free: &Vec<usize>, coupling: &na::DMatrix<f64>, tool_world: &Transform, position_error: &na::Vector3<f64>, orientation_error: &na::Vector3<f64>, target: &IkTarget, settings: &IkSettings, damping: f64, weight: &impl Fn(usize) -> f64) -> Vec<f64>{
+    let tool_position = tool_world.translation_part().to_vector();
+    let rows = match target{
+        IkTarget::Position(_) => 3,
+        IkTarget::Pose(_) => 6,
+    };
+    let (position_weight, orientation_weight) = match target{
+        IkTarget::Position(_) => (1.0, 0.0),
+        IkTarget::Pose(_) => (settings.position_weight, settings.orientation_weight),
+    };
+    let mut jacobian = na::DMatrix::<f64>::zeros(rows, axes.len());
+    for (column, axis) in axes.iter().enumerate(){
+        let (linear, angular) = if axis.rotation{
+            (axis.direction.cross(&(tool_position - axis.origin)), axis.direction)
+        }else{
+            (axis.direction, na::Vector3::zeros())
+        };
+        jacobian.fixed_view_mut::<3, 1>(0, column).copy_from(&(linear * position_weight));
+        if rows == 6{
+            jacobian.fixed_view_mut::<3, 1>(3, column).copy_from(&(angular * orientation_weight));
+        }
+    }
+    let mut error = na::DVector::<f64>::zeros(rows);
+    error.fixed_rows_mut::<3>(0).copy_from(&(position_error * position_weight));
+    if rows == 6{
+        error.fixed_rows_mut::<3>(3).copy_from(&(orientation_error * orientation_weight));
+    }
+
+    //in terms of the free values rather than the chain's own
+    let jacobian = jacobian * coupling;
+
+    let joint_weights = na::DMatrix::<f64>::from_diagonal(&na::DVector::from_iterator(free.len(), free.iter().map(|index| weight(*index))));
+    let weighted_transpose = &joint_weights * jacobian.transpose();
+    let system = &jacobian * &weighted_transpose + na::DMatrix::<f64>::identity(rows, rows) * (damping * damping);
+    match system.lu().solve(&error){
+        Some(solved) => (weighted_transpose * solved).iter().cloned().collect(),
+        None => vec![0.0; free.len()],
+    }
+}
+
+//One pass over the chain from the tool back to the base, each degree of freedom moved on its own
+//as far as brings the tool closest to the target. Weights below 1 take only part of that move. A
+//coupled degree of freedom is moved through the free value that moves it most.
+fn cyclic_coordinate_descent_sweep(tree: &KinematicTree, joints: &Vec<Joint>, values: &mut Vec<f64>, chain: &Vec<usize>, free: &Vec<usize>, coupling: &na::DMatrix<f64>, end: NodeId, tool: &Transform, target: &IkTarget, settings: &IkSettings, weight: &impl Fn(usize) -> f64, base: &impl Fn(NodeId) -> Option<Transform>) -> Result<(), IkError>{
+    let count: usize = chain.iter().map(|joint| joints[*joint].degrees_of_freedom()).sum();
+    for position in (0..count).rev(){
+        let world = tree.forward(joints, values, base)?;
+        let axis = &chain_axes(tree, joints, values, &world, chain)[position];
+        let column = match (0..free.len()).max_by(|one, two| coupling[(position, *one)].abs().total_cmp(&coupling[(position, *two)].abs())){
+            Some(column) if coupling[(position, column)].abs() > f64::EPSILON => column,
+            _ => continue,
+        };
+        let amount = weight(free[column]).min(1.0);
+        if amount <= 0.0{
+            continue;
+        }
+        let tool_world = world[&end].compose(tool);
+        let tool_position = tool_world.translation_part().to_vector();
+        let step = if axis.rotation{
+            //turn the tool point about the axis onto the plane through the axis and the target
+            let from = perpendicular_part(&(tool_position - axis.origin), &axis.direction);
+            let to = perpendicular_part(&(target.position() - axis.origin), &axis.direction);
+            let mut turn = if from.norm() > model_tolerance() && to.norm() > model_tolerance(){
+                from.cross(&to).dot(&axis.direction).atan2(from.dot(&to))
+            }else{
+                0.0
+            };
+            if let IkTarget::Pose(_) = target{
+                let (_, orientation_error) = target_error(&tool_world, target);
+                let total = settings.position_weight + settings.orientation_weight;
+                if total > 0.0{
+                    turn = (settings.position_weight * turn + settings.orientation_weight * orientation_error.dot(&axis.direction)) / total;
+                }
+            }
+            turn
+        }else{
+            (target.position() - tool_position).dot(&axis.direction)
+        };
+        let before = values.clone();
+        values[free[column]] += step * amount / coupling[(position, column)];
+        settle(tree, values, &before, &settings.limits);
+    }
+    Ok(())
+}
+
+fn perpendicular_part(vector: &na::Vector3<f64>, axis: &na::Vector3<f64>) -> na::Vector3<f64>{
+    vector - axis * vector.dot(axis)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //two unit links turning about z: ground 0, first link 1, second link 2, the tool at the end of
+    //the second link
+    fn planar_arm() -> (Vec<Joint>, Transform){
+        let shoulder = Joint::new("shoulder", JointType::Revolute, 0, Transform::identity(), 1, Transform::identity()).unwrap();
+        let elbow = Joint::new("elbow", JointType::Revolute, 1, Transform::translation(Direction::new(1.0, 0.0, 0.0)), 2, Transform::identity()).unwrap();
+        (vec![shoulder, elbow], Transform::translation(Direction::new(1.0, 0.0, 0.0)))
+    }
+
+    fn tool_position(joints: &Vec<Joint>, tool: &Transform, values: &[f64]) -> Point{
+        let world = KinematicTree::new(joints).forward(joints, values, |_| Some(Transform::identity())).unwrap();
+        world[&2].compose(tool).apply_point(&Point::origin())
+    }
+
+    fn solve(joints: &Vec<Joint>, tool: &Transform, start: &[f64], target: Point, settings: &IkSettings) -> Result<IkSolution, IkError>{
+        solve_inverse_kinematics(&KinematicTree::new(joints), joints, start, 2, tool, &IkTarget::Position(target), settings, |_| Some(Transform::identity()))
+    }
+
+    #[test]
+    fn both_methods_reach_a_point(){
+        let (joints, tool) = planar_arm();
+        let target = Point::new(0.5, 1.2, 0.0);
+        for method in [IkMethod::DampedLeastSquares{damping: 0.1}, IkMethod::CyclicCoordinateDescent]{
+            let settings = IkSettings{method: method, max_iterations: 500, ..IkSettings::default()};
+            let solution = solve(&joints, &tool, &[0.3, 0.3], target, &settings).unwrap();
+            //the values returned are the ones that converged, not an earlier best guess
+            assert!(solution.position_error <= settings.position_tolerance, "{:?}", method);
+            let reached = tool_position(&joints, &tool, &solution.values);
+            assert!((reached - target).length() <= settings.position_tolerance, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn limits_are_respected(){
+        let (joints, tool) = planar_arm();
+        //(1, 1) can be reached elbow up or elbow down, keeping the elbow positive leaves one way
+        let limits = vec![Some((-1.0, 1.0)), Some((0.0, std::f64::consts::PI))];
+        for method in [IkMethod::DampedLeastSquares{damping: 0.1}, IkMethod::CyclicCoordinateDescent]{
+            let settings = IkSettings{method: method, max_iterations: 500, limits: limits.clone(), ..IkSettings::default()};
+            let solution = solve(&joints, &tool, &[0.5, 0.2], Point::new(1.0, 1.0, 0.0), &settings).unwrap();
+            assert!(solution.values[0].abs() < 1e-6, "{:?}", method);
+            assert!((solution.values[1] - std::f64::consts::FRAC_PI_2).abs() < 1e-6, "{:?}", method);
+
+            //straight up needs the shoulder at a quarter turn, past its limit
+            match solve(&joints, &tool, &[0.5, 0.2], Point::new(0.0, 2.0, 0.0), &settings){
+                Err(IkError::NotConverged(closest)) => {
+                    assert!(closest.values[0] <= 1.0 && closest.values[0] >= -1.0, "{:?}", method);
+                    assert!(closest.values[1] >= 0.0 && closest.values[1] <= std::f64::consts::PI, "{:?}", method);
+                },
+                other => panic!("{:?} should not reach past the limit, got {:?}", method, other),
+            }
+        }
+    }
+
+    #[test]
+    fn coupled_values_stay_inside_their_limits(){
+        let (joints, tool) = planar_arm();
+        //the elbow follows the shoulder one to one and only has a small range of its own
+        let tree = KinematicTree::new(&joints).with_couplings(&joints, &[JointCoupling::belt("", JointValue::new(0, 0), JointValue::new(1, 0), 1.0)]).unwrap();
+        let limits = vec![None, Some((-0.4, 0.4))];
+        for method in [IkMethod::DampedLeastSquares{damping: 0.1}, IkMethod::CyclicCoordinateDescent]{
+            //coordinate descent turning a shoulder that carries the elbow with it closes in slowly
+            let settings = IkSettings{method: method, max_iterations: 500, position_tolerance: 1e-5, limits: limits.clone(), ..IkSettings::default()};
+            let solve_for = |target: Point| solve_inverse_kinematics(&tree, &joints, &[0.0, 0.0], 2, &tool, &IkTarget::Position(target), &settings, |_| Some(Transform::identity()));
+            let solution = solve_for(tool_position(&joints, &tool, &[0.3, 0.3])).unwrap();
+            assert!((solution.values[0] - 0.3).abs() < 1e-4 && solution.values[0] == solution.values[1], "{:?}", method);
+            match solve_for(tool_position(&joints, &tool, &[0.8, 0.8])){
+                Err(IkError::NotConverged(closest)) => {
+                    assert!(closest.values[1].abs() <= 0.4 + 1e-9, "{:?} {:?}", method, closest);
+                    assert_eq!(closest.values[0], closest.values[1], "{:?}", method);
+                },
+                other => panic!("{:?} should not take the elbow past its limit, got {:?}", method, other),
+            }
+        }
+    }
+
+    #[test]
+    fn zero_weight_joint_stays_put(){
+        let (joints, tool) = planar_arm();
+        let target = tool_position(&joints, &tool, &[0.5, 0.8]);
+        for method in [IkMethod::DampedLeastSquares{damping: 0.1}, IkMethod::CyclicCoordinateDescent]{
+            let settings = IkSettings{method: method, max_iterations: 500, joint_weights: vec![0.0, 1.0], ..IkSettings::default()};
+            let solution = solve(&joints, &tool, &[0.5, -0.4], target, &settings).unwrap();
+            assert_eq!(solution.values[0], 0.5, "{:?}", method);
+            assert!((solution.values[1] - 0.8).abs() < 1e-6, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn unreachable_target_is_not_converged(){
+        let (joints, tool) = planar_arm();
+        for method in [IkMethod::DampedLeastSquares{damping: 0.1}, IkMethod::CyclicCoordinateDescent]{
+            let settings = IkSettings{method: method, ..IkSettings::default()};
+            match solve(&joints, &tool, &[0.3, 0.3], Point::new(5.0, 0.0, 0.0), &settings){
+                //fully stretched towards it is as close as the arm gets, the closest kept should
+                //be near that
+                Err(IkError::NotConverged(closest)) => {
+                    assert!(closest.position_error >= 3.0 - 1e-9, "{:?}", method);
+                    assert!(closest.position_error < 3.1, "{:?} {:?}", method, closest);
+                },
+                other => panic!("{:?} should not reach the target, got {:?}", method, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//Joints connecting two parts. Each part carries a joint frame in its own local coordinates and the
+//joint moves the child's frame relative to the parent's according to the joint values, so
+//
+//    child world = parent world * parent frame * motion(values) * child frame^-1
+//
+//Motion is always expressed along the axes of the joint frame: revolute joints turn about its z
+//axis, prismatic joints slide along it and so on.
+use super::*;
+
+//one degree of freedom, about or along an axis (0 = x, 1 = y, 2 = z) of the joint frame
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum JointAxis{
+    Rotation(usize),
+    Translation(usize),
+}
+impl JointAxis{
+    pub fn motion(&self, value: f64) -> Transform{
+        let mut unit = na::Vector3::<f64>::zeros();
+        match self{
+            Self::Rotation(axis) => {
+                unit[*axis] = 1.0;
+                Transform::rotation_about_axis(Direction::from_vector(unit), value)
+            },
+            Self::Translation(axis) => {
+                unit[*axis] = value;
+                Transform::translation(Direction::from_vector(unit))
+            },
+        }
+    }
+    pub fn is_rotation(&self) -> bool{
+        matches!(self, Self::Rotation(_))
+    }
+    //short name for readouts, rz for a turn about z and so on
+    pub fn label(&self) -> String{
+        match self{
+            Self::Rotation(axis) => format!("r{}", ["x", "y", "z"][*axis]),
+            Self::Translation(axis) => format!("t{}", ["x", "y", "z"][*axis]),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum JointType{
+    //turns about z
+    Revolute,
+    //slides along z
+    Prismatic,
+    //turns about and slides along z, values are [angle, distance]
+    Cylindrical,
+    //turns about z, then the new y, then the new x, values are [angle z, angle y, angle x]
+    Spherical,
+    //slides in the xy plane and turns about z, values are [x, y, angle]
+    Planar,
+    Fixed,
+}
+impl JointType{
+    //the degrees of freedom in the order the joint values are given, applied one after the other
+    pub fn axes(&self) -> &'static [JointAxis]{
+        match self{
+            Self::Revolute => &[JointAxis::Rotation(2)],
+            Self::Prismatic => &[JointAxis::Translation(2)],
+            Self::Cylindrical => &[JointAxis::Rotation(2), JointAxis::Translation(2)],
+            Self::Spherical => &[JointAxis::Rotation(2), JointAxis::Rotation(1), JointAxis::Rotation(0)],
+            Self::Planar => &[JointAxis::Translation(0), JointAxis::Translation(1), JointAxis::Rotation(2)],
+            Self::Fixed => &[],
+        }
+    }
+    pub fn degrees_of_freedom(&self) -> usize{
+        self.axes().len()
+    }
+    //the child's joint frame relative to the parent's for the given values
+    pub fn motion(&self, values: &[f64]) -> Transform{
+        self.axes().iter().zip(values.iter())
+            .fold(Transform::identity(), |motion, (axis, value)| motion.compose(&axis.motion(*value)))
+    }
+}
+
+//what happens when a joint is given a value outside its range
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum LimitPolicy{
+    //the value is held at the nearer end of the range
+    Clamp,
+    //the values are refused and the joint keeps the ones it had
+    Reject,
+    //the value is taken as given and shows up in limit reports
+    Flag,
+}
+
+//Range of one degree of freedom, radians for turns and model units for slides. The velocity limit
+//(per second) is optional and checked between the frames of a motion study.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct DofLimit{
+    pub min: f64,
+    pub max: f64,
+    pub velocity: Option<f64>,
+}
+impl DofLimit{
+    pub fn new(min: f64, max: f64) -> Self{
+        DofLimit{
+            min: min,
+            max: max,
+            velocity: None,
+        }
+    }
+    pub fn state(&self, value: f64) -> LimitState{
+        let tolerance = model_tolerance();
+        if value < self.min - tolerance{
+            LimitState::BelowMinimum
+        }else if value > self.max + tolerance{
+            LimitState::AboveMaximum
+        }else if value <= self.min + tolerance{
+            LimitState::AtMinimum
+        }else if value >= self.max - tolerance{
+            LimitState::AtMaximum
+        }else{
+            LimitState::Within
+        }
+    }
+    pub fn clamp(&self, value: f64) -> f64{
+        value.clamp(self.min, self.max)
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum LimitState{
+    Within,
+    AtMinimum,
+    AtMaximum,
+    BelowMinimum,
+    AboveMaximum,
+    //moving faster than the velocity limit, with the speed it moved at
+    TooFast(f64),
+}
+impl LimitState{
+    pub fn is_beyond(&self) -> bool{
+        matches!(self, Self::BelowMinimum | Self::AboveMaximum | Self::TooFast(_))
+    }
+}
+
+#[derive(Clone,Debug)]
+pub enum JointError{
+    JointNotFound(usize),
+    //joint values have to match the degrees of freedom one to one
+    WrongValueCount{expected: usize, found: usize},
+    //a part cannot be jointed to itself
+    SameParentAndChild(NodeId),
+    //the child frame has no inverse
+    SingularFrame,
+    NoSuchDegreeOfFreedom(usize),
+    //min above max
+    InvalidLimit(DofLimit),
+    //the joint's policy is Reject and the value is outside the degree of freedom's range
+    BeyondLimit{dof: usize, value: f64, limit: DofLimit},
+    Kinematics(KinematicsError),
+    Scene(SceneGraphError),
+}
+impl From<SceneGraphError> for JointError{
+    fn from(error: SceneGraphError) -> Self{
+        JointError::Scene(error)
+    }
+}
+impl From<KinematicsError> for JointError{
+    fn from(error: KinematicsError) -> Self{
+        JointError::Kinematics(error)
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct Joint{
+    pub name: String,
+    joint_type: JointType,
+    //scene nodes of the two parts
+    parent: NodeId,
+    child: NodeId,
+    //joint frames in each part's local coordinates
+    parent_frame: Transform,
+    child_frame: Transform,
+    values: Vec<f64>,
+    //one per degree of freedom, None where it moves freely
+    limits: Vec<Option<DofLimit>>,
+    limit_policy: LimitPolicy,
+}
+
+impl Joint{
+    //Starts with every joint value at zero, where the two joint frames line up, without limits and
+    //with the Flag policy
+    pub fn new(name: &str, joint_type: JointType, parent: NodeId, parent_frame: Transform, child: NodeId, child_frame: Transform) -> Result<Self, JointError>{
+        if parent == child{
+            return Err(JointError::SameParentAndChild(parent));
+        }
+        if child_frame.inverse().is_none(){
+            return Err(JointError::SingularFrame);
+        }
+        Ok(Joint{
+            name: name.to_string(),
+            joint_type: joint_type,
+            parent: parent,
+            child: child,
+            parent_frame: parent_frame,
+            child_frame: child_frame,
+            values: vec![0.0; joint_type.degrees_of_freedom()],
+            limits: vec![None; joint_type.degrees_of_freedom()],
+            limit_policy: LimitPolicy::Flag,
+        })
+    }
+
+    pub fn get_type(&self) -> JointType{
+        self.joint_type
+    }
+    pub fn get_parent(&self) -> NodeId{
+        self.parent
+    }
+    pub fn get_child(&self) -> NodeId{
+        self.child
+    }
+    pub fn get_parent_frame(&self) -> Transform{
+        self.parent_frame
+    }
+    pub fn get_child_frame(&self) -> Transform{
+        self.child_frame
+    }
+    pub fn degrees_of_freedom(&self) -> usize{
+        self.joint_type.degrees_of_freedom()
+    }
+    pub fn get_values(&self) -> &Vec<f64>{
+        &self.values
+    }
+    //the values go through the limit policy first
+    pub fn set_values(&mut self, values: Vec<f64>) -> Result<(), JointError>{
+        self.values = self.limited_values(&values)?;
+        Ok(())
+    }
+    //What the joint would take for the values under its limit policy: clamped, refused or as given
+    pub fn limited_values(&self, values: &[f64]) -> Result<Vec<f64>, JointError>{
+        if values.len() != self.degrees_of_freedom(){
+            return Err(JointError::WrongValueCount{
+                expected: self.degrees_of_freedom(),
+                found: values.len(),
+            });
+        }
+        let mut limited = values.to_vec();
+        for (dof, (value, limit)) in limited.iter_mut().zip(self.limits.iter()).enumerate(){
+            let limit = match limit{
+                Some(limit) => limit,
+                None => continue,
+            };
+            match self.limit_policy{
+                LimitPolicy::Clamp => *value = limit.clamp(*value),
+                LimitPolicy::Reject if limit.state(*value).is_beyond() => return Err(JointError::BeyondLimit{
+                    dof: dof,
+                    value: *value,
+                    limit: *limit,
+                }),
+                _ => {},
+            }
+        }
+        Ok(limited)
+    }
+
+    pub fn get_limits(&self) -> &Vec<Option<DofLimit>>{
+        &self.limits
+    }
+    //The current values are not changed to fit, a joint can sit outside a range it was just given
+    //and be flagged for it
+    pub fn set_limit(&mut self, dof: usize, limit: Option<DofLimit>) -> Result<(), JointError>{
+        if dof >= self.degrees_of_freedom(){
+            return Err(JointError::NoSuchDegreeOfFreedom(dof));
+        }
+        if let Some(limit) = limit{
+            if !(limit.min <= limit.max){
+                return Err(JointError::InvalidLimit(limit));
+            }
+        }
+        self.limits[dof] = limit;
+        Ok(())
+    }
+    pub fn get_limit_policy(&self) -> LimitPolicy{
+        self.limit_policy
+    }
+    pub fn set_limit_policy(&mut self, policy: LimitPolicy){
+        self.limit_policy = policy;
+    }
+    //where each of the values sits against its degree of freedom's range
+    pub fn limit_states(&self, values: &[f64]) -> Vec<LimitState>{
+        values.iter().zip(self.limits.iter())
+            .map(|(value, limit)| limit.map_or(LimitState::Within, |limit| limit.state(*value)))
+            .collect()
+    }
+
+    //the child's frame relative to the parent's
+    pub fn motion(&self) -> Transform{
+        self.joint_type.motion(&self.values)
+    }
+    //where the child part has to be for the parent part to be at parent_world
+    pub fn child_world_transform(&self, parent_world: &Transform) -> Transform{
+        self.child_world_transform_with(parent_world, &self.values)
+    }
+    //the same for values other than the joint's own, which are left alone
+    pub fn child_world_transform_with(&self, parent_world: &Transform, values: &[f64]) -> Transform{
+        let child_frame_inverse = self.child_frame.inverse().unwrap_or(Transform::identity());
+        parent_world.compose(&self.parent_frame).compose(&self.joint_type.motion(values)).compose(&child_frame_inverse)
+    }
+}
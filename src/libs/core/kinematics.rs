@@ -0,0 +1,378 @@
+//Forward kinematics over a tree of joints. The tree is worked out once from the joints (which
+//joint comes before which, where each joint's values sit in the combined value vector) so
+//evaluating a configuration is one pass of transform products, cheap enough to run on every
+//slider movement. Joints that would close a loop are kept out of the tree, forward kinematics
+//ignores them and the loop closure solver makes the tree agree with them.
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Clone,Debug)]
+pub enum KinematicsError{
+    WrongValueCount{expected: usize, found: usize},
+    //a root part the caller gave no world transform for
+    MissingBase(NodeId),
+    //a joint with the Reject policy was given a value outside its range
+    BeyondLimit{joint: usize, dof: usize, value: f64},
+    //no joint values close the loops for the driving values, closure is the joint furthest from
+    //closing when the solver gave up
+    CannotAssemble{closure: usize, residual: f64},
+    //the coupling at this index cannot be used, see resolve_couplings
+    InvalidCoupling(usize),
+    //a driving value whose joint or degree of freedom does not exist
+    InvalidDrivingValue(JointValue),
+    //an index past the end of the combined value vector
+    NoSuchValue(usize),
+    Scene(SceneGraphError),
+}
+impl From<SceneGraphError> for KinematicsError{
+    fn from(error: SceneGraphError) -> Self{
+        KinematicsError::Scene(error)
+    }
+}
+
+//a joint refusing its slice of the combined value vector, as an error for the whole vector
+fn joint_values_error(joint: usize, error: JointError) -> KinematicsError{
+    match error{
+        JointError::BeyondLimit{dof, value, ..} => KinematicsError::BeyondLimit{
+            joint: joint,
+            dof: dof,
+            value: value,
+        },
+        JointError::WrongValueCount{expected, found} => KinematicsError::WrongValueCount{
+            expected: expected,
+            found: found,
+        },
+        JointError::Kinematics(error) => error,
+        //setting values fails for no other reason
+        _ => KinematicsError::NoSuchValue(joint),
+    }
+}
+
+//a degree of freedom that is at or past its limits
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct LimitReport{
+    pub joint: usize,
+    pub dof: usize,
+    pub value: f64,
+    pub state: LimitState,
+}
+
+#[derive(Clone,Debug)]
+pub struct KinematicTree{
+    //joint indices ordered so every joint comes after the joint that moves its parent
+    order: Vec<usize>,
+    //where each joint's values start in the combined value vector, indexed by joint
+    offsets: Vec<usize>,
+    total_degrees_of_freedom: usize,
+    //parts that are not the child of any tree joint, their pose comes from outside
+    roots: Vec<NodeId>,
+    //joints left out of the tree because they close a loop, in index order
+    closures: Vec<usize>,
+    //ordered so leaders are set before their followers
+    couplings: Vec<ResolvedCoupling>,
+}
+
+impl KinematicTree{
+    pub fn new(joints: &Vec<Joint>) -> Self{
+        let mut offsets = Vec::with_capacity(joints.len());
+        let mut total = 0;
+        for joint in joints.iter(){
+            offsets.push(total);
+            total += joint.degrees_of_freedom();
+        }
+
+        //a part already moved by an earlier joint is only held by the later ones
+        let mut closures: Vec<usize> = vec![];
+        for index in 0..joints.len(){
+            if joints[..index].iter().any(|joint| joint.get_child() == joints[index].get_child()){
+                closures.push(index);
+            }
+        }
+        loop{
+            let in_tree = |index: &usize| !closures.contains(index);
+            let mut roots: Vec<NodeId> = vec![];
+            for index in (0..joints.len()).filter(in_tree){
+                let parent = joints[index].get_parent();
+                let driven = (0..joints.len()).filter(in_tree).any(|other| joints[other].get_child() == parent);
+                if !driven && !roots.contains(&parent){
+                    roots.push(parent);
+                }
+            }
+            //breadth first from the roots
+            let mut order = vec![];
+            let mut frontier = roots.clone();
+            while !frontier.is_empty(){
+                let mut next = vec![];
+                for node in frontier.iter(){
+                    for index in (0..joints.len()).filter(in_tree){
+                        if joints[index].get_parent() == *node{
+                            order.push(index);
+                            next.push(joints[index].get_child());
+                        }
+                    }
+                }
+                frontier = next;
+            }
+            //Anything left over is on a ring of joints each moving the next. The last one added
+            //closes the ring, which makes the part it moves a root.
+            match (0..joints.len()).rev().find(|index| in_tree(index) && !order.contains(index)){
+                Some(closing) => closures.push(closing),
+                None => {
+                    closures.sort();
+                    return KinematicTree{
+                        order: order,
+                        offsets: offsets,
+                        total_degrees_of_freedom: total,
+                        roots: roots,
+                        closures: closures,
+                        couplings: vec![],
+                    };
+                },
+            }
+        }
+    }
+
+    //the same tree with the couplings' followers worked out from their leaders
+    pub fn with_couplings(mut self, joints: &Vec<Joint>, couplings: &[JointCoupling]) -> Result<Self, KinematicsError>{
+        self.couplings = resolve_couplings(joints, couplings)?;
+        Ok(self)
+    }
+    //sets every coupled value from its leaders
+    pub fn apply_couplings(&self, values: &mut Vec<f64>){
+        for coupling in self.couplings.iter(){
+            let value = coupling.leaders.iter().fold(coupling.offset, |sum, (leader, ratio)| sum + ratio * values.get(*leader).cloned().unwrap_or(0.0));
+            if let Some(follower) = values.get_mut(coupling.follower){
+                *follower = value;
+            }
+        }
+    }
+    //The followers outside their ranges, limits indexed like the combined value vector as from
+    //limits. A follower cannot be clamped on its own without breaking its coupling, so callers
+    //refuse or back off from values where any are.
+    pub fn followers_beyond_limits(&self, values: &[f64], limits: &Vec<Option<(f64, f64)>>) -> Vec<usize>{
+        let tolerance = model_tolerance();
+        self.couplings.iter().map(|coupling| coupling.follower).filter(|follower| {
+            match (values.get(*follower), limits.get(*follower)){
+                (Some(value), Some(Some((lowest, highest)))) => *value < lowest - tolerance || *value > highest + tolerance,
+                _ => false,
+            }
+        }).collect()
+    }
+    pub fn get_couplings(&self) -> &Vec<ResolvedCoupling>{
+        &self.couplings
+    }
+    //positions in the combined value vector set by couplings rather than freely
+    pub fn coupled_values(&self) -> Vec<usize>{
+        self.couplings.iter().map(|coupling| coupling.follower).collect()
+    }
+
+    pub fn total_degrees_of_freedom(&self) -> usize{
+        self.total_degrees_of_freedom
+    }
+    pub fn get_roots(&self) -> &Vec<NodeId>{
+        &self.roots
+    }
+    pub fn get_order(&self) -> &Vec<usize>{
+        &self.order
+    }
+    pub fn get_closures(&self) -> &Vec<usize>{
+        &self.closures
+    }
+    //range of the joint's values in the combined value vector
+    pub fn value_range(&self, joints: &Vec<Joint>, joint: usize) -> std::ops::Range<usize>{
+        self.offsets[joint]..self.offsets[joint] + joints[joint].degrees_of_freedom()
+    }
+
+    //the tree joints from a root down to the one moving the node, empty if no joint moves it
+    pub fn chain_to(&self, joints: &Vec<Joint>, node: NodeId) -> Vec<usize>{
+        let mut chain = vec![];
+        let mut current = node;
+        while let Some(index) = self.order.iter().find(|index| joints[**index].get_child() == current){
+            chain.push(*index);
+            current = joints[*index].get_parent();
+        }
+        chain.reverse();
+        chain
+    }
+
+    //every joint's current values one after the other, in joint order
+    pub fn gather_values(&self, joints: &Vec<Joint>) -> Vec<f64>{
+        joints.iter().flat_map(|joint| joint.get_values().iter().cloned()).collect()
+    }
+    //Sets every joint's values through its limit policy. If a joint refuses its values none of
+    //the joints change.
+    pub fn scatter_values(&self, joints: &mut Vec<Joint>, values: &[f64]) -> Result<(), KinematicsError>{
+        let limited = self.apply_limit_policies(joints, values)?;
+        for index in 0..joints.len(){
+            let range = self.value_range(joints, index);
+            joints[index].set_values(limited[range].to_vec()).map_err(|error| joint_values_error(index, error))?;
+        }
+        Ok(())
+    }
+
+    //Runs the combined values through every joint's limit policy, the result is what the joints
+    //would actually take
+    pub fn apply_limit_policies(&self, joints: &Vec<Joint>, values: &[f64]) -> Result<Vec<f64>, KinematicsError>{
+        self.check_count(values)?;
+        let mut limited = values.to_vec();
+        for index in 0..joints.len(){
+            let range = self.value_range(joints, index);
+            let joint_values = joints[index].limited_values(&values[range.clone()]).map_err(|error| joint_values_error(index, error))?;
+            limited[range].copy_from_slice(&joint_values);
+        }
+        Ok(limited)
+    }
+    //The ranges the solvers keep every value inside, one after the other and indexed like the
+    //combined value vector. A joint whose policy is Flag may go past its limits so has none here.
+    pub fn limits(&self, joints: &Vec<Joint>) -> Vec<Option<(f64, f64)>>{
+        joints.iter().flat_map(|joint| {
+            let flagged = joint.get_limit_policy() == LimitPolicy::Flag;
+            joint.get_limits().iter().map(move |limit| limit.filter(|_| !flagged).map(|limit| (limit.min, limit.max)))
+        }).collect()
+    }
+    //the degrees of freedom at or past either end of their range for the combined values
+    pub fn limit_report(&self, joints: &Vec<Joint>, values: &[f64]) -> Result<Vec<LimitReport>, KinematicsError>{
+        self.check_count(values)?;
+        let mut report = vec![];
+        for index in 0..joints.len(){
+            let range = self.value_range(joints, index);
+            let states = joints[index].limit_states(&values[range.clone()]);
+            for (dof, (value, state)) in values[range].iter().zip(states.into_iter()).enumerate(){
+                if state != LimitState::Within{
+                    report.push(LimitReport{
+                        joint: index,
+                        dof: dof,
+                        value: *value,
+                        state: state,
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+    //the degrees of freedom that moved faster than their velocity limit going from one set of
+    //combined values to the other in the time step
+    pub fn velocity_report(&self, joints: &Vec<Joint>, from: &[f64], to: &[f64], time_step: f64) -> Result<Vec<LimitReport>, KinematicsError>{
+        self.check_count(from)?;
+        self.check_count(to)?;
+        let mut report = vec![];
+        if time_step <= 0.0{
+            return Ok(report);
+        }
+        for index in 0..joints.len(){
+            let range = self.value_range(joints, index);
+            for (dof, limit) in joints[index].get_limits().iter().enumerate(){
+                let value_index = range.start + dof;
+                let speed = (to[value_index] - from[value_index]).abs() / time_step;
+                if let Some(DofLimit{velocity: Some(velocity), ..}) = limit{
+                    if speed > *velocity{
+                        report.push(LimitReport{
+                            joint: index,
+                            dof: dof,
+                            value: to[value_index],
+                            state: LimitState::TooFast(speed),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+    fn check_count(&self, values: &[f64]) -> Result<(), KinematicsError>{
+        if values.len() != self.total_degrees_of_freedom{
+            return Err(KinematicsError::WrongValueCount{
+                expected: self.total_degrees_of_freedom,
+                found: values.len(),
+            });
+        }
+        Ok(())
+    }
+
+    //World transform of every part in the tree for the combined joint values. base gives the world
+    //transform of the root parts.
+    pub fn forward(&self, joints: &Vec<Joint>, values: &[f64], base: impl Fn(NodeId) -> Option<Transform>) -> Result<HashMap<NodeId, Transform>, KinematicsError>{
+        self.check_count(values)?;
+        let mut world: HashMap<NodeId, Transform> = HashMap::new();
+        for root in self.roots.iter(){
+            world.insert(*root, base(*root).ok_or(KinematicsError::MissingBase(*root))?);
+        }
+        for index in self.order.iter(){
+            let joint = &joints[*index];
+            let child_world = joint.child_world_transform_with(&world[&joint.get_parent()], &values[self.value_range(joints, *index)]);
+            world.insert(joint.get_child(), child_world);
+        }
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn revolute(parent: NodeId, at: f64, child: NodeId) -> Joint{
+        Joint::new("", JointType::Revolute, parent, Transform::translation(Direction::new(at, 0.0, 0.0)), child, Transform::identity()).unwrap()
+    }
+
+    #[test]
+    fn refused_values_leave_every_joint_alone(){
+        let mut joints = vec![revolute(0, 0.0, 1), revolute(1, 1.0, 2)];
+        joints[1].set_limit(0, Some(DofLimit::new(-1.0, 1.0))).unwrap();
+        joints[1].set_limit_policy(LimitPolicy::Reject);
+        let tree = KinematicTree::new(&joints);
+        match tree.scatter_values(&mut joints, &[0.5, 2.0]){
+            Err(KinematicsError::BeyondLimit{joint, dof, value}) => assert_eq!((joint, dof, value), (1, 0, 2.0)),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(tree.gather_values(&joints), vec![0.0, 0.0]);
+
+        tree.scatter_values(&mut joints, &[0.5, 0.25]).unwrap();
+        assert_eq!(tree.gather_values(&joints), vec![0.5, 0.25]);
+    }
+
+    #[test]
+    fn flagged_limits_do_not_hold_the_solvers_back(){
+        let mut joints = vec![revolute(0, 0.0, 1), revolute(1, 1.0, 2)];
+        for joint in joints.iter_mut(){
+            joint.set_limit(0, Some(DofLimit::new(-1.0, 1.0))).unwrap();
+        }
+        joints[0].set_limit_policy(LimitPolicy::Clamp);
+        joints[1].set_limit_policy(LimitPolicy::Flag);
+        let tree = KinematicTree::new(&joints);
+        assert_eq!(tree.limits(&joints), vec![Some((-1.0, 1.0)), None]);
+        //still reported though
+        tree.scatter_values(&mut joints, &[0.0, 2.0]).unwrap();
+        let report = tree.limit_report(&joints, &tree.gather_values(&joints)).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].joint, 1);
+    }
+
+    #[test]
+    fn two_link_chain_ends_where_its_angles_say(){
+        let joints = vec![revolute(0, 0.0, 1), revolute(1, 1.0, 2)];
+        let tree = KinematicTree::new(&joints);
+        let end = |values: &[f64]| {
+            let world = tree.forward(&joints, values, |_| Some(Transform::identity())).unwrap();
+            world[&2].apply_point(&Point::new(1.0, 0.0, 0.0)).xyz()
+        };
+        let half_pi = std::f64::consts::FRAC_PI_2;
+        for (values, expected) in [
+            ([0.0, 0.0], na::Vector3::new(2.0, 0.0, 0.0)),
+            ([half_pi, 0.0], na::Vector3::new(0.0, 2.0, 0.0)),
+            ([0.0, half_pi], na::Vector3::new(1.0, 1.0, 0.0)),
+            ([half_pi, -half_pi], na::Vector3::new(1.0, 1.0, 0.0)),
+            ([std::f64::consts::FRAC_PI_4, std::f64::consts::PI], na::Vector3::zeros()),
+        ]{
+            assert!((end(&values) - expected).norm() < 1e-12, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn a_ring_of_joints_has_one_closure(){
+        //0 to 1 to 2 and back round from 2 to 0
+        let joints = vec![revolute(0, 0.0, 1), revolute(1, 1.0, 2), revolute(2, 1.0, 0)];
+        let tree = KinematicTree::new(&joints);
+        assert_eq!(tree.get_closures().len(), 1);
+        assert_eq!(tree.get_order().len(), 2);
+        assert!(!tree.get_order().contains(&tree.get_closures()[0]));
+    }
+}
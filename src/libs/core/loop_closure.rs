@@ -0,0 +1,335 @@
+//Closed loops of joints, like four-bars, slider-cranks and scissor lifts. The tree joints pose the
+//parts as usual and every joint left out of the tree (see KinematicTree) holds its two parts
+//together: its joint frames have to meet,
+//
+//    parent world * parent frame * motion(closure values) = child world * child frame
+//
+//Newton-Raphson on the mismatch of every closure at once finds the joint values that are not
+//driving. The Jacobian is taken by finite differences so any joint type works, and each step is
+//the least squares one so repeated constraints (a planar four-bar repeats three) do no harm.
+//Coupled values are never solved for, they follow their leaders wherever the solver moves them.
+use super::*;
+
+//step used for the finite difference Jacobian
+const DIFFERENCE_STEP: f64 = 1e-7;
+//largest move of a driving value solved in one go, radians or model units
+const CONTINUATION_STEP: f64 = 0.1;
+
+#[derive(Clone,Debug)]
+pub struct LoopSettings{
+    pub max_iterations: usize,
+    //largest mismatch, in model units and radians together, still counted as closed
+    pub tolerance: f64,
+}
+impl Default for LoopSettings{
+    fn default() -> Self{
+        LoopSettings{
+            max_iterations: 50,
+            tolerance: model_tolerance() * 10.0,
+        }
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct LoopSolution{
+    //the combined value vector with the dependent values solved
+    pub values: Vec<f64>,
+    pub iterations: usize,
+    pub residual: f64,
+    //Whether the solution went over to the other way of assembling the mechanism, e.g. a four-bar
+    //from open to crossed. None when the start was not assembled or sat on a singular pose, where
+    //the branch cannot be told.
+    pub branch_flipped: Option<bool>,
+}
+
+//the mismatch of every closure joint, translation then rotation, six rows each
+fn closure_residuals(tree: &KinematicTree, joints: &Vec<Joint>, values: &[f64], base: &impl Fn(NodeId) -> Option<Transform>) -> Result<na::DVector<f64>, KinematicsError>{
+    let mut values = values.to_vec();
+    tree.apply_couplings(&mut values);
+    let values = &values;
+    let world = tree.forward(joints, values, base)?;
+    let pose = |node: NodeId| world.get(&node).cloned().or_else(|| base(node)).ok_or(KinematicsError::MissingBase(node));
+    let mut residuals = na::DVector::<f64>::zeros(6 * tree.get_closures().len());
+    for (row, index) in tree.get_closures().iter().enumerate(){
+        let joint = &joints[*index];
+        let motion = joint.get_type().motion(&values[tree.value_range(joints, *index)]);
+        let parent_side = pose(joint.get_parent())?.compose(&joint.get_parent_frame()).compose(&motion);
+        let child_side = pose(joint.get_child())?.compose(&joint.get_child_frame());
+        let translation = child_side.translation_part().to_vector() - parent_side.translation_part().to_vector();
+        let rotation = rotation_vector(&(parent_side.rotation_part() * child_side.rotation_part().transpose()));
+        residuals.fixed_rows_mut::<3>(6 * row).copy_from(&translation);
+        residuals.fixed_rows_mut::<3>(6 * row + 3).copy_from(&rotation);
+    }
+    Ok(residuals)
+}
+
+//Values that move the loops: every closure's own and those of the tree joints between each
+//closure's two parts. Joints above where the two sides meet carry the whole loop and are left out.
+//A coupled value in a loop brings its leaders in with it.
+fn loop_values(tree: &KinematicTree, joints: &Vec<Joint>) -> Vec<usize>{
+    let mut loop_joints: Vec<usize> = vec![];
+    for index in tree.get_closures().iter(){
+        let parent_chain = tree.chain_to(joints, joints[*index].get_parent());
+        let child_chain = tree.chain_to(joints, joints[*index].get_child());
+        for joint in parent_chain.iter().filter(|joint| !child_chain.contains(joint))
+            .chain(child_chain.iter().filter(|joint| !parent_chain.contains(joint)))
+            .chain(std::iter::once(index)){
+            if !loop_joints.contains(joint){
+                loop_joints.push(*joint);
+            }
+        }
+    }
+    loop_joints.sort();
+    let mut values: Vec<usize> = loop_joints.into_iter().flat_map(|joint| tree.value_range(joints, joint)).collect();
+    //followers come after their leaders, so going backwards reaches leaders of leaders too
+    for coupling in tree.get_couplings().iter().rev(){
+        if values.contains(&coupling.follower){
+            for (leader, _) in coupling.leaders.iter(){
+                if !values.contains(leader){
+                    values.push(*leader);
+                }
+            }
+        }
+    }
+    values.sort();
+    values
+}
+
+fn residual_jacobian(tree: &KinematicTree, joints: &Vec<Joint>, values: &[f64], unknowns: &Vec<usize>, residuals: &na::DVector<f64>, base: &impl Fn(NodeId) -> Option<Transform>) -> Result<na::DMatrix<f64>, KinematicsError>{
+    let mut jacobian = na::DMatrix::<f64>::zeros(residuals.len(), unknowns.len());
+    let mut stepped = values.to_vec();
+    for (column, unknown) in unknowns.iter().enumerate(){
+        stepped[*unknown] += DIFFERENCE_STEP;
+        let difference = (closure_residuals(tree, joints, &stepped, base)? - residuals) / DIFFERENCE_STEP;
+        jacobian.set_column(column, &difference);
+        stepped[*unknown] = values[*unknown];
+    }
+    Ok(jacobian)
+}
+
+//Which way the dependent values are assembled, as the sign of the Jacobian seen through a fixed
+//basis of its columns. Returns the basis with the sign so later poses can be compared against it.
+fn branch_reference(jacobian: &na::DMatrix<f64>) -> Option<(na::DMatrix<f64>, f64)>{
+    if jacobian.ncols() == 0 || jacobian.ncols() > jacobian.nrows() || rank_of(jacobian) < jacobian.ncols(){
+        return None;
+    }
+    let qr = jacobian.clone().qr();
+    let sign = qr.r().determinant().signum();
+    Some((qr.q(), sign))
+}
+fn rank_of(matrix: &na::DMatrix<f64>) -> usize{
+    let svd = matrix.clone().svd(false, false);
+    svd.rank(1e-9 * svd.singular_values.max().max(1.0))
+}
+
+//Newton-Raphson from values until the loops close or the iterations run out, halving any step that
+//would make the mismatch worse or take a coupled value out of its range. Returns the iterations
+//used and the mismatch left.
+fn newton_raphson(tree: &KinematicTree, joints: &Vec<Joint>, values: &mut Vec<f64>, unknowns: &Vec<usize>, settings: &LoopSettings, base: &impl Fn(NodeId) -> Option<Transform>) -> Result<(usize, na::DVector<f64>), KinematicsError>{
+    let limits = tree.limits(joints);
+    let mut residuals = closure_residuals(tree, joints, values, base)?;
+    for iteration in 0..settings.max_iterations{
+        if residuals.amax() <= settings.tolerance || unknowns.is_empty(){
+            return Ok((iteration, residuals));
+        }
+        let jacobian = residual_jacobian(tree, joints, values, unknowns, &residuals, base)?;
+        let step = match jacobian.svd(true, true).solve(&(-&residuals), 1e-12){
+            Ok(step) => step,
+            Err(_) => return Ok((iteration, residuals)),
+        };
+        let mut scale = 1.0;
+        loop{
+            let mut trial = values.clone();
+            for (unknown, change) in unknowns.iter().zip(step.iter()){
+                trial[*unknown] += change * scale;
+                if let Some(Some((lowest, highest))) = limits.get(*unknown){
+                    trial[*unknown] = trial[*unknown].clamp(*lowest, *highest);
+                }
+            }
+            //followers are only checked, clamping them would break their couplings
+            let mut coupled = trial.clone();
+            tree.apply_couplings(&mut coupled);
+            let followers_within = tree.followers_beyond_limits(&coupled, &limits).is_empty();
+            let trial_residuals = closure_residuals(tree, joints, &trial, base)?;
+            if followers_within && (trial_residuals.norm() < residuals.norm() || scale < 1.0 / 64.0){
+                *values = trial;
+                residuals = trial_residuals;
+                break;
+            }
+            if scale < 1.0 / 64.0{
+                //every step this way takes a follower past its range
+                return Ok((iteration, residuals));
+            }
+            scale *= 0.5;
+        }
+    }
+    Ok((settings.max_iterations, residuals))
+}
+
+//Solves the loops for the values not in driving, starting from values (the combined value vector
+//with the driving values already set). previous is where the mechanism was before: the branch is
+//judged against it and, when it was assembled, the driving values are moved there from it in
+//small steps so the solution follows the branch it started on. Values are kept inside the joints'
+//limits after every step, coupled values included.
+pub fn solve_loop_closure(tree: &KinematicTree, joints: &Vec<Joint>, previous: &[f64], values: &[f64], driving: &[usize], settings: &LoopSettings, base: impl Fn(NodeId) -> Option<Transform>) -> Result<LoopSolution, KinematicsError>{
+    if let Some(index) = driving.iter().find(|index| **index >= values.len() || **index >= previous.len()){
+        return Err(KinematicsError::NoSuchValue(*index));
+    }
+    let coupled = tree.coupled_values();
+    let unknowns: Vec<usize> = loop_values(tree, joints).into_iter().filter(|index| !driving.contains(index) && !coupled.contains(index)).collect();
+
+    let previous_residuals = closure_residuals(tree, joints, previous, &base)?;
+    let reference = if previous_residuals.amax() <= settings.tolerance{
+        branch_reference(&residual_jacobian(tree, joints, previous, &unknowns, &previous_residuals, &base)?)
+    }else{
+        None
+    };
+    let steps = match reference{
+        Some(_) => {
+            let largest_move = driving.iter().map(|index| (values[*index] - previous[*index]).abs()).fold(0.0, f64::max);
+            ((largest_move / CONTINUATION_STEP).ceil() as usize).max(1)
+        },
+        None => 1,
+    };
+
+    let mut current = values.to_vec();
+    let mut iterations = 0;
+    for step in 1..=steps{
+        let fraction = step as f64 / steps as f64;
+        for index in driving.iter(){
+            current[*index] = previous[*index] + (values[*index] - previous[*index]) * fraction;
+        }
+        let (used, residuals) = newton_raphson(tree, joints, &mut current, &unknowns, settings, &base)?;
+        iterations += used;
+        if residuals.amax() > settings.tolerance{
+            let worst = (0..tree.get_closures().len())
+                .max_by(|one, two| residuals.rows(6 * one, 6).amax().total_cmp(&residuals.rows(6 * two, 6).amax()))
+                .unwrap_or(0);
+            return Err(KinematicsError::CannotAssemble{
+                closure: tree.get_closures().get(worst).cloned().unwrap_or(0),
+                residual: residuals.amax(),
+            });
+        }
+    }
+
+    tree.apply_couplings(&mut current);
+    let residuals = closure_residuals(tree, joints, &current, &base)?;
+    let branch_flipped = match reference{
+        Some((basis, sign)) => {
+            let jacobian = residual_jacobian(tree, joints, &current, &unknowns, &residuals, &base)?;
+            let seen = (basis.transpose() * jacobian).determinant();
+            if seen.abs() > f64::EPSILON {Some(seen.signum() != sign)} else {None}
+        },
+        None => None,
+    };
+    Ok(LoopSolution{
+        values: current,
+        iterations: iterations,
+        residual: residuals.amax(),
+        branch_flipped: branch_flipped,
+    })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn revolute(parent: NodeId, at: f64, child: NodeId, child_at: f64) -> Joint{
+        let frame = |x: f64| Transform::translation(Direction::new(x, 0.0, 0.0));
+        Joint::new("", JointType::Revolute, parent, frame(at), child, frame(child_at)).unwrap()
+    }
+
+    //Ground 0, crank 1, coupler 2 and rocker 3, all turning about z. The crank turns at the origin,
+    //the rocker at (ground, 0), and the coupler to rocker joint closes the loop. Values are crank,
+    //coupler, rocker, closure.
+    fn four_bar(crank: f64, coupler: f64, rocker: f64, ground: f64) -> Vec<Joint>{
+        vec![
+            revolute(0, 0.0, 1, 0.0),
+            revolute(1, crank, 2, 0.0),
+            revolute(0, ground, 3, 0.0),
+            revolute(2, coupler, 3, rocker),
+        ]
+    }
+
+    fn solve(joints: &Vec<Joint>, previous: &[f64], values: &[f64]) -> Result<LoopSolution, KinematicsError>{
+        let tree = KinematicTree::new(joints);
+        solve_loop_closure(&tree, joints, previous, values, &[0], &LoopSettings::default(), |_| Some(Transform::identity()))
+    }
+
+    fn world_origin(joints: &Vec<Joint>, values: &[f64], node: NodeId, offset: f64) -> na::Vector3<f64>{
+        let world = KinematicTree::new(joints).forward(joints, values, |_| Some(Transform::identity())).unwrap();
+        world[&node].apply_point(&Point::new(offset, 0.0, 0.0)).xyz()
+    }
+
+    #[test]
+    fn four_bar_closes_for_a_driven_crank(){
+        let joints = four_bar(1.0, 3.0, 2.5, 3.0);
+        //roughly the open pose for the crank at 0, close enough for the solver to pull together
+        let guess = vec![0.0, 1.0, 1.7, 0.7];
+        let assembled = solve(&joints, &guess, &guess).unwrap();
+        assert!(assembled.residual <= LoopSettings::default().tolerance);
+        assert_eq!(assembled.values[0], 0.0);
+
+        let mut driven = assembled.values.clone();
+        driven[0] = 0.8;
+        let solution = solve(&joints, &assembled.values, &driven).unwrap();
+        assert!(solution.residual <= LoopSettings::default().tolerance);
+        assert_eq!(solution.values[0], 0.8);
+        assert_eq!(solution.branch_flipped, Some(false));
+        //the coupler's far end sits on the rocker's far end
+        let coupler_end = world_origin(&joints, &solution.values, 2, 3.0);
+        let rocker_end = world_origin(&joints, &solution.values, 3, 2.5);
+        assert!((coupler_end - rocker_end).norm() < 1e-6);
+        assert!((world_origin(&joints, &solution.values, 1, 1.0) - na::Vector3::new(0.8_f64.cos(), 0.8_f64.sin(), 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn crossing_over_is_reported_as_a_branch_flip(){
+        let joints = four_bar(1.0, 3.0, 2.5, 3.0);
+        let guess = vec![0.0, 1.0, 1.7, 0.7];
+        let open = solve(&joints, &guess, &guess).unwrap();
+        //starting the solver from the mirror image lands on the crossed way of assembling it
+        let crossed_guess: Vec<f64> = open.values.iter().map(|value| -value).collect();
+        let crossed = solve(&joints, &open.values, &crossed_guess).unwrap();
+        assert!(crossed.residual <= LoopSettings::default().tolerance);
+        assert_eq!(crossed.branch_flipped, Some(true));
+        //and back from there is a flip too, while staying put is not
+        assert_eq!(solve(&joints, &crossed.values, &open.values).unwrap().branch_flipped, Some(true));
+        assert_eq!(solve(&joints, &open.values, &open.values).unwrap().branch_flipped, Some(false));
+    }
+
+    #[test]
+    fn slider_crank_closes(){
+        //ground 0, crank 1, connecting rod 2 and slider 3 sliding along x
+        let along_x = Transform::rotation_about_axis(Direction::new(0.0, 1.0, 0.0), std::f64::consts::FRAC_PI_2);
+        let joints = vec![
+            revolute(0, 0.0, 1, 0.0),
+            revolute(1, 1.0, 2, 0.0),
+            Joint::new("", JointType::Prismatic, 0, along_x, 3, along_x).unwrap(),
+            revolute(2, 3.0, 3, 0.0),
+        ];
+        let guess = vec![0.5, -0.6, 3.8, 0.15];
+        let assembled = solve(&joints, &guess, &guess).unwrap();
+        let mut driven = assembled.values.clone();
+        driven[0] = 1.5;
+        let solution = solve(&joints, &assembled.values, &driven).unwrap();
+        assert!(solution.residual <= LoopSettings::default().tolerance);
+        let expected = 1.5_f64.cos() + (9.0 - 1.5_f64.sin().powi(2)).sqrt();
+        assert!((solution.values[2] - expected).abs() < 1e-6);
+        assert!((world_origin(&joints, &solution.values, 3, 0.0) - na::Vector3::new(expected, 0.0, 0.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn unreachable_crank_angle_cannot_assemble(){
+        //with the crank pointing away from the rocker the coupler and rocker together are too short
+        let joints = four_bar(1.0, 1.2, 2.5, 3.0);
+        let guess = vec![0.0, 1.5, 2.0, 0.5];
+        let assembled = solve(&joints, &guess, &guess).unwrap();
+        let mut driven = assembled.values.clone();
+        driven[0] = std::f64::consts::PI;
+        match solve(&joints, &assembled.values, &driven){
+            Err(KinematicsError::CannotAssemble{closure, ..}) => assert_eq!(closure, 3),
+            other => panic!("expected CannotAssemble, got {:?}", other),
+        }
+    }
+}
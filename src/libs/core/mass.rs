@@ -0,0 +1,204 @@
+//Mass properties of closed PerspectiveObjects. Everything is found with the divergence theorem:
+//each side is fanned into triangles and every triangle forms a tetrahedron with the origin, the
+//signed sums of those tetrahedra give the volume, first moments and second moments of the solid.
+use super::*;
+
+#[derive(Clone,Debug)]
+pub enum MassPropertiesError{
+    //some edge is not shared by exactly two sides, the volume would be meaningless
+    OpenSolid,
+    ZeroVolume,
+    InvalidDensity(f64),
+}
+
+#[derive(Clone,Debug)]
+pub struct MassProperties{
+    pub density: f64,
+    pub volume: f64,
+    pub mass: f64,
+    pub surface_area: f64,
+    pub centre_of_mass: Point,
+    //inertia tensor about the centre of mass, axes aligned with the world axes (so it follows the
+    //object's current orientation)
+    pub inertia_about_centroid: na::Matrix3<f64>,
+}
+impl MassProperties{
+    //inertia tensor about an arbitrary point using the parallel axis theorem
+    pub fn inertia_about_point(&self, point: Point) -> na::Matrix3<f64>{
+        let offset = point.xyz() - self.centre_of_mass.xyz();
+        self.inertia_about_centroid +
+            (na::Matrix3::<f64>::identity() * offset.dot(&offset) - offset * offset.transpose()) * self.mass
+    }
+    //moment of inertia about the axis through point with the given direction
+    pub fn inertia_about_axis(&self, point: Point, direction: na::Vector3<f64>) -> f64{
+        let axis = match direction.try_normalize(f64::EPSILON){
+            Some(axis) => axis,
+            None => return 0.0,
+        };
+        (axis.transpose() * self.inertia_about_point(point) * axis)[(0, 0)]
+    }
+    //principal moments (ascending) with the matching principal axes as columns
+    pub fn principal_moments(&self) -> (na::Vector3<f64>, na::Matrix3<f64>){
+        let eigen = self.inertia_about_centroid.symmetric_eigen();
+        let mut order = [0, 1, 2];
+        order.sort_by(|a, b| eigen.eigenvalues[*a].total_cmp(&eigen.eigenvalues[*b]));
+        let moments = na::Vector3::new(eigen.eigenvalues[order[0]], eigen.eigenvalues[order[1]], eigen.eigenvalues[order[2]]);
+        let axes = na::Matrix3::from_columns(&[
+            eigen.eigenvectors.column(order[0]).into_owned(),
+            eigen.eigenvectors.column(order[1]).into_owned(),
+            eigen.eigenvectors.column(order[2]).into_owned(),
+        ]);
+        (moments, axes)
+    }
+}
+
+//Unit density integrals over the solid about the origin, kept separate so the centre calculation
+//does not need a density or a closed solid check
+pub struct VolumeIntegrals{
+    pub volume: f64,
+    pub first_moment: na::Vector3<f64>,
+    //covariance matrix, the integral of x * x^T over the volume
+    pub covariance: na::Matrix3<f64>,
+}
+
+pub fn integrate_sides(sides: &Vec<Surface>) -> VolumeIntegrals{
+    //covariance of the canonical tetrahedron (0, e1, e2, e3)
+    let canonical = na::Matrix3::new(
+        2.0, 1.0, 1.0,
+        1.0, 2.0, 1.0,
+        1.0, 1.0, 2.0,
+        ) / 120.0;
+    let mut volume = 0.0;
+    let mut first_moment = na::Vector3::<f64>::zeros();
+    let mut covariance = na::Matrix3::<f64>::zeros();
+
+    for side in sides.iter(){
+        let points = side.get_key_points();
+        if points.len() < 3{
+            continue;
+        }
+        let anchor = points[0].xyz();
+        for i in 1..(points.len() - 1){
+            let second = points[i].xyz();
+            let third = points[i + 1].xyz();
+            let columns = na::Matrix3::from_columns(&[anchor, second, third]);
+            let determinant = anchor.dot(&second.cross(&third));
+
+            volume += determinant / 6.0;
+            first_moment += (anchor + second + third) * (determinant / 24.0);
+            covariance += columns * canonical * columns.transpose() * determinant;
+        }
+    }
+    //sides wound inwards give a negative volume, everything flips together
+    if volume < 0.0{
+        volume = -volume;
+        first_moment = -first_moment;
+        covariance = -covariance;
+    }
+    VolumeIntegrals{
+        volume: volume,
+        first_moment: first_moment,
+        covariance: covariance,
+    }
+}
+
+pub fn surface_area(sides: &Vec<Surface>) -> f64{
+    sides.iter().map(|side| side.normal_unnormalised().norm() / 2.0).sum()
+}
+
+impl PerspectiveObject{
+    pub fn surface_area(&self) -> f64{
+        surface_area(&self.get_sides())
+    }
+
+    pub fn mass_properties(&self, density: f64) -> Result<MassProperties, MassPropertiesError>{
+        if density <= 0.0 || !density.is_finite(){
+            return Err(MassPropertiesError::InvalidDensity(density));
+        }
+        if !self.is_closed(){
+            return Err(MassPropertiesError::OpenSolid);
+        }
+        let integrals = integrate_sides(&self.get_sides());
+        if integrals.volume <= f64::EPSILON{
+            return Err(MassPropertiesError::ZeroVolume);
+        }
+        let mass = integrals.volume * density;
+        let centroid = integrals.first_moment / integrals.volume;
+
+        //shift the covariance to the centroid then turn it into the inertia tensor
+        let covariance = (integrals.covariance - centroid * centroid.transpose() * integrals.volume) * density;
+        let inertia = na::Matrix3::<f64>::identity() * covariance.trace() - covariance;
+
+        Ok(MassProperties{
+            density: density,
+            volume: integrals.volume,
+            mass: mass,
+            surface_area: self.surface_area(),
+            centre_of_mass: Point::from_xyz(centroid),
+            inertia_about_centroid: inertia,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //axis aligned box between the corners, sides wound outwards
+    fn cuboid(min: na::Vector3<f64>, max: na::Vector3<f64>) -> PerspectiveObject{
+        let corner = |index: usize| Point::from_xyz(na::Vector3::new(
+            if index & 1 == 0 {min.x} else {max.x},
+            if index & 2 == 0 {min.y} else {max.y},
+            if index & 4 == 0 {min.z} else {max.z},
+        ));
+        let faces = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        PerspectiveObject::new(faces.iter().map(|face| Surface::new(face.iter().map(|index| corner(*index)).collect())).collect())
+    }
+    fn assert_close(one: &na::Matrix3<f64>, two: &na::Matrix3<f64>){
+        assert!((one - two).amax() < 1e-9, "{} != {}", one, two);
+    }
+
+    #[test]
+    fn unit_cube(){
+        let cube = cuboid(na::Vector3::zeros(), na::Vector3::new(1.0, 1.0, 1.0));
+        let properties = cube.mass_properties(2.0).unwrap();
+        assert!((properties.volume - 1.0).abs() < 1e-12);
+        assert!((properties.mass - 2.0).abs() < 1e-12);
+        assert!((properties.surface_area - 6.0).abs() < 1e-12);
+        assert!((properties.centre_of_mass.xyz() - na::Vector3::new(0.5, 0.5, 0.5)).norm() < 1e-12);
+        //m (a^2 + b^2) / 12 about every axis
+        assert_close(&properties.inertia_about_centroid, &(na::Matrix3::identity() * (2.0 * 2.0 / 12.0)));
+        //about a corner the products of inertia appear, -m/4 off the diagonal and 2m/3 on it
+        let corner = properties.inertia_about_point(Point::origin());
+        assert_close(&corner, &na::Matrix3::new(
+            4.0 / 3.0, -0.5, -0.5,
+            -0.5, 4.0 / 3.0, -0.5,
+            -0.5, -0.5, 4.0 / 3.0,
+        ));
+    }
+
+    #[test]
+    fn moved_and_turned_box(){
+        //1 x 2 x 3, so the moments about its own axes are 6.5, 5 and 2.5 with density 1
+        let mut block = cuboid(na::Vector3::new(-0.5, -1.0, -1.5), na::Vector3::new(0.5, 1.0, 1.5));
+        let turn = na::Rotation3::from_axis_angle(&na::Vector3::z_axis(), 0.5).into_inner();
+        let centre = na::Vector3::new(3.0, -2.0, 1.0);
+        block.set_pose(Point::from_xyz(centre), turn);
+        let properties = block.mass_properties(1.0).unwrap();
+        assert!((properties.volume - 6.0).abs() < 1e-9);
+        assert!((properties.centre_of_mass.xyz() - centre).norm() < 1e-9);
+
+        let own_axes = na::Matrix3::from_diagonal(&na::Vector3::new(6.5, 5.0, 2.5));
+        assert_close(&properties.inertia_about_centroid, &(turn * own_axes * turn.transpose()));
+        let (moments, _) = properties.principal_moments();
+        assert!((moments - na::Vector3::new(2.5, 5.0, 6.5)).norm() < 1e-9);
+
+        //parallel axis theorem out to the origin
+        let expected = turn * own_axes * turn.transpose() + (na::Matrix3::identity() * centre.norm_squared() - centre * centre.transpose()) * 6.0;
+        assert_close(&properties.inertia_about_point(Point::origin()), &expected);
+        //the z axis through the origin picks up m (x^2 + y^2)
+        let about_z = properties.inertia_about_axis(Point::origin(), na::Vector3::z());
+        let turned_z = (turn * own_axes * turn.transpose())[(2, 2)];
+        assert!((about_z - (turned_z + 6.0 * 13.0)).abs() < 1e-9);
+    }
+}
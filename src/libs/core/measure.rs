@@ -0,0 +1,585 @@
+//Measuring picked geometry in the viewer. Picking goes through the camera: the cursor becomes a ray
+//and the vertex, edge or face under it is found in world space. Vertices win over edges and edges
+//over faces since the smaller targets are the harder ones to hit, but nothing hidden behind a face
+//can be picked. The measurements themselves only look at the picked geometry.
+use super::*;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum PickKind{
+    Vertex,
+    Edge,
+    Face,
+}
+
+#[derive(Clone,Debug)]
+pub enum PickedEntity{
+    Vertex(Point),
+    Edge(Line),
+    Face(Surface),
+}
+impl PickedEntity{
+    pub fn kind(&self) -> PickKind{
+        match self{
+            Self::Vertex(_) => PickKind::Vertex,
+            Self::Edge(_) => PickKind::Edge,
+            Self::Face(_) => PickKind::Face,
+        }
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct Pick{
+    //index into the object list the pick was made from
+    pub object_index: usize,
+    pub entity: PickedEntity,
+    //where on the entity the cursor was
+    pub point: Point,
+    //along the pick ray from the camera
+    pub distance: f64,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum MeasureMode{
+    //between the picked points, whatever they were picked on
+    Distance,
+    //closest approach of two picked entities
+    MinimumDistance,
+    Angle,
+    //circle through the vertices picked on an arc, edges count with both their ends
+    Radius,
+    Area,
+}
+impl MeasureMode{
+    pub fn accepts(&self) -> &'static [PickKind]{
+        match self{
+            Self::Distance | Self::MinimumDistance => &[PickKind::Vertex, PickKind::Edge, PickKind::Face],
+            Self::Angle => &[PickKind::Edge, PickKind::Face],
+            Self::Radius => &[PickKind::Vertex, PickKind::Edge],
+            Self::Area => &[PickKind::Face],
+        }
+    }
+    //enough picks have been made to measure
+    pub fn is_complete(&self, picks: &Vec<Pick>) -> bool{
+        match self{
+            Self::Distance | Self::MinimumDistance | Self::Angle => picks.len() >= 2,
+            Self::Radius => arc_points(picks).len() >= 3,
+            Self::Area => !picks.is_empty(),
+        }
+    }
+}
+
+#[derive(Clone,Debug)]
+pub enum MeasureError{
+    NotEnoughPicks(MeasureMode),
+    UnsupportedPick(MeasureMode, PickKind),
+    //edges with no length, faces with no area, arc points on a straight line
+    Degenerate(MeasureMode),
+}
+
+#[derive(Clone,Debug)]
+pub enum Measurement{
+    Distance{from: Point, to: Point, distance: f64},
+    MinimumDistance(ClosestPoints),
+    //radians between 0 and pi, drawn as the arc from arm_one to arm_two around vertex
+    Angle{angle: f64, vertex: Point, arm_one: Point, arm_two: Point},
+    //fit_error is the furthest any of the points is off the circle
+    Radius{centre: Point, radius: f64, normal: Direction, fit_error: f64},
+    Area{area: f64, centroid: Point, outline: Surface},
+}
+
+impl Measurement{
+    pub fn value(&self) -> f64{
+        match self{
+            Self::Distance{distance, ..} => *distance,
+            Self::MinimumDistance(closest) => closest.distance,
+            Self::Angle{angle, ..} => *angle,
+            Self::Radius{radius, ..} => *radius,
+            Self::Area{area, ..} => *area,
+        }
+    }
+    pub fn report(&self) -> String{
+        match self{
+            Self::Distance{distance, from, to} => {
+                let delta = *to - *from;
+                format!("distance {:.4} (dx {:.4}, dy {:.4}, dz {:.4})", distance, delta.to_vector().x, delta.to_vector().y, delta.to_vector().z)
+            },
+            Self::MinimumDistance(closest) => format!("minimum distance {:.4}", closest.distance),
+            Self::Angle{angle, ..} => format!("angle {:.3}°", angle.to_degrees()),
+            Self::Radius{radius, fit_error, ..} => format!("radius {:.4} (diameter {:.4}, fit error {:.2e})", radius, radius * 2.0, fit_error),
+            Self::Area{area, ..} => format!("area {:.4}", area),
+        }
+    }
+    //what to draw over the view to show the measurement
+    pub fn overlay_lines(&self) -> Vec<Line>{
+        match self{
+            Self::Distance{from, to, ..} => vec![Line::new(*from, *to)],
+            Self::MinimumDistance(closest) => vec![Line::new(closest.on_first, closest.on_second)],
+            Self::Angle{angle, vertex, arm_one, arm_two} => {
+                let mut lines = vec![Line::new(*vertex, *arm_one), Line::new(*vertex, *arm_two)];
+                let (one, two) = (*arm_one - *vertex, *arm_two - *vertex);
+                let arc_radius = one.length().min(two.length()) * 0.3;
+                //no arc for a straight or zero angle, there is no plane to draw it in
+                if let (Some(unit_one), Some(unit_two)) = (one.normalize(), two.normalize()){
+                    if angle.sin() > 1e-6{
+                        let segments = 16;
+                        let along_arc = |fraction: f64| *vertex + (unit_one * ((1.0 - fraction) * angle).sin() + unit_two * (fraction * angle).sin()) * (arc_radius / angle.sin());
+                        for i in 0..segments{
+                            lines.push(Line::new(
+                                along_arc(i as f64 / segments as f64),
+                                along_arc((i + 1) as f64 / segments as f64),
+                            ));
+                        }
+                    }
+                }
+                lines
+            },
+            Self::Radius{centre, radius, normal, ..} => {
+                let axis_u = Direction::from_vector(any_perpendicular(&normal.to_vector()));
+                let axis_v = normal.cross(&axis_u);
+                let around = |angle: f64| *centre + (axis_u * angle.cos() + axis_v * angle.sin()) * *radius;
+                let segments = 48;
+                let mut lines = vec![Line::new(*centre, around(0.0))];
+                for i in 0..segments{
+                    let step = 2.0 * std::f64::consts::PI / segments as f64;
+                    lines.push(Line::new(around(i as f64 * step), around((i + 1) as f64 * step)));
+                }
+                lines
+            },
+            Self::Area{outline, ..} => surface_outline(outline),
+        }
+    }
+}
+
+fn surface_outline(surface: &Surface) -> Vec<Line>{
+    let corners = surface.get_key_points();
+    (0..corners.len()).map(|i| Line::new(corners[i], corners[(i + 1) % corners.len()])).collect()
+}
+
+fn swapped(closest: ClosestPoints) -> ClosestPoints{
+    ClosestPoints{
+        on_first: closest.on_second,
+        on_second: closest.on_first,
+        distance: closest.distance,
+    }
+}
+
+fn closer(best: Option<ClosestPoints>, candidate: ClosestPoints) -> Option<ClosestPoints>{
+    match best{
+        Some(current) if current.distance <= candidate.distance => Some(current),
+        _ => Some(candidate),
+    }
+}
+
+//Crossing the surface means touching it, otherwise the closest approach is to its outline or from
+//one of the segment's ends. Exact for non convex surfaces too.
+fn segment_surface_closest_points(segment: &Line, surface: &Surface) -> Option<ClosestPoints>{
+    let corners = surface.get_key_points();
+    if corners.is_empty(){
+        return None;
+    }
+    if let LinePlaneResult::Intersection{point, parameter} = line_plane_intersection(segment, &corners[0], &Direction::from_vector(surface.normal())){
+        if (0.0..=1.0).contains(&parameter) && polygon_contains(surface, &point){
+            return Some(ClosestPoints{
+                on_first: point,
+                on_second: point,
+                distance: 0.0,
+            });
+        }
+    }
+    let mut best = None;
+    for i in 0..corners.len(){
+        best = closer(best, segment_segment_closest_points(segment, &Line::new(corners[i], corners[(i + 1) % corners.len()])));
+    }
+    for end in [segment.point_a, segment.point_b]{
+        if let Some((closest, distance)) = point_surface_closest(&end, surface){
+            best = closer(best, ClosestPoints{
+                on_first: end,
+                on_second: closest,
+                distance: distance,
+            });
+        }
+    }
+    best
+}
+
+//Closest approach between any two picked entities, on_first lies on first
+pub fn minimum_distance(first: &PickedEntity, second: &PickedEntity) -> Option<ClosestPoints>{
+    match (first, second){
+        (PickedEntity::Vertex(one), PickedEntity::Vertex(two)) => Some(ClosestPoints{
+            on_first: *one,
+            on_second: *two,
+            distance: (*two - *one).length(),
+        }),
+        (PickedEntity::Vertex(point), PickedEntity::Edge(edge)) => {
+            let (closest, distance) = point_segment_closest(point, edge);
+            Some(ClosestPoints{
+                on_first: *point,
+                on_second: closest,
+                distance: distance,
+            })
+        },
+        (PickedEntity::Vertex(point), PickedEntity::Face(face)) => {
+            let (closest, distance) = point_surface_closest(point, face)?;
+            Some(ClosestPoints{
+                on_first: *point,
+                on_second: closest,
+                distance: distance,
+            })
+        },
+        (PickedEntity::Edge(one), PickedEntity::Edge(two)) => Some(segment_segment_closest_points(one, two)),
+        (PickedEntity::Edge(edge), PickedEntity::Face(face)) => segment_surface_closest_points(edge, face),
+        (PickedEntity::Face(one), PickedEntity::Face(two)) => {
+            match polygon_polygon_intersection(one, two){
+                PolygonIntersection::Crossing(segments) => {
+                    let touching = segments[0].point_a;
+                    return Some(ClosestPoints{
+                        on_first: touching,
+                        on_second: touching,
+                        distance: 0.0,
+                    });
+                },
+                PolygonIntersection::Coplanar(Some(overlap)) => {
+                    let touching = overlap.get_key_points()[0];
+                    return Some(ClosestPoints{
+                        on_first: touching,
+                        on_second: touching,
+                        distance: 0.0,
+                    });
+                },
+                _ => {},
+            }
+            //apart, so the closest pair has a point on the boundary of one of them
+            let mut best = None;
+            for edge in surface_outline(one).iter(){
+                if let Some(candidate) = segment_surface_closest_points(edge, two){
+                    best = closer(best, candidate);
+                }
+            }
+            for edge in surface_outline(two).iter(){
+                if let Some(candidate) = segment_surface_closest_points(edge, one){
+                    best = closer(best, swapped(candidate));
+                }
+            }
+            best
+        },
+        (PickedEntity::Edge(_), PickedEntity::Vertex(_)) | (PickedEntity::Face(_), PickedEntity::Vertex(_)) | (PickedEntity::Face(_), PickedEntity::Edge(_)) => {
+            minimum_distance(second, first).map(swapped)
+        },
+    }
+}
+
+//The end of the edge further from the point, so the arm points away from the corner
+fn far_end(edge: &Line, from: &Point) -> Point{
+    if (edge.point_a - *from).length() > (edge.point_b - *from).length(){
+        edge.point_a
+    }else{
+        edge.point_b
+    }
+}
+
+fn unit_angle(one: &Direction, two: &Direction) -> f64{
+    (one.dot(two) / (one.length() * two.length())).clamp(-1.0, 1.0).acos()
+}
+
+//Edges: the angle between them seen from where they come closest (the shared corner when they
+//meet). Faces: the angle between their normals, 0 for parallel faces. An edge and a face: the
+//angle the edge makes with the face's plane, between 0 and pi/2.
+pub fn angle_between(first: &PickedEntity, second: &PickedEntity) -> Option<Measurement>{
+    match (first, second){
+        (PickedEntity::Edge(one), PickedEntity::Edge(two)) => {
+            let closest = segment_segment_closest_points(one, two);
+            let arm_one = far_end(one, &closest.on_first);
+            let arm_two = far_end(two, &closest.on_second);
+            let (along_one, along_two) = (arm_one - closest.on_first, arm_two - closest.on_second);
+            if along_one.length() <= model_tolerance() || along_two.length() <= model_tolerance(){
+                return None;
+            }
+            Some(Measurement::Angle{
+                angle: unit_angle(&along_one, &along_two),
+                vertex: closest.on_first,
+                arm_one: arm_one,
+                arm_two: closest.on_first + along_two,
+            })
+        },
+        (PickedEntity::Face(one), PickedEntity::Face(two)) => {
+            let (normal_one, normal_two) = (Direction::from_vector(one.normal()), Direction::from_vector(two.normal()));
+            if normal_one.length() < 0.5 || normal_two.length() < 0.5{
+                return None;
+            }
+            let vertex = surface_centroid(one)?;
+            let size = (one.normal_unnormalised().norm() / 2.0).sqrt();
+            Some(Measurement::Angle{
+                angle: unit_angle(&normal_one, &normal_two),
+                vertex: vertex,
+                arm_one: vertex + normal_one * size,
+                arm_two: vertex + normal_two * size,
+            })
+        },
+        (PickedEntity::Edge(edge), PickedEntity::Face(face)) | (PickedEntity::Face(face), PickedEntity::Edge(edge)) => {
+            let normal = Direction::from_vector(face.normal());
+            if normal.length() < 0.5{
+                return None;
+            }
+            let vertex = match line_plane_intersection(edge, &face.get_key_points()[0], &normal){
+                LinePlaneResult::Intersection{point, ..} => point,
+                LinePlaneResult::Parallel | LinePlaneResult::Contained => edge.point_a,
+                LinePlaneResult::Degenerate => return None,
+            };
+            let arm_one = far_end(edge, &vertex);
+            let along = arm_one - vertex;
+            if along.length() <= model_tolerance(){
+                return None;
+            }
+            let dropped = along - normal * along.dot(&normal);
+            Some(Measurement::Angle{
+                angle: std::f64::consts::FRAC_PI_2 - unit_angle(&along, &normal).min(std::f64::consts::PI - unit_angle(&along, &normal)),
+                vertex: vertex,
+                arm_one: arm_one,
+                arm_two: vertex + dropped,
+            })
+        },
+        _ => None,
+    }
+}
+
+//area weighted centre of a planar surface, None when it has no area
+pub fn surface_centroid(surface: &Surface) -> Option<Point>{
+    let normal = surface.normal();
+    let mut total = 0.0;
+    let mut weighted = na::Vector3::<f64>::zeros();
+    for triangle in surface_triangles(surface).iter(){
+        let (a, b, c) = (triangle[0].xyz(), triangle[1].xyz(), triangle[2].xyz());
+        //signed so the fan also works for non convex surfaces
+        let area = (b - a).cross(&(c - a)).dot(&normal) / 2.0;
+        total += area;
+        weighted += (a + b + c) * (area / 3.0);
+    }
+    if total.abs() <= f64::EPSILON{
+        return None;
+    }
+    Some(Point::from_xyz(weighted / total))
+}
+
+//Least squares circle through three or more points: the best fit plane first, then an algebraic
+//circle fit in it. None when the points are (nearly) on a straight line.
+pub fn fit_circle(points: &Vec<Point>) -> Option<(Point, f64, Direction, f64)>{
+    if points.len() < 3{
+        return None;
+    }
+    let mean = points.iter().fold(na::Vector3::<f64>::zeros(), |sum, point| sum + point.xyz()) / points.len() as f64;
+    let centred = na::DMatrix::from_fn(points.len(), 3, |row, column| points[row].xyz()[column] - mean[column]);
+    let svd = centred.svd(false, true);
+    let v_t = svd.v_t?;
+    //singular values come sorted largest first
+    if svd.singular_values[1] <= model_tolerance() * svd.singular_values[0].max(1.0){
+        return None;
+    }
+    let axis_u = na::Vector3::new(v_t[(0, 0)], v_t[(0, 1)], v_t[(0, 2)]);
+    let axis_v = na::Vector3::new(v_t[(1, 0)], v_t[(1, 1)], v_t[(1, 2)]);
+    let normal = axis_u.cross(&axis_v);
+
+    //x^2 + y^2 + d x + e y + f = 0 is linear in d, e and f
+    let flat: Vec<(f64, f64)> = points.iter().map(|point| ((point.xyz() - mean).dot(&axis_u), (point.xyz() - mean).dot(&axis_v))).collect();
+    let system = na::DMatrix::from_fn(flat.len(), 3, |row, column| match column{
+        0 => flat[row].0,
+        1 => flat[row].1,
+        _ => 1.0,
+    });
+    let right = na::DVector::from_fn(flat.len(), |row, _| -(flat[row].0 * flat[row].0 + flat[row].1 * flat[row].1));
+    let solution = system.svd(true, true).solve(&right, f64::EPSILON).ok()?;
+    let (centre_u, centre_v) = (-solution[0] / 2.0, -solution[1] / 2.0);
+    let radius_squared = centre_u * centre_u + centre_v * centre_v - solution[2];
+    if radius_squared <= 0.0 || !radius_squared.is_finite(){
+        return None;
+    }
+    let radius = radius_squared.sqrt();
+    let fit_error = flat.iter()
+        .map(|(u, v)| (((u - centre_u).powi(2) + (v - centre_v).powi(2)).sqrt() - radius).abs())
+        .fold(0.0, f64::max);
+    let centre = Point::from_xyz(mean + axis_u * centre_u + axis_v * centre_v);
+    Some((centre, radius, Direction::from_vector(normal), fit_error))
+}
+
+//the distinct points the picks put on an arc
+fn arc_points(picks: &Vec<Pick>) -> Vec<Point>{
+    let mut points = vec![];
+    for pick in picks.iter(){
+        match &pick.entity{
+            PickedEntity::Vertex(point) => points.push(*point),
+            PickedEntity::Edge(edge) => {
+                points.push(edge.point_a);
+                points.push(edge.point_b);
+            },
+            PickedEntity::Face(_) => {},
+        }
+    }
+    weld_points(&points, model_tolerance())
+}
+
+pub fn measure(mode: MeasureMode, picks: &Vec<Pick>) -> Result<Measurement, MeasureError>{
+    if !mode.is_complete(picks){
+        return Err(MeasureError::NotEnoughPicks(mode));
+    }
+    if let Some(pick) = picks.iter().find(|pick| !mode.accepts().contains(&pick.entity.kind())){
+        return Err(MeasureError::UnsupportedPick(mode, pick.entity.kind()));
+    }
+    let measurement = match mode{
+        MeasureMode::Distance => {
+            let (from, to) = (picks[0].point, picks[1].point);
+            Some(Measurement::Distance{
+                from: from,
+                to: to,
+                distance: (to - from).length(),
+            })
+        },
+        MeasureMode::MinimumDistance => minimum_distance(&picks[0].entity, &picks[1].entity).map(Measurement::MinimumDistance),
+        MeasureMode::Angle => angle_between(&picks[0].entity, &picks[1].entity),
+        MeasureMode::Radius => fit_circle(&arc_points(picks)).map(|(centre, radius, normal, fit_error)| Measurement::Radius{
+            centre: centre,
+            radius: radius,
+            normal: normal,
+            fit_error: fit_error,
+        }),
+        MeasureMode::Area => match &picks[0].entity{
+            PickedEntity::Face(face) => surface_centroid(face).map(|centroid| Measurement::Area{
+                area: face.normal_unnormalised().norm() / 2.0,
+                centroid: centroid,
+                outline: face.clone(),
+            }),
+            _ => None,
+        },
+    };
+    measurement.ok_or(MeasureError::Degenerate(mode))
+}
+
+impl Camera{
+    //None for points closer than min_depth_difference, they cannot be drawn
+    pub fn project_to_screen(&self, point: &Point) -> Option<(f64, f64)>{
+        let projected = self.screen_projection() * point.point_to_vector();
+        if projected.z < self.min_depth_difference{
+            return None;
+        }
+        Some((projected.x / projected.z, projected.y / projected.z))
+    }
+
+    //the ray from the camera through a screen position, undoing project_to_screen
+    pub fn screen_to_ray(&self, screen_x: f64, screen_y: f64) -> Ray{
+        let screen_to_world = match self.screen_projection().try_inverse(){
+            Some(inverse) => inverse,
+            None => return Ray::new(self.centre, Direction::new(0.0, 0.0, 1.0)),
+        };
+        //the camera centre and the point under the cursor at depth 1
+        let (origin, through_screen) = match (
+            Point::from_homogeneous(screen_to_world * na::Vector4::new(0.0, 0.0, 0.0, 1.0)),
+            Point::from_homogeneous(screen_to_world * na::Vector4::new(screen_x, screen_y, 1.0, 1.0)),
+        ){
+            (Some(origin), Some(through_screen)) => (origin, through_screen),
+            _ => return Ray::new(self.centre, Direction::new(0.0, 0.0, 1.0)),
+        };
+        Ray::new(origin, (through_screen - origin).normalize().unwrap_or(Direction::new(0.0, 0.0, 1.0)))
+    }
+
+    //Finds what is under the screen position among the objects the spatial index places in view.
+    //Vertices and edges count as under the cursor within pick_radius pixels.
+    pub fn pick(&self, objects: &Vec<coordinate_object>, spatial_index: &Bvh, screen_x: f64, screen_y: f64, kinds: &[PickKind], pick_radius: f64) -> Option<Pick>{
+        let ray = self.screen_to_ray(screen_x, screen_y);
+        let reach = Line::new(ray.origin, ray.point_at(self.max_depth_difference));
+        let near_cursor = |point: &Point| match self.project_to_screen(point){
+            Some((x, y)) => (x - screen_x).powi(2) + (y - screen_y).powi(2) <= pick_radius * pick_radius,
+            None => false,
+        };
+        let along_ray = |point: &Point| (*point - ray.origin).dot(&ray.direction);
+
+        let mut candidates: Vec<Pick> = vec![];
+        //the nearest face hit hides anything further along the ray
+        let mut nearest_face = f64::INFINITY;
+        for index in spatial_index.query_frustum(&self.view_frustum()).iter(){
+            let (vertices, edges, faces) = match &objects[*index]{
+                coordinate_object::Point_object(point) => (vec![*point], vec![], vec![]),
+                coordinate_object::Line_object(line) => (line.get_points(), vec![*line], vec![]),
+                coordinate_object::Perspective_object(perspective_object) => (perspective_object.get_points(), perspective_object.get_edges(), perspective_object.get_sides()),
+                coordinate_object::Plane_object(plane) => (vec![], vec![], vec![Surface::new(plane.get_points())]),
+                coordinate_object::Camera_object(_) => (vec![], vec![], vec![]),
+            };
+            for face in faces.into_iter(){
+                if let Some((point, distance)) = ray_surface_intersection(&ray, &face){
+                    nearest_face = nearest_face.min(distance);
+                    candidates.push(Pick{
+                        object_index: *index,
+                        entity: PickedEntity::Face(face),
+                        point: point,
+                        distance: distance,
+                    });
+                }
+            }
+            for edge in edges.into_iter(){
+                let closest = segment_segment_closest_points(&reach, &edge);
+                if near_cursor(&closest.on_second){
+                    candidates.push(Pick{
+                        object_index: *index,
+                        entity: PickedEntity::Edge(edge),
+                        point: closest.on_second,
+                        distance: along_ray(&closest.on_second),
+                    });
+                }
+            }
+            for vertex in vertices.into_iter(){
+                if near_cursor(&vertex){
+                    candidates.push(Pick{
+                        object_index: *index,
+                        entity: PickedEntity::Vertex(vertex),
+                        point: vertex,
+                        distance: along_ray(&vertex),
+                    });
+                }
+            }
+        }
+
+        let priority = |kind: PickKind| match kind{
+            PickKind::Vertex => 0,
+            PickKind::Edge => 1,
+            PickKind::Face => 2,
+        };
+        //edges and corners of the nearest face sit exactly on it, only slack for rounding
+        let hidden_beyond = nearest_face + model_tolerance().max(nearest_face * 1e-9);
+        candidates.into_iter()
+            .filter(|candidate| kinds.contains(&candidate.entity.kind()) && candidate.distance <= hidden_beyond)
+            .min_by(|one, two| priority(one.entity.kind()).cmp(&priority(two.entity.kind())).then(one.distance.total_cmp(&two.distance)))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn pick_returns_the_nearest_side_under_the_cursor(){
+        let square = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        //the far box is listed first so the order of the objects cannot decide it
+        let objects = vec![
+            coordinate_object::Perspective_object(super::super::test_shapes::prism(&square, 10.0, 12.0)),
+            coordinate_object::Perspective_object(super::super::test_shapes::prism(&square, 5.0, 7.0)),
+        ];
+        let spatial_index = Bvh::build(&objects);
+        let camera = Camera::new();
+        let (eye, inside) = (camera.eye_position(), Point::new(0.2, -0.3, 6.0));
+        let (screen_x, screen_y) = camera.project_to_screen(&inside).unwrap();
+        //where the line of sight through that point enters the near box
+        let entry = eye + (inside - eye) * ((5.0 - eye.get_depth()) / (inside.get_depth() - eye.get_depth()));
+
+        let pick = camera.pick(&objects, &spatial_index, screen_x, screen_y, &[PickKind::Face], 2.0).unwrap();
+        assert_eq!(pick.object_index, 1);
+        assert!(pick.point.approx_eq(&entry, 1e-9), "{:?} != {:?}", pick.point, entry);
+        assert!((pick.distance - (entry - eye).length()).abs() < 1e-9);
+        match pick.entity{
+            PickedEntity::Face(face) => assert!((face.normal() - na::Vector3::new(0.0, 0.0, -1.0)).norm() < 1e-9),
+            other => panic!("picked {:?}", other),
+        }
+
+        //with the near box gone the far one shows through
+        let spatial_index = Bvh::build(&objects[..1].to_vec());
+        let pick = camera.pick(&objects, &spatial_index, screen_x, screen_y, &[PickKind::Face], 2.0).unwrap();
+        assert_eq!(pick.object_index, 0);
+        assert!((pick.point.get_depth() - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,274 @@
+//How much a set of joined parts can move, worked out two ways. Grübler–Kutzbach counts from the
+//joint graph alone:
+//
+//    M = 6 (bodies - 1) - sum over joints of (6 - joint degrees of freedom)
+//
+//with every grounded part counted as the one ground body. The constraint Jacobian looks at the
+//actual geometry: the unknowns are the twist of every moving body and the rate of every joint
+//degree of freedom, and each joint asks that the child's twist equals the parent's plus the joint's
+//own motion, six equations per joint. Its null space is the set of motions the mechanism can make,
+//so it sees redundancy the count cannot, like parallel hinges in a closed loop. Each coupling ties
+//its follower's rate to its leaders' and takes one more degree of freedom away.
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Clone,Debug)]
+pub enum MobilityError{
+    //the joints do not touch any of the grounded parts
+    NoGround,
+    MissingPose(NodeId),
+    Kinematics(KinematicsError),
+}
+impl From<KinematicsError> for MobilityError{
+    fn from(error: KinematicsError) -> Self{
+        MobilityError::Kinematics(error)
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ConstraintState{
+    //free motions remain
+    UnderConstrained,
+    //no motion and no constraint repeated
+    ExactlyConstrained,
+    //some constraints repeat others, whether or not anything can still move
+    OverConstrained,
+}
+
+//one independent way the mechanism can move, scaled so the largest joint rate is 1
+#[derive(Clone,Debug)]
+pub struct FreeMotion{
+    //indexed like the combined value vector
+    pub joint_rates: Vec<f64>,
+    //angular then linear velocity of each moving part, about the world origin
+    pub body_twists: Vec<(NodeId, na::Vector6<f64>)>,
+}
+
+#[derive(Clone,Debug)]
+pub struct MobilityReport{
+    //moving parts plus the ground
+    pub bodies: usize,
+    pub joints: usize,
+    pub grubler_kutzbach: i64,
+    pub constraint_count: usize,
+    pub constraint_rank: usize,
+    //dimension of the constraint Jacobian's null space
+    pub mobility: usize,
+    pub redundant_constraints: usize,
+    //joints (taken in order) whose constraints repeat ones already imposed, with how many do
+    pub redundant_joints: Vec<(usize, usize)>,
+    pub free_motions: Vec<FreeMotion>,
+    pub state: ConstraintState,
+}
+impl MobilityReport{
+    pub fn summary(&self) -> String{
+        format!("{:?}: mobility {} (Grubler-Kutzbach {}), {} redundant constraints", self.state, self.mobility, self.grubler_kutzbach, self.redundant_constraints)
+    }
+}
+
+//The joint's degrees of freedom as unit twists about the world origin for its current values, each
+//axis carried by the motion of the ones before it like JointType::motion
+fn joint_twists(joint: &Joint, parent_world: &Transform) -> Vec<na::Vector6<f64>>{
+    let mut frame = parent_world.compose(&joint.get_parent_frame());
+    let mut twists = vec![];
+    for (axis, value) in joint.get_type().axes().iter().zip(joint.get_values().iter()){
+        let along = match axis{
+            JointAxis::Rotation(along) | JointAxis::Translation(along) => *along,
+        };
+        let direction = frame.linear_part().column(along).into_owned();
+        let direction = direction.try_normalize(f64::EPSILON).unwrap_or(direction);
+        let mut twist = na::Vector6::zeros();
+        if axis.is_rotation(){
+            let origin = frame.translation_part().to_vector();
+            twist.fixed_rows_mut::<3>(0).copy_from(&direction);
+            twist.fixed_rows_mut::<3>(3).copy_from(&origin.cross(&direction));
+        }else{
+            twist.fixed_rows_mut::<3>(3).copy_from(&direction);
+        }
+        twists.push(twist);
+        frame = frame.compose(&axis.motion(*value));
+    }
+    twists
+}
+
+fn rank(matrix: &na::DMatrix<f64>) -> usize{
+    if matrix.nrows() == 0 || matrix.ncols() == 0{
+        return 0;
+    }
+    let svd = matrix.clone().svd(false, false);
+    let largest = svd.singular_values.max();
+    svd.rank(1e-9 * largest.max(1.0))
+}
+
+//Mobility of the mechanism made by the joints. grounded parts do not move, with none given the
+//roots of the joint tree are taken as the ground. world gives each part's current
+//world transform.
+pub fn analyse_mobility(joints: &Vec<Joint>, couplings: &[JointCoupling], grounded: &[NodeId], world: impl Fn(NodeId) -> Option<Transform>) -> Result<MobilityReport, MobilityError>{
+    let couplings = resolve_couplings(joints, couplings)?;
+    let mut ground: Vec<NodeId> = grounded.to_vec();
+    if ground.is_empty(){
+        ground = KinematicTree::new(joints).get_roots().clone();
+    }
+    if !joints.is_empty() && !joints.iter().any(|joint| ground.contains(&joint.get_parent()) || ground.contains(&joint.get_child())){
+        return Err(MobilityError::NoGround);
+    }
+
+    //columns: six per moving body, then one per joint degree of freedom
+    let mut body_column: HashMap<NodeId, usize> = HashMap::new();
+    let mut moving: Vec<NodeId> = vec![];
+    for joint in joints.iter(){
+        for node in [joint.get_parent(), joint.get_child()]{
+            if !ground.contains(&node) && !body_column.contains_key(&node){
+                body_column.insert(node, moving.len() * 6);
+                moving.push(node);
+            }
+        }
+    }
+    let rate_start = moving.len() * 6;
+    let total_rates: usize = joints.iter().map(|joint| joint.degrees_of_freedom()).sum();
+    let columns = rate_start + total_rates;
+
+    let mut constraints = na::DMatrix::<f64>::zeros(6 * joints.len() + couplings.len(), columns);
+    let mut offset = rate_start;
+    for (index, joint) in joints.iter().enumerate(){
+        let row = 6 * index;
+        let parent_world = world(joint.get_parent()).ok_or(MobilityError::MissingPose(joint.get_parent()))?;
+        //child twist - parent twist - joint twists * rates = 0
+        if let Some(column) = body_column.get(&joint.get_child()){
+            constraints.view_mut((row, *column), (6, 6)).fill_with_identity();
+        }
+        if let Some(column) = body_column.get(&joint.get_parent()){
+            let block = -na::Matrix6::<f64>::identity();
+            constraints.view_mut((row, *column), (6, 6)).copy_from(&block);
+        }
+        for twist in joint_twists(joint, &parent_world).iter(){
+            constraints.view_mut((row, offset), (6, 1)).copy_from(&(-twist));
+            offset += 1;
+        }
+    }
+
+    //follower rate - sum of ratio * leader rate = 0
+    for (index, coupling) in couplings.iter().enumerate(){
+        let row = 6 * joints.len() + index;
+        constraints[(row, rate_start + coupling.follower)] = 1.0;
+        for (leader, ratio) in coupling.leaders.iter(){
+            constraints[(row, rate_start + leader)] -= ratio;
+        }
+    }
+
+    let constraint_count = 6 * joints.len() + couplings.len();
+    let constraint_rank = rank(&constraints);
+    let mobility = columns - constraint_rank;
+    let redundant_constraints = constraint_count - constraint_rank;
+
+    //greedily, a joint is redundant by however much it fails to raise the rank of those before it
+    let mut redundant_joints = vec![];
+    let mut previous_rank = 0;
+    for index in 0..joints.len(){
+        let current_rank = rank(&constraints.rows(0, 6 * (index + 1)).into_owned());
+        if current_rank - previous_rank < 6{
+            redundant_joints.push((index, 6 - (current_rank - previous_rank)));
+        }
+        previous_rank = current_rank;
+    }
+
+    let mut free_motions = vec![];
+    if mobility > 0{
+        let normal = constraints.transpose() * &constraints;
+        let eigen = na::SymmetricEigen::new(normal);
+        let mut order: Vec<usize> = (0..columns).collect();
+        order.sort_by(|one, two| eigen.eigenvalues[*one].total_cmp(&eigen.eigenvalues[*two]));
+        for column in order.into_iter().take(mobility){
+            let vector = eigen.eigenvectors.column(column).into_owned();
+            let rates = vector.rows(rate_start, total_rates);
+            let largest = if rates.amax() > 1e-9 {rates.amax()} else {vector.amax()};
+            let tidy = |value: f64| if (value / largest).abs() < 1e-9 {0.0} else {value / largest};
+            free_motions.push(FreeMotion{
+                joint_rates: rates.iter().map(|rate| tidy(*rate)).collect(),
+                body_twists: moving.iter().map(|node| {
+                    let start = body_column[node];
+                    (*node, na::Vector6::from_iterator((start..start + 6).map(|row| tidy(vector[row]))))
+                }).collect(),
+            });
+        }
+    }
+
+    let bodies = moving.len() + 1;
+    let grubler_kutzbach = 6 * (bodies as i64 - 1) - joints.iter().map(|joint| 6 - joint.degrees_of_freedom() as i64).sum::<i64>() - couplings.len() as i64;
+    let state = if redundant_constraints > 0{
+        ConstraintState::OverConstrained
+    }else if mobility > 0{
+        ConstraintState::UnderConstrained
+    }else{
+        ConstraintState::ExactlyConstrained
+    };
+    Ok(MobilityReport{
+        bodies: bodies,
+        joints: joints.len(),
+        grubler_kutzbach: grubler_kutzbach,
+        constraint_count: constraint_count,
+        constraint_rank: constraint_rank,
+        mobility: mobility,
+        redundant_constraints: redundant_constraints,
+        redundant_joints: redundant_joints,
+        free_motions: free_motions,
+        state: state,
+    })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn revolute(parent: NodeId, at: f64, child: NodeId, child_at: f64) -> Joint{
+        let frame = |x: f64| Transform::translation(Direction::new(x, 0.0, 0.0));
+        Joint::new("", JointType::Revolute, parent, frame(at), child, frame(child_at)).unwrap()
+    }
+
+    //poses the joints for the values with part 0 as the ground at the origin
+    fn analyse(joints: &mut Vec<Joint>, values: &[f64], couplings: &[JointCoupling]) -> MobilityReport{
+        let tree = KinematicTree::new(joints);
+        tree.scatter_values(joints, values).unwrap();
+        let world = tree.forward(joints, values, |_| Some(Transform::identity())).unwrap();
+        analyse_mobility(joints, couplings, &[0], |node| if node == 0 {Some(Transform::identity())} else {world.get(&node).cloned()}).unwrap()
+    }
+
+    #[test]
+    fn planar_four_bar_moves_one_way_with_three_repeated_constraints(){
+        //crank 1 and rocker 3 stand up from a ground of length 2 and the coupler 2 joins their tops
+        let mut joints = vec![revolute(0, 0.0, 1, 0.0), revolute(1, 1.0, 2, 0.0), revolute(0, 2.0, 3, 0.0), revolute(2, 2.0, 3, 1.0)];
+        let report = analyse(&mut joints, &[FRAC_PI_2, -FRAC_PI_2, FRAC_PI_2, FRAC_PI_2], &[]);
+        assert_eq!(report.bodies, 4);
+        assert_eq!(report.grubler_kutzbach, -2);
+        assert_eq!(report.mobility, 1);
+        assert_eq!(report.redundant_constraints, 3);
+        assert_eq!(report.state, ConstraintState::OverConstrained);
+        //the closing joint is the one that repeats what the others already hold
+        assert_eq!(report.redundant_joints, vec![(3, 3)]);
+    }
+
+    #[test]
+    fn serial_arm_is_under_constrained(){
+        let mut joints = vec![revolute(0, 0.0, 1, 0.0), revolute(1, 1.0, 2, 0.0)];
+        let report = analyse(&mut joints, &[0.3, 0.7], &[]);
+        assert_eq!(report.grubler_kutzbach, 2);
+        assert_eq!(report.mobility, 2);
+        assert_eq!(report.redundant_constraints, 0);
+        assert_eq!(report.state, ConstraintState::UnderConstrained);
+        assert_eq!(report.free_motions.len(), 2);
+    }
+
+    #[test]
+    fn a_gear_takes_one_motion_away(){
+        let mut joints = vec![revolute(0, 0.0, 1, 0.0), revolute(1, 1.0, 2, 0.0)];
+        let gear = JointCoupling::gear("", JointValue::new(0, 0), JointValue::new(1, 0), 0.5);
+        let report = analyse(&mut joints, &[0.3, 0.7], &[gear]);
+        assert_eq!(report.grubler_kutzbach, 1);
+        assert_eq!(report.mobility, 1);
+        assert_eq!(report.state, ConstraintState::UnderConstrained);
+        //the one motion left turns the elbow back at half the shoulder's rate
+        let rates = &report.free_motions[0].joint_rates;
+        assert!((rates[1] + 0.5 * rates[0]).abs() < 1e-9);
+    }
+}
@@ -1,15 +1,61 @@
 extern crate nalgebra as na;
 use std::cmp::Ordering;
-use std::ops::Add;
+use std::ops::{Add, Sub, Mul, Neg};
 use std::{thread, env};
 use std::time::{Duration};
 
+pub mod edges;
+pub use edges::*;
+pub mod mass;
+pub use mass::*;
+pub mod bounds;
+pub use bounds::*;
+pub mod bvh;
+pub use bvh::*;
+pub mod tolerance;
+pub use tolerance::*;
+pub mod transform;
+pub use transform::*;
+pub mod scene;
+pub use scene::*;
+pub mod queries;
+pub use queries::*;
+pub mod plane;
+pub use plane::*;
+pub mod measure;
+pub use measure::*;
+pub mod annotation;
+pub use annotation::*;
+pub mod viewport;
+pub use viewport::*;
+pub mod joints;
+pub use joints::*;
+pub mod assembly;
+pub use assembly::*;
+pub mod kinematics;
+pub use kinematics::*;
+pub mod coupling;
+pub use coupling::*;
+pub mod inverse_kinematics;
+pub use inverse_kinematics::*;
+pub mod mobility;
+pub use mobility::*;
+pub mod loop_closure;
+pub use loop_closure::*;
+pub mod motion;
+pub use motion::*;
+pub mod collision;
+pub use collision::*;
+#[cfg(test)]
+pub(crate) mod test_shapes;
+
 #[derive(Clone,Debug)]
 pub enum coordinate_object{
     Camera_object(Camera),
     Point_object(Point),
     Perspective_object(PerspectiveObject),
     Line_object(Line),
+    Plane_object(Plane),
 
 }
 impl Point_Construct for coordinate_object{
@@ -19,12 +65,33 @@ impl Point_Construct for coordinate_object{
             Self::Point_object(point) => point.get_points(),
             Self::Perspective_object(perspective_object) => perspective_object.get_points(),
             Self::Line_object(line) => line.get_points(),
+            Self::Plane_object(plane) => plane.get_points(),
+        }
+    }
+    //dispatched so objects that cache their bounds get to use the cache
+    fn axis_aligned_bounds(&self) -> Option<BoundingBox>{
+        match self{
+            Self::Camera_object(camera) => camera.axis_aligned_bounds(),
+            Self::Point_object(point) => point.axis_aligned_bounds(),
+            Self::Perspective_object(perspective_object) => perspective_object.axis_aligned_bounds(),
+            Self::Line_object(line) => line.axis_aligned_bounds(),
+            Self::Plane_object(plane) => plane.axis_aligned_bounds(),
+        }
+    }
+    fn oriented_bounds(&self) -> Option<OrientedBoundingBox>{
+        match self{
+            Self::Camera_object(camera) => camera.oriented_bounds(),
+            Self::Point_object(point) => point.oriented_bounds(),
+            Self::Perspective_object(perspective_object) => perspective_object.oriented_bounds(),
+            Self::Line_object(line) => line.oriented_bounds(),
+            Self::Plane_object(plane) => plane.oriented_bounds(),
         }
     }
 }
 
+//Only directions can translate, a position has no meaning as an offset
 pub trait Translatable {
-    fn translate(&mut self, to_translate_by: na::Vector4<f64>);
+    fn translate(&mut self, to_translate_by: Direction);
 }
 //Rotate here means around the object centre and has to do with orientation and NOT position (not
 //rotating around )
@@ -33,28 +100,39 @@ pub trait Rotatable {
 }
 pub trait Point_Construct{
     fn get_points(&self) -> Vec<Point>;
+    //World space bounds of the points, None when there are no points. Recomputed on every call
+    //here, objects with many points override these to cache the result.
+    fn axis_aligned_bounds(&self) -> Option<BoundingBox>{
+        BoundingBox::from_points(&self.get_points())
+    }
+    fn oriented_bounds(&self) -> Option<OrientedBoundingBox>{
+        OrientedBoundingBox::from_points(&self.get_points(), &vec![])
+    }
 }
 
 
+//A position in space. Stored homogeneously for the camera matrices but always normalised so that w
+//is 1, anything coming in with another w is divided through once in from_homogeneous. Offsets
+//between positions are Directions, which translation does not affect.
 #[derive(Clone,Copy,Debug,PartialEq,PartialOrd)]
 pub struct Point{
    point: na::Vector4<f64>,
 }
 impl Point{
 
-    //Note!! not equalised!!!
-    fn point_ignore_w(&mut self) -> na::Vector3<f64>{
-        na::Vector3::new(
-            self.point.x,
-            self.point.y,
-            self.point.z,
-        )
-    }
-
-    pub fn vector_to_point(vector: na::Vector4<f64>) -> Point{
-        Point{
-            point: vector,
+    //The one place homogeneous division happens. A w of zero is a direction rather than a
+    //position so there is nothing to divide to.
+    pub fn from_homogeneous(vector: na::Vector4<f64>) -> Option<Point>{
+        if vector.w.abs() < f64::EPSILON{
+            return None;
         }
+        Some(Point{
+            point: na::Vector4::new(
+                vector.x / vector.w,
+                vector.y / vector.w,
+                vector.z / vector.w,
+                1.0,
+            )})
     }
 
     pub fn point_to_vector(&self) -> na::Vector4<f64>{
@@ -66,43 +144,144 @@ impl Point{
             )
     }
 
-    pub fn new(x: f64, y:f64, z:f64, w:f64) -> Self{
+    //a position given directly, w is 1. Homogeneous vectors go through from_homogeneous
+    pub fn new(x: f64, y:f64, z:f64) -> Self{
         Point{
             point: na::Vector4::new(
                 x,
                 y,
                 z,
-                w,
+                1.0,
             )}
     }
     pub fn get_depth(&self) -> f64{
         self.point.z
     }
 
+    pub fn xyz(&self) -> na::Vector3<f64>{
+        na::Vector3::new(
+            self.point.x,
+            self.point.y,
+            self.point.z,
+        )
+    }
+
+    pub fn from_xyz(vector: na::Vector3<f64>) -> Point{
+        Point::new(vector.x, vector.y, vector.z)
+    }
+
+    pub fn origin() -> Point{
+        Point::new(0.0, 0.0, 0.0)
+    }
+
+    //Lexicographic on x then y then z. Ordering by distance from the origin made distinct points
+    //with the same norm compare equal, so it cannot be used to group identical points.
     pub fn float_cmp(one: &Self, other: &Self) -> Ordering{
-        let this_value: f64 = (one.point.x.powf(2.0) + one.point.y.powf(2.0) + one.point.z.powf(2.)).sqrt();
-        let other_value: f64 = (other.point.x.powf(2.0) + other.point.y.powf(2.0) + other.point.z.powf(2.0)).sqrt();
-        if this_value < other_value {
-            Ordering::Less
-        } else if this_value > other_value{
-            Ordering::Greater
-        }else{
-            Ordering::Equal
-        }
+        one.point.x.total_cmp(&other.point.x)
+            .then(one.point.y.total_cmp(&other.point.y))
+            .then(one.point.z.total_cmp(&other.point.z))
     }
     pub fn sort_point_vector(mut items: Vec<Point>) -> Vec<Point>{
         items.sort_by(|a, b| Point::float_cmp(a,b));
         items
     }
 }
-impl Add for Point{
-    type Output = Self;
+//position + offset = position
+impl Add<Direction> for Point{
+    type Output = Point;
+
+    fn add(self, other: Direction) -> Point {
+        Point::from_xyz(self.xyz() + other.direction)
+    }
+}
+impl Sub<Direction> for Point{
+    type Output = Point;
 
-    fn add(self, other: Self ) -> Self {
-        Self {
-            point: self.point + other.point,
+    fn sub(self, other: Direction) -> Point {
+        Point::from_xyz(self.xyz() - other.direction)
+    }
+}
+//position - position = offset between them
+impl Sub for Point{
+    type Output = Direction;
+
+    fn sub(self, other: Point) -> Direction {
+        Direction::from_vector(self.xyz() - other.xyz())
+    }
+}
+
+//An offset or direction in space (w = 0 in homogeneous form). Not necessarily unit length, use
+//normalize for that.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Direction{
+    direction: na::Vector3<f64>,
+}
+impl Direction{
+    pub fn new(x: f64, y: f64, z: f64) -> Self{
+        Direction{
+            direction: na::Vector3::new(x, y, z),
+        }
+    }
+    pub fn from_vector(vector: na::Vector3<f64>) -> Self{
+        Direction{
+            direction: vector,
         }
     }
+    pub fn to_vector(&self) -> na::Vector3<f64>{
+        self.direction
+    }
+    pub fn to_homogeneous(&self) -> na::Vector4<f64>{
+        na::Vector4::new(self.direction.x, self.direction.y, self.direction.z, 0.0)
+    }
+    pub fn length(&self) -> f64{
+        self.direction.norm()
+    }
+    //None for a zero length direction
+    pub fn normalize(&self) -> Option<Direction>{
+        self.direction.try_normalize(f64::EPSILON).map(Direction::from_vector)
+    }
+    pub fn dot(&self, other: &Direction) -> f64{
+        self.direction.dot(&other.direction)
+    }
+    pub fn cross(&self, other: &Direction) -> Direction{
+        Direction::from_vector(self.direction.cross(&other.direction))
+    }
+}
+impl Add for Direction{
+    type Output = Direction;
+
+    fn add(self, other: Direction) -> Direction {
+        Direction::from_vector(self.direction + other.direction)
+    }
+}
+impl Sub for Direction{
+    type Output = Direction;
+
+    fn sub(self, other: Direction) -> Direction {
+        Direction::from_vector(self.direction - other.direction)
+    }
+}
+impl Mul<f64> for Direction{
+    type Output = Direction;
+
+    fn mul(self, scale: f64) -> Direction {
+        Direction::from_vector(self.direction * scale)
+    }
+}
+impl Neg for Direction{
+    type Output = Direction;
+
+    fn neg(self) -> Direction {
+        Direction::from_vector(-self.direction)
+    }
+}
+//rotating an offset, translation does not apply to directions
+impl Mul<Direction> for na::Matrix3<f64>{
+    type Output = Direction;
+
+    fn mul(self, direction: Direction) -> Direction {
+        Direction::from_vector(self * direction.direction)
+    }
 }
 
 impl Point_Construct for Point{
@@ -147,6 +326,41 @@ pub struct Surface{
     //Not Just an alias for future editing
     key_points: Vec<Point>,
 }
+impl Surface{
+    //key points are the corners of the (for now planar) polygon, in winding order
+    pub fn new(key_points: Vec<Point>) -> Self{
+        Surface{
+            key_points: key_points,
+        }
+    }
+    pub fn get_key_points(&self) -> &Vec<Point>{
+        &self.key_points
+    }
+    //Newell's method, works for any planar polygon regardless of convexity. Not normalised to
+    //unit length so callers can also use it for the area (half the length).
+    pub fn normal_unnormalised(&self) -> na::Vector3<f64>{
+        let mut normal = na::Vector3::<f64>::zeros();
+        let count = self.key_points.len();
+        for i in 0..count{
+            let current = self.key_points[i].xyz();
+            let next = self.key_points[(i + 1) % count].xyz();
+            normal += current.cross(&next);
+        }
+        normal
+    }
+    pub fn normal(&self) -> na::Vector3<f64>{
+        let normal = self.normal_unnormalised();
+        match normal.try_normalize(f64::EPSILON){
+            Some(unit) => unit,
+            None => na::Vector3::<f64>::zeros(),
+        }
+    }
+}
+impl Point_Construct for Surface{
+    fn get_points(&self) -> Vec<Point>{
+        self.key_points.clone()
+    }
+}
 
 #[derive(Clone,Debug)]
 pub struct PerspectiveObject{
@@ -154,21 +368,33 @@ pub struct PerspectiveObject{
     //centre point.
     orientation: na::Matrix3<f64>,
     centre: Point,
+    //in object local coordinates, the orientation and centre place them in the world
     sides: Vec<Surface>,
+    bounds_cache: BoundsCache,
 }
 impl PerspectiveObject{
-    fn new(given_sides: Vec<Surface>) -> Self{
+    //sides are given in world coordinates, they are stored relative to their centre with no
+    //rotation so the object starts out exactly where the sides were
+    pub fn new(given_sides: Vec<Surface>) -> Self{
+        let centre = Self::calculate_centre(&given_sides);
+        let to_local = Transform::translation(Point::origin() - centre);
         Self{
-            orientation: na::Matrix3::<f64>::zeros(),
-            centre: Self::calculate_centre(&given_sides.clone()),
-            sides: given_sides,
+            orientation: na::Matrix3::<f64>::identity(),
+            centre: centre,
+            sides: given_sides.iter().map(|side| side.transformed(&to_local)).collect(),
+            bounds_cache: BoundsCache::default(),
         }
     }
 
+    //The centre is the centroid of the enclosed volume, a vertex average is only used for flat or
+    //empty objects where there is no volume to take the centroid of
     fn calculate_centre(sides: &Vec<Surface>) -> Point{
+        let integrals = integrate_sides(sides);
+        if integrals.volume > f64::EPSILON{
+            return Point::from_xyz(integrals.first_moment / integrals.volume);
+        }
         let mut points_vec: Vec<Point> = sides_to_points(sides);
-        let mut sorted_vec = Point::sort_point_vector(points_vec);
-        sorted_vec.dedup();
+        let mut sorted_vec = weld_points(&points_vec, model_tolerance());
 
         let mut average_x: f64 = 0.0; 
         let mut average_y: f64 = 0.0; 
@@ -184,26 +410,65 @@ impl PerspectiveObject{
         average_y = average_y / number_of_points;
         average_z = average_z / number_of_points;
 
-        Point::new(average_x, average_y, average_z, 1.0)
+        Point::new(average_x, average_y, average_z)
+    }
+    //local coordinates to world coordinates: rotate about the centre then move to it
+    pub fn pose(&self) -> Transform{
+        Transform::translation(self.centre - Point::origin()).compose(&Transform::rotation(self.orientation))
+    }
+    pub fn get_centre(&self) -> Point{
+        self.centre
+    }
+    pub fn get_orientation(&self) -> na::Matrix3<f64>{
+        self.orientation
+    }
+    //Places the object without touching its local geometry, orientation should be a proper
+    //rotation matrix
+    pub fn set_pose(&mut self, centre: Point, orientation: na::Matrix3<f64>){
+        self.centre = centre;
+        self.orientation = orientation;
+        self.bounds_cache.invalidate();
+    }
+    pub fn get_local_sides(&self) -> &Vec<Surface>{
+        &self.sides
+    }
+    //The world space geometry. Everything that needs to know where the object actually is
+    //(rendering, export, collision) should go through this rather than the local sides.
+    pub fn get_sides(&self) -> Vec<Surface>{
+        let pose = self.pose();
+        self.sides.iter().map(|side| side.transformed(&pose)).collect()
+    }
+    pub fn get_local_points(&self) -> Vec<Point>{
+        weld_points(&sides_to_points(&self.sides), model_tolerance())
     }
 }
 impl Rotatable for PerspectiveObject{
     fn rotate(&mut self, to_rotate_by: na::Matrix3<f64>){
      self.orientation = self.orientation * to_rotate_by;
+     self.bounds_cache.invalidate();
     }
 }
 impl Translatable for PerspectiveObject{
-    fn translate(&mut self, to_translate_by: na::Vector4<f64>){
-        self.centre = self.centre + Point::vector_to_point(to_translate_by);
+    fn translate(&mut self, to_translate_by: Direction){
+        self.centre = self.centre + to_translate_by;
+        self.bounds_cache.invalidate();
     }
 }
 impl Point_Construct for PerspectiveObject{
     fn get_points(&self) -> Vec<Point>{
-        let mut points_vec: Vec<Point> = sides_to_points(&self.sides);
-        let mut sorted_vec = Point::sort_point_vector(points_vec);
-        sorted_vec.dedup();
-
-        sorted_vec
+        let pose = self.pose();
+        let local = self.get_local_points();
+        local.iter().map(|point| point.transformed(&pose)).collect()
+    }
+    fn axis_aligned_bounds(&self) -> Option<BoundingBox>{
+        self.bounds_cache.axis_aligned(|| BoundingBox::from_points(&self.get_points()))
+    }
+    //side normals are the best candidates for a flush box face
+    fn oriented_bounds(&self) -> Option<OrientedBoundingBox>{
+        self.bounds_cache.oriented(|| {
+            let normals = self.get_sides().iter().map(|side| side.normal()).collect();
+            OrientedBoundingBox::from_points(&self.get_points(), &normals)
+        })
     }
 }
 
@@ -227,7 +492,7 @@ impl Camera{
     pub fn new() -> Self{
         let mut new_camera = Camera {
             orientation: na::Matrix3::<f64>::identity(),
-            centre: Point::new(0.0,0.0,0.0,0.0),
+            centre: Point::origin(),
             calibration_matrix: na::Matrix4::<f64>::zeros(),
             camera_extrinsics: na::Matrix4::<f64>::zeros(),
             extrinsics_inverse: na::Matrix4::<f64>::zeros(),
@@ -240,7 +505,7 @@ impl Camera{
             min_depth_difference: 1.0,
             max_depth_difference: 1200.0,
         };
-        new_camera.update_extrinsics_centre(Point::new(0.0,0.0,1.0,1.0));
+        new_camera.update_extrinsics_centre(Point::new(0.0,0.0,1.0));
         new_camera.update_intrinsics();
         new_camera.update_superior_matrix();
         new_camera
@@ -293,6 +558,9 @@ impl Camera{
                 0.0,                     0.0,                      0.0,                      1.0
             );
     }
+    pub fn get_screen_size(&self) -> (i32, i32){
+        (self.screen_x, self.screen_y)
+    }
     pub fn update_screen_size(&mut self, width: i32, height: i32){
         self.screen_x = width;
         self.screen_y = height;
@@ -306,6 +574,7 @@ impl Camera{
     }
     pub fn to_local_coords_vec(&self, point: Point) -> na::Vector3<f64>{               
             //apply change of basis to get truly camera oriented coords
+            //the extrinsics are affine so w stays 1
             let local_point_world_coords: na::Vector4::<f64>= self.extrinsics_inverse * point.clone().point_to_vector();
             print!("\ninverse:{:?}\n", self.extrinsics_inverse);
             print!("\nbasis matrix: {:?}\n",self.basis_change_matrix);
@@ -319,9 +588,9 @@ impl Camera{
         //creating new unit vectors for the local coordinates of the camera that are facing
         //the way the camera faces
                 
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
                 
         //manually making a matrix where each column is one of the unit vectors.
         self.basis_change_matrix = na::Matrix3::new(
@@ -344,38 +613,59 @@ impl Camera{
     pub fn return_visible_objects<'a>(& 'a self, objects: & 'a Vec<coordinate_object>) -> Vec<&coordinate_object>{
         //predeclare before for loop
         let mut visible_objects: Vec<& coordinate_object> = vec![];
-        let mut local_point: na::Vector3<f64>;
-        let mut depth_difference: f64;
         for object in objects.iter(){
-            for point in object.get_points().iter(){
-                //because of the way we represent lines, we need to be able to register a line in
-                //front of us with a point really far away, this is why the max_depth_difference is
-                //used to render objects and not here (it would invalidate lines longer than the
-                //max difference)
-                local_point = self.to_local_coords_vec(*point);
-                depth_difference = local_point.z;
-
-                //print!("\n{:?}\n",self.orientation);
-                
-                print!("point: {:?}\n",point);
-                print!("the moved point in local coords point C:{:?}\n", local_point);
-                //print!("depth_difference: {:?}\n",depth_difference);
-
-                if depth_difference >= self.min_depth_difference{
+            if self.is_object_visible(object){
                 visible_objects.push(&object);
-                    break;
-                }
             }
         }
         visible_objects
     }
 
+    //same check as return_visible_objects but only over the objects the spatial index places in
+    //the view frustum, so objects far outside the view are never looked at
+    pub fn return_visible_objects_indexed<'a>(& 'a self, objects: & 'a Vec<coordinate_object>, spatial_index: &Bvh) -> Vec<&'a coordinate_object>{
+        let mut visible_objects: Vec<& coordinate_object> = vec![];
+        for index in spatial_index.query_frustum(&self.view_frustum()).iter(){
+            if self.is_object_visible(&objects[*index]){
+                visible_objects.push(&objects[*index]);
+            }
+        }
+        visible_objects
+    }
+
+    pub fn is_object_visible(&self, object: &coordinate_object) -> bool{
+        let projection = self.screen_projection();
+        for point in object.get_points().iter(){
+            //because of the way we represent lines, we need to be able to register a line in
+            //front of us with a point really far away, this is why the max_depth_difference is
+            //used to render objects and not here (it would invalidate lines longer than the
+            //max difference). The depth is the one the drawing divides by.
+            if (projection * point.point_to_vector()).z >= self.min_depth_difference{
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn get_screen_values(&self, objects: &Vec<coordinate_object>) -> Vec<[u8;4]>{
+        self.render_objects(self.return_visible_objects(objects))
+    }
+
+    pub fn render_objects(&self, visible_objects: Vec<&coordinate_object>) -> Vec<[u8;4]>{
+        let mut pixel_buffer = self.new_pixel_buffer();
+        self.render_objects_into(&mut pixel_buffer, visible_objects);
+        pixel_buffer
+    }
+    //an empty (black) buffer the size of the screen
+    pub fn new_pixel_buffer(&self) -> Vec<[u8;4]>{
         let mut pixel_buffer: Vec<[u8;4]> = vec![];
         for i in 0..(self.screen_x * self.screen_y ){
             pixel_buffer.push([0,0,0,0]);
         }
-        let mut visible_objects: Vec<&coordinate_object> = self.return_visible_objects(objects);
+        pixel_buffer
+    }
+    //draws over whatever is already in the buffer, so backgrounds can go in first
+    pub fn render_objects_into(&self, pixel_buffer: &mut Vec<[u8;4]>, visible_objects: Vec<&coordinate_object>){
         let mut temp_vec: na::Vector4<f64>;
 
         for vis_obj in visible_objects.iter(){
@@ -435,11 +725,135 @@ impl Camera{
                             },
                         };
                     },
+                coordinate_object::Plane_object(plane) => {
+                        for line in plane.grid_lines().iter(){
+                            self.draw_line(pixel_buffer, line, [0x40, 0x40, 0x40, 0xff]);
+                        }
+                    },
+                //outline of every side where the object's pose puts it
+                coordinate_object::Perspective_object(perspective_object) => {
+                        for side in perspective_object.get_sides().iter(){
+                            let corners = side.get_key_points();
+                            for i in 0..corners.len(){
+                                let edge = Line::new(corners[i], corners[(i + 1) % corners.len()]);
+                                self.draw_line(pixel_buffer, &edge, [0xb0, 0xb0, 0xb0, 0xff]);
+                            }
+                        }
+                    },
                 _ => ({print!("DevDel: object not considered")}),
             }
         }
-        pixel_buffer[0] =  [0x5e, 0x48, 0xe8, 0xff];
-        pixel_buffer
+    }
+    //a small square centred on the point, for highlighting picks
+    pub fn draw_marker(&self, pixel_buffer: &mut Vec<[u8;4]>, point: &Point, colour: [u8;4]){
+        if let Some((x, y)) = self.project_to_screen(point){
+            let (x, y) = (x as i32, y as i32);
+            for y_i in y - 2 ..= y + 2{
+                for x_i in x - 2 ..= x + 2{
+                    if 0 <= y_i && y_i < self.screen_y && 0 <= x_i && x_i < self.screen_x{
+                        pixel_buffer[(y_i * self.screen_x + x_i) as usize] = colour;
+                    }
+                }
+            }
+        }
+    }
+    //Arrowhead with its tip at the point, pointing along the direction as seen on screen. Sized in
+    //pixels so it reads the same at any distance.
+    pub fn draw_arrowhead(&self, pixel_buffer: &mut Vec<[u8;4]>, tip: &Point, direction: &Direction, colour: [u8;4]){
+        let (tip_x, tip_y) = match self.project_to_screen(tip){
+            Some(position) => position,
+            None => return,
+        };
+        let (behind_x, behind_y) = match self.project_to_screen(&(*tip - *direction)){
+            Some(position) => position,
+            None => return,
+        };
+        let (back_x, back_y) = (behind_x - tip_x, behind_y - tip_y);
+        let length = (back_x * back_x + back_y * back_y).sqrt();
+        if length < f64::EPSILON{
+            return;
+        }
+        let size = 8.0;
+        let (back_x, back_y) = (back_x / length * size, back_y / length * size);
+        //the two barbs sit 30 degrees either side of the shaft
+        let (sin, cos) = (std::f64::consts::FRAC_PI_6.sin(), std::f64::consts::FRAC_PI_6.cos());
+        for side in [-1.0, 1.0]{
+            let barb = (back_x * cos - back_y * sin * side, back_x * sin * side + back_y * cos);
+            self.draw_screen_line(pixel_buffer, (tip_x, tip_y), (tip_x + barb.0, tip_y + barb.1), colour);
+        }
+    }
+    //text with its top left corner at a screen position
+    pub fn draw_text(&self, pixel_buffer: &mut Vec<[u8;4]>, text: &str, position: (i32, i32), scale: i32, colour: [u8;4]){
+        crate::libs::display_utils::draw_text(pixel_buffer, self.screen_x, self.screen_y, text, position.0, position.1, scale, colour);
+    }
+    //Text centred just above where the anchor lands on screen. Drawn flat on the screen so it
+    //always faces the camera and stays the same size at any distance.
+    pub fn draw_label(&self, pixel_buffer: &mut Vec<[u8;4]>, text: &str, anchor: &Point, colour: [u8;4]){
+        if let Some((x, y)) = self.project_to_screen(anchor){
+            let (width, height) = crate::libs::display_utils::text_size(text, 1);
+            self.draw_text(pixel_buffer, text, (x as i32 - width / 2, y as i32 - height - 3), 1, colour);
+        }
+    }
+    //world to screen pixels (before dividing by depth), the same projection the points use
+    pub(crate) fn screen_projection(&self) -> na::Matrix4<f64>{
+        self.calibration_matrix * self.camera_extrinsics
+    }
+    //Draws a world space segment with the same projection the points use. The part closer than
+    //min_depth_difference is cut off first so lines running behind the camera do not wrap around.
+    pub fn draw_line(&self, pixel_buffer: &mut Vec<[u8;4]>, line: &Line, colour: [u8;4]){
+        let projection = self.screen_projection();
+        let mut start = projection * line.point_a.point_to_vector();
+        let mut end = projection * line.point_b.point_to_vector();
+        let near = self.min_depth_difference;
+        if start.z < near && end.z < near{
+            return;
+        }
+        if start.z < near{
+            start = start + (end - start) * ((near - start.z) / (end.z - start.z));
+        }else if end.z < near{
+            end = end + (start - end) * ((near - end.z) / (start.z - end.z));
+        }
+        self.draw_screen_line(pixel_buffer, (start.x / start.z, start.y / start.z), (end.x / end.z, end.y / end.z), colour);
+    }
+    //a segment between two positions in screen pixels
+    pub fn draw_screen_line(&self, pixel_buffer: &mut Vec<[u8;4]>, start: (f64, f64), end: (f64, f64), colour: [u8;4]){
+        let (mut start_x, mut start_y) = start;
+        let (delta_x, delta_y) = (end.0 - start_x, end.1 - start_y);
+
+        //clip to the screen so lines reaching far outside it are not stepped through pixel by
+        //pixel (Liang-Barsky)
+        let (mut enter, mut exit) = (0.0_f64, 1.0_f64);
+        let screen_width = self.screen_x as f64;
+        let screen_height = self.screen_y as f64;
+        for (towards, room) in [(-delta_x, start_x), (delta_x, screen_width - start_x), (-delta_y, start_y), (delta_y, screen_height - start_y)]{
+            if towards.abs() < f64::EPSILON{
+                if room < 0.0{
+                    return;
+                }
+            }else if towards < 0.0{
+                enter = enter.max(room / towards);
+            }else{
+                exit = exit.min(room / towards);
+            }
+        }
+        if enter > exit{
+            return;
+        }
+        let end_x = start_x + delta_x * exit;
+        let end_y = start_y + delta_y * exit;
+        start_x += delta_x * enter;
+        start_y += delta_y * enter;
+
+        //stepping one pixel at a time along the longer screen direction
+        let steps = (end_x - start_x).abs().max((end_y - start_y).abs()).ceil().max(1.0) as i32;
+        for step in 0..=steps{
+            let fraction = step as f64 / steps as f64;
+            let x_i = (start_x + (end_x - start_x) * fraction) as i32;
+            let y_i = (start_y + (end_y - start_y) * fraction) as i32;
+            if 0 <= y_i && y_i < self.screen_y && 0 <= x_i && x_i < self.screen_x{
+                pixel_buffer[(y_i * self.screen_x + x_i) as usize] = colour;
+            }
+        }
     }
     //camera needs to be updated before this function can be called
     fn point_to_screen_position(&self, point: Point) -> (f64,f64,f64){
@@ -489,93 +903,63 @@ impl Camera{
     //MOVEMENT SECTION
     //
     pub fn move_forward(&mut self){
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
 
         let movement_factor = 0.5;
-        let to_add = na::Vector4::new(
-                 unit_vector_z.x,
-                 unit_vector_z.y,
-                 unit_vector_z.z,
-                 0.0
-                 );
-        self.centre = Point::vector_to_point(self.centre.point + to_add* movement_factor);
+        let to_add = Direction::from_vector(unit_vector_z);
+        self.centre = self.centre + to_add * movement_factor;
         self.update_camera();
     }
     pub fn move_back(&mut self){
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
 
         let movement_factor = 0.5;
-        let to_add = na::Vector4::new(
-                 -unit_vector_z.x,
-                 -unit_vector_z.y,
-                 -unit_vector_z.z,
-                 0.0
-                 );
-        self.centre = Point::vector_to_point(self.centre.point + to_add* movement_factor);
+        let to_add = -Direction::from_vector(unit_vector_z);
+        self.centre = self.centre + to_add * movement_factor;
         self.update_camera();
     }
     pub fn move_down(&mut self){
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
 
         let movement_factor = 0.5;
-        let to_add = na::Vector4::new(
-                 unit_vector_y.x,
-                 unit_vector_y.y,
-                 unit_vector_y.z,
-                 0.0
-                 );
-        self.centre = Point::vector_to_point(self.centre.point + to_add* movement_factor);
+        let to_add = Direction::from_vector(unit_vector_y);
+        self.centre = self.centre + to_add * movement_factor;
         self.update_camera();
     }
     pub fn move_up(&mut self){
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
 
         let movement_factor = 0.5;
-        let to_add = na::Vector4::new(
-                 -unit_vector_y.x,
-                 -unit_vector_y.y,
-                 -unit_vector_y.z,
-                 0.0
-                 );
-        self.centre = Point::vector_to_point(self.centre.point + to_add* movement_factor);
+        let to_add = -Direction::from_vector(unit_vector_y);
+        self.centre = self.centre + to_add * movement_factor;
         self.update_camera();
     }   
     pub fn move_right(&mut self){
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
 
         let movement_factor = 0.5;
-        let to_add = na::Vector4::new(
-                 unit_vector_x.x,
-                 unit_vector_x.y,
-                 unit_vector_x.z,
-                 0.0
-                 );
-        self.centre = Point::vector_to_point(self.centre.point + to_add* movement_factor);
+        let to_add = Direction::from_vector(unit_vector_x);
+        self.centre = self.centre + to_add * movement_factor;
         self.update_camera();
     }
  pub fn move_left(&mut self){
-        let unit_vector_x = self.orientation * Point::new(1.0,0.0,0.0,1.0).point_ignore_w(); 
-        let unit_vector_y = self.orientation * Point::new(0.0,1.0,0.0,1.0).point_ignore_w();
-        let unit_vector_z = self.orientation * Point::new(0.0,0.0,1.0,1.0).point_ignore_w(); 
+        let unit_vector_x = self.orientation * Direction::new(1.0,0.0,0.0).to_vector(); 
+        let unit_vector_y = self.orientation * Direction::new(0.0,1.0,0.0).to_vector();
+        let unit_vector_z = self.orientation * Direction::new(0.0,0.0,1.0).to_vector(); 
 
         let movement_factor = 0.5;
-        let to_add = na::Vector4::new(
-                 -unit_vector_x.x,
-                 -unit_vector_x.y,
-                 -unit_vector_x.z,
-                 0.0
-                 );
-        self.centre = Point::vector_to_point(self.centre.point + to_add* movement_factor);
+        let to_add = -Direction::from_vector(unit_vector_x);
+        self.centre = self.centre + to_add * movement_factor;
         self.update_camera();
     }
 
@@ -583,8 +967,8 @@ impl Camera{
 //These are definitely subject to change as they will need to update the callibration matrix and/or
 //the camera extrinsics 
 impl Translatable for Camera{
-    fn translate(&mut self, to_translate_by: na::Vector4<f64>){
-        self.centre = self.centre + Point::vector_to_point(to_translate_by);
+    fn translate(&mut self, to_translate_by: Direction){
+        self.centre = self.centre + to_translate_by;
     }
 }
 impl Rotatable for Camera{
@@ -0,0 +1,301 @@
+//Motion studies: drivers move chosen joint values over time and the assembly is stepped through
+//the study at a fixed time step, keeping every frame so it can be played back, scrubbed through or
+//written out as CSV. Velocities and accelerations are taken from the recorded frames by finite
+//differences, so values solved by the loop closure solver get them as well as the driven ones.
+use super::*;
+use std::f64::consts::PI;
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum MotionProfile{
+    //start + velocity * time
+    ConstantVelocity{start: f64, velocity: f64},
+    //offset + amplitude * sin(2 pi frequency time + phase), frequency in cycles per second
+    Sinusoid{offset: f64, amplitude: f64, frequency: f64, phase: f64},
+    //(time, value) pairs, straight between them and held past the first and last
+    Keyframes(Vec<(f64, f64)>),
+}
+impl MotionProfile{
+    pub fn value_at(&self, time: f64) -> f64{
+        match self{
+            Self::ConstantVelocity{start, velocity} => start + velocity * time,
+            Self::Sinusoid{offset, amplitude, frequency, phase} => offset + amplitude * (2.0 * PI * frequency * time + phase).sin(),
+            Self::Keyframes(keys) => {
+                let mut keys = keys.clone();
+                keys.sort_by(|one, two| one.0.total_cmp(&two.0));
+                match (keys.first(), keys.last()){
+                    (Some(first), _) if time <= first.0 => first.1,
+                    (_, Some(last)) if time >= last.0 => last.1,
+                    (None, _) | (_, None) => 0.0,
+                    _ => {
+                        let after = keys.iter().position(|key| key.0 > time).unwrap();
+                        let (before, after) = (keys[after - 1], keys[after]);
+                        before.1 + (after.1 - before.1) * (time - before.0) / (after.0 - before.0)
+                    },
+                }
+            },
+        }
+    }
+}
+
+//a profile driving one degree of freedom of one joint
+#[derive(Clone,Debug,PartialEq)]
+pub struct MotionDriver{
+    pub value: JointValue,
+    pub profile: MotionProfile,
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct MotionFrame{
+    pub time: f64,
+    //the combined value vector and its rates
+    pub values: Vec<f64>,
+    pub value_velocities: Vec<f64>,
+    pub value_accelerations: Vec<f64>,
+    //x, y and z of each part's origin in world space in turn, in the order of the study's parts
+    pub positions: Vec<f64>,
+    pub position_velocities: Vec<f64>,
+    pub position_accelerations: Vec<f64>,
+    //degrees of freedom that moved faster than their velocity limit since the frame before
+    pub too_fast: Vec<LimitReport>,
+}
+
+#[derive(Clone,Debug)]
+pub enum MotionError{
+    //the time step has to be above zero and the duration not below it
+    InvalidTiming{duration: f64, time_step: f64},
+    //the assembly could not be posed for the frame at this time
+    Kinematics{time: f64, error: KinematicsError},
+    //a driver on a joint or degree of freedom that does not exist
+    InvalidDriver(JointValue),
+}
+
+#[derive(Clone,Debug)]
+pub struct MotionStudy{
+    pub drivers: Vec<MotionDriver>,
+    pub duration: f64,
+    pub time_step: f64,
+    //column names for the values and the parts, set when the frames are recorded
+    value_labels: Vec<String>,
+    parts: Vec<(NodeId, String)>,
+    frames: Vec<MotionFrame>,
+}
+
+impl MotionStudy{
+    pub fn new(duration: f64, time_step: f64) -> Self{
+        MotionStudy{
+            drivers: vec![],
+            duration: duration,
+            time_step: time_step,
+            value_labels: vec![],
+            parts: vec![],
+            frames: vec![],
+        }
+    }
+    pub fn add_driver(&mut self, value: JointValue, profile: MotionProfile){
+        self.drivers.push(MotionDriver{
+            value: value,
+            profile: profile,
+        });
+    }
+    //where every driven value sits in the combined value vector of the joints, refused if any of
+    //them does not exist
+    pub fn driven_values(&self, joints: &Vec<Joint>) -> Result<Vec<usize>, MotionError>{
+        self.drivers.iter().map(|driver| driver.value.index(joints).ok_or(MotionError::InvalidDriver(driver.value))).collect()
+    }
+    //the configuration with every driven value set for the time, the rest as in current
+    pub fn driven_configuration(&self, joints: &Vec<Joint>, current: &[f64], time: f64) -> Result<Vec<f64>, MotionError>{
+        let mut values = current.to_vec();
+        for (driver, index) in self.drivers.iter().zip(self.driven_values(joints)?){
+            match values.get_mut(index){
+                Some(value) => *value = driver.profile.value_at(time),
+                None => return Err(MotionError::InvalidDriver(driver.value)),
+            }
+        }
+        Ok(values)
+    }
+    //every time_step from 0, with the duration itself as the last frame
+    pub fn frame_times(&self) -> Result<Vec<f64>, MotionError>{
+        if !(self.time_step > 0.0) || !(self.duration >= 0.0){
+            return Err(MotionError::InvalidTiming{
+                duration: self.duration,
+                time_step: self.time_step,
+            });
+        }
+        let steps = (self.duration / self.time_step - model_tolerance()).ceil().max(0.0) as usize;
+        Ok((0..=steps).map(|step| (step as f64 * self.time_step).min(self.duration)).collect())
+    }
+
+    //Keeps the frames, each a time with the combined values and the part positions at it, and
+    //works out their velocities and accelerations
+    pub fn record(&mut self, value_labels: Vec<String>, parts: Vec<(NodeId, String)>, samples: Vec<(f64, Vec<f64>, Vec<f64>)>){
+        let times: Vec<f64> = samples.iter().map(|sample| sample.0).collect();
+        let values: Vec<Vec<f64>> = samples.iter().map(|sample| sample.1.clone()).collect();
+        let positions: Vec<Vec<f64>> = samples.iter().map(|sample| sample.2.clone()).collect();
+        let value_velocities = differentiate(&times, &values);
+        let value_accelerations = differentiate(&times, &value_velocities);
+        let position_velocities = differentiate(&times, &positions);
+        let position_accelerations = differentiate(&times, &position_velocities);
+        self.frames = (0..samples.len()).map(|index| MotionFrame{
+            time: times[index],
+            values: values[index].clone(),
+            value_velocities: value_velocities[index].clone(),
+            value_accelerations: value_accelerations[index].clone(),
+            positions: positions[index].clone(),
+            position_velocities: position_velocities[index].clone(),
+            position_accelerations: position_accelerations[index].clone(),
+            too_fast: vec![],
+        }).collect();
+        self.value_labels = value_labels;
+        self.parts = parts;
+    }
+    //Fills in too_fast for every recorded frame from the step since the frame before. Call after
+    //record with the joints the study was run on.
+    pub fn check_velocities(&mut self, tree: &KinematicTree, joints: &Vec<Joint>) -> Result<(), MotionError>{
+        for index in 1..self.frames.len(){
+            let (before, frame) = (&self.frames[index - 1], &self.frames[index]);
+            let too_fast = tree.velocity_report(joints, &before.values, &frame.values, frame.time - before.time)
+                .map_err(|error| MotionError::Kinematics{time: frame.time, error: error})?;
+            self.frames[index].too_fast = too_fast;
+        }
+        Ok(())
+    }
+    //every velocity limit broken during the study, with the time of the frame it was broken by
+    pub fn velocity_violations(&self) -> Vec<(f64, LimitReport)>{
+        self.frames.iter().flat_map(|frame| frame.too_fast.iter().map(move |report| (frame.time, *report))).collect()
+    }
+    pub fn get_frames(&self) -> &Vec<MotionFrame>{
+        &self.frames
+    }
+    pub fn get_parts(&self) -> &Vec<(NodeId, String)>{
+        &self.parts
+    }
+    //the frame closest to the time, None before anything is recorded
+    pub fn frame_at(&self, time: f64) -> Option<usize>{
+        (0..self.frames.len()).min_by(|one, two| (self.frames[*one].time - time).abs().total_cmp(&(self.frames[*two].time - time).abs()))
+    }
+
+    //One row per frame: the time, then value, velocity and acceleration of every joint value, then
+    //the same for x, y and z of every part
+    pub fn to_csv(&self) -> String{
+        let mut header = vec!["time".to_string()];
+        for label in self.value_labels.iter(){
+            for quantity in ["value", "velocity", "acceleration"]{
+                header.push(format!("{} {}", label, quantity));
+            }
+        }
+        for (_, name) in self.parts.iter(){
+            for quantity in ["position", "velocity", "acceleration"]{
+                for axis in ["x", "y", "z"]{
+                    header.push(format!("{} {} {}", name, quantity, axis));
+                }
+            }
+        }
+        let mut csv = header.iter().map(|column| csv_field(column)).collect::<Vec<String>>().join(",");
+        csv.push('\n');
+        for frame in self.frames.iter(){
+            let mut row = vec![frame.time];
+            for index in 0..frame.values.len(){
+                row.extend([frame.values[index], frame.value_velocities[index], frame.value_accelerations[index]]);
+            }
+            for part in 0..self.parts.len(){
+                for quantity in [&frame.positions, &frame.position_velocities, &frame.position_accelerations]{
+                    row.extend_from_slice(&quantity[3 * part..3 * part + 3]);
+                }
+            }
+            csv.push_str(&row.iter().map(|number| number.to_string()).collect::<Vec<String>>().join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+    pub fn write_csv(&self, path: &std::path::Path) -> std::io::Result<()>{
+        std::fs::write(path, self.to_csv())
+    }
+}
+
+//names with commas or quotes are quoted
+fn csv_field(text: &str) -> String{
+    if text.contains(',') || text.contains('"'){
+        format!("\"{}\"", text.replace('"', "\"\""))
+    }else{
+        text.to_string()
+    }
+}
+
+//Rate of change of each column: central differences inside, one sided at the two ends and zero for
+//a single sample
+fn differentiate(times: &Vec<f64>, samples: &Vec<Vec<f64>>) -> Vec<Vec<f64>>{
+    let count = samples.len();
+    (0..count).map(|index| {
+        if count < 2{
+            return vec![0.0; samples[index].len()];
+        }
+        let (before, after) = (index.saturating_sub(1), (index + 1).min(count - 1));
+        let interval = times[after] - times[before];
+        samples[index].iter().enumerate().map(|(column, _)| {
+            if interval > 0.0 {(samples[after][column] - samples[before][column]) / interval} else {0.0}
+        }).collect()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn slider() -> Vec<Joint>{
+        vec![Joint::new("slide", JointType::Prismatic, 0, Transform::identity(), 1, Transform::identity()).unwrap()]
+    }
+
+    #[test]
+    fn keyframes_interpolate_and_hold_past_the_ends(){
+        //given out of order on purpose
+        let profile = MotionProfile::Keyframes(vec![(2.0, 4.0), (0.0, 0.0), (1.0, 1.0)]);
+        for (time, expected) in [(-1.0, 0.0), (0.0, 0.0), (0.5, 0.5), (1.0, 1.0), (1.5, 2.5), (2.0, 4.0), (3.0, 4.0)]{
+            assert!((profile.value_at(time) - expected).abs() < 1e-12, "{}", time);
+        }
+    }
+
+    #[test]
+    fn the_last_frame_is_the_duration(){
+        let times = MotionStudy::new(1.0, 0.3).frame_times().unwrap();
+        assert_eq!(times.len(), 5);
+        assert!((times[3] - 0.9).abs() < 1e-12);
+        assert_eq!(*times.last().unwrap(), 1.0);
+        assert!(matches!(MotionStudy::new(1.0, 0.0).frame_times(), Err(MotionError::InvalidTiming{..})));
+    }
+
+    #[test]
+    fn constant_velocity_has_no_acceleration(){
+        let joints = slider();
+        let mut study = MotionStudy::new(1.0, 0.1);
+        study.add_driver(JointValue::new(0, 0), MotionProfile::ConstantVelocity{start: 0.5, velocity: 2.0});
+        let mut samples = vec![];
+        for time in study.frame_times().unwrap().into_iter(){
+            let values = study.driven_configuration(&joints, &[0.0], time).unwrap();
+            samples.push((time, values.clone(), vec![0.0, 0.0, values[0]]));
+        }
+        study.record(vec!["slide z".to_string()], vec![(1, "part".to_string())], samples);
+        for frame in study.get_frames().iter(){
+            assert!((frame.values[0] - (0.5 + 2.0 * frame.time)).abs() < 1e-12);
+            assert!((frame.value_velocities[0] - 2.0).abs() < 1e-9);
+            assert!(frame.value_accelerations[0].abs() < 1e-6);
+            assert!((frame.position_velocities[2] - 2.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn drivers_on_missing_values_are_refused(){
+        let joints = slider();
+        let mut study = MotionStudy::new(1.0, 0.5);
+        study.add_driver(JointValue::new(0, 1), MotionProfile::ConstantVelocity{start: 0.0, velocity: 1.0});
+        assert!(matches!(study.driven_values(&joints), Err(MotionError::InvalidDriver(value)) if value == JointValue::new(0, 1)));
+        assert!(study.driven_configuration(&joints, &[0.0], 0.5).is_err());
+    }
+
+    #[test]
+    fn csv_headers_with_commas_are_quoted(){
+        let mut study = MotionStudy::new(0.0, 1.0);
+        study.record(vec!["arm, left \"a\"".to_string()], vec![], vec![(0.0, vec![1.0], vec![])]);
+        let header = study.to_csv().lines().next().unwrap().to_string();
+        assert_eq!(header, "time,\"arm, left \"\"a\"\" value\",\"arm, left \"\"a\"\" velocity\",\"arm, left \"\"a\"\" acceleration\"");
+    }
+}
@@ -0,0 +1,260 @@
+//Infinite planes with a frame of their own. The in-plane axes give sketches a 2D coordinate system
+//and the plane doubles as the reference for mirroring and sectioning. Only the grid drawn for it
+//in the viewport is bounded.
+use super::*;
+
+#[derive(Clone,Debug)]
+pub enum PlaneError{
+    //the normal (or the cross product of the edges) has no length
+    ZeroNormal,
+    CollinearPoints(Point, Point, Point),
+    TooFewPoints(usize),
+    //the furthest a key point sits off the best fit plane
+    NonPlanarSurface(f64),
+}
+
+#[derive(Clone,Copy,Debug)]
+pub struct Plane{
+    origin: Point,
+    //unit vectors, axis_u x axis_v = normal
+    normal: Direction,
+    axis_u: Direction,
+    axis_v: Direction,
+    //the grid drawn for the plane covers -half_extent..half_extent along both axes
+    half_extent: f64,
+    grid_spacing: f64,
+}
+
+impl Plane{
+    pub fn from_point_normal(origin: Point, normal: Direction) -> Result<Self, PlaneError>{
+        let unit = normal.normalize().ok_or(PlaneError::ZeroNormal)?;
+        let axis_u = Direction::from_vector(any_perpendicular(&unit.to_vector()));
+        Ok(Plane::from_frame(origin, unit, axis_u))
+    }
+    //origin at the first point, u axis towards the second, normal by the right hand rule
+    pub fn from_three_points(first: Point, second: Point, third: Point) -> Result<Self, PlaneError>{
+        let along = second - first;
+        let normal = along.cross(&(third - first));
+        if normal.length() <= model_tolerance() * along.length().max(1.0){
+            return Err(PlaneError::CollinearPoints(first, second, third));
+        }
+        Ok(Plane::from_frame(first, normal.normalize().unwrap(), along.normalize().unwrap()))
+    }
+    //The plane a surface lies in, facing the same way as its normal, with the u axis along its
+    //first edge
+    pub fn from_surface(surface: &Surface) -> Result<Self, PlaneError>{
+        let key_points = surface.get_key_points();
+        if key_points.len() < 3{
+            return Err(PlaneError::TooFewPoints(key_points.len()));
+        }
+        let normal = Direction::from_vector(surface.normal());
+        if normal.length() < 0.5{
+            return Err(PlaneError::ZeroNormal);
+        }
+        let origin = key_points[0];
+        let deviation = key_points.iter()
+            .map(|point| (*point - origin).dot(&normal).abs())
+            .fold(0.0, f64::max);
+        if deviation > model_tolerance(){
+            return Err(PlaneError::NonPlanarSurface(deviation));
+        }
+        let first_edge = key_points[1] - origin;
+        let in_plane = first_edge - normal * first_edge.dot(&normal);
+        let axis_u = in_plane.normalize().unwrap_or(Direction::from_vector(any_perpendicular(&normal.to_vector())));
+        let mut plane = Plane::from_frame(origin, normal, axis_u);
+        let extent = key_points.iter().map(|point| (*point - origin).length()).fold(0.0, f64::max);
+        plane.half_extent = extent.max(plane.grid_spacing);
+        Ok(plane)
+    }
+    //normal and axis_u must be unit length and perpendicular
+    fn from_frame(origin: Point, normal: Direction, axis_u: Direction) -> Self{
+        Plane{
+            origin: origin,
+            normal: normal,
+            axis_u: axis_u,
+            axis_v: normal.cross(&axis_u),
+            half_extent: 5.0,
+            grid_spacing: 1.0,
+        }
+    }
+    pub fn xy() -> Self{
+        Plane::from_frame(Point::origin(), Direction::new(0.0, 0.0, 1.0), Direction::new(1.0, 0.0, 0.0))
+    }
+    pub fn yz() -> Self{
+        Plane::from_frame(Point::origin(), Direction::new(1.0, 0.0, 0.0), Direction::new(0.0, 1.0, 0.0))
+    }
+    pub fn zx() -> Self{
+        Plane::from_frame(Point::origin(), Direction::new(0.0, 1.0, 0.0), Direction::new(0.0, 0.0, 1.0))
+    }
+
+    pub fn get_origin(&self) -> Point{
+        self.origin
+    }
+    pub fn get_normal(&self) -> Direction{
+        self.normal
+    }
+    pub fn get_axes(&self) -> (Direction, Direction){
+        (self.axis_u, self.axis_v)
+    }
+    pub fn get_half_extent(&self) -> f64{
+        self.half_extent
+    }
+    pub fn get_grid_spacing(&self) -> f64{
+        self.grid_spacing
+    }
+    //non positive sizes are ignored
+    pub fn set_grid(&mut self, half_extent: f64, grid_spacing: f64){
+        if half_extent > 0.0{
+            self.half_extent = half_extent;
+        }
+        if grid_spacing > 0.0{
+            self.grid_spacing = grid_spacing;
+        }
+    }
+    //same plane facing the other way, the v axis flips to keep the frame right handed
+    pub fn flipped(&self) -> Plane{
+        let mut plane = *self;
+        plane.normal = -self.normal;
+        plane.axis_v = -self.axis_v;
+        plane
+    }
+
+    //positive on the side the normal points to
+    pub fn signed_distance(&self, point: &Point) -> f64{
+        (*point - self.origin).dot(&self.normal)
+    }
+    pub fn project_point(&self, point: &Point) -> Point{
+        *point - self.normal * self.signed_distance(point)
+    }
+    //sketch coordinates of the point's projection onto the plane
+    pub fn to_plane_coordinates(&self, point: &Point) -> (f64, f64){
+        let offset = *point - self.origin;
+        (offset.dot(&self.axis_u), offset.dot(&self.axis_v))
+    }
+    pub fn from_plane_coordinates(&self, u: f64, v: f64) -> Point{
+        self.origin + self.axis_u * u + self.axis_v * v
+    }
+    //plane coordinates (u, v, height above the plane) to world coordinates
+    pub fn to_world_transform(&self) -> Transform{
+        let frame = na::Matrix3::from_columns(&[self.axis_u.to_vector(), self.axis_v.to_vector(), self.normal.to_vector()]);
+        Transform::from_parts(frame, self.origin - Point::origin())
+    }
+    pub fn mirror_transform(&self) -> Transform{
+        Transform::mirror(self.origin, self.normal)
+    }
+
+    //The outline where the plane cuts through the object, one segment per side it crosses. Sides
+    //lying in the plane are left out.
+    pub fn section(&self, object: &PerspectiveObject) -> Vec<Line>{
+        let mut segments = vec![];
+        for side in object.get_sides().iter(){
+            if side.get_key_points().len() < 3{
+                continue;
+            }
+            let side_normal = Direction::from_vector(side.normal());
+            let (origin, direction) = match plane_plane_intersection(&self.origin, &self.normal, &side.get_key_points()[0], &side_normal){
+                Some(line) => line,
+                None => continue,
+            };
+            let origin = origin.xyz();
+            let direction = direction.to_vector();
+            for (enter, exit) in polygon_line_intervals(side, &origin, &direction, &side_normal.to_vector()).iter(){
+                segments.push(Line::new(
+                    Point::from_xyz(origin + direction * *enter),
+                    Point::from_xyz(origin + direction * *exit),
+                ));
+            }
+        }
+        segments
+    }
+
+    //lines of constant u then lines of constant v across the bounded grid, always including the
+    //border even when the extent is not a multiple of the spacing
+    pub fn grid_lines(&self) -> Vec<Line>{
+        let mut offsets = vec![];
+        let mut offset = 0.0;
+        while offset < self.half_extent{
+            offsets.push(offset);
+            if offset > 0.0{
+                offsets.push(-offset);
+            }
+            offset += self.grid_spacing;
+        }
+        offsets.push(self.half_extent);
+        offsets.push(-self.half_extent);
+
+        let mut lines = vec![];
+        for offset in offsets.iter(){
+            lines.push(Line::new(
+                self.from_plane_coordinates(*offset, -self.half_extent),
+                self.from_plane_coordinates(*offset, self.half_extent),
+            ));
+        }
+        for offset in offsets.iter(){
+            lines.push(Line::new(
+                self.from_plane_coordinates(-self.half_extent, *offset),
+                self.from_plane_coordinates(self.half_extent, *offset),
+            ));
+        }
+        lines
+    }
+}
+
+impl Point_Construct for Plane{
+    //the corners of the drawn grid, the plane itself has no extent
+    fn get_points(&self) -> Vec<Point>{
+        let extent = self.half_extent;
+        vec![
+            self.from_plane_coordinates(-extent, -extent),
+            self.from_plane_coordinates(extent, -extent),
+            self.from_plane_coordinates(extent, extent),
+            self.from_plane_coordinates(-extent, extent),
+        ]
+    }
+}
+
+impl Transformable for Plane{
+    //The frame is rebuilt from the mapped normal and u axis so it stays orthonormal under scale
+    //and shear. Mirroring flips the normal through apply_normal, the v axis follows from it.
+    fn transform(&mut self, transform: &Transform){
+        let normal = transform.apply_normal(&self.normal);
+        let mapped_u = transform.apply_direction(&self.axis_u);
+        let in_plane = mapped_u - normal * mapped_u.dot(&normal);
+        self.origin = transform.apply_point(&self.origin);
+        self.normal = normal;
+        self.axis_u = in_plane.normalize().unwrap_or(Direction::from_vector(any_perpendicular(&normal.to_vector())));
+        self.axis_v = normal.cross(&self.axis_u);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn plane_coordinates_round_trip(){
+        let tilted = Plane::from_point_normal(Point::new(1.0, -2.0, 3.0), Direction::new(0.3, -1.0, 2.0)).unwrap();
+        let planes = [
+            Plane::xy(),
+            Plane::zx(),
+            tilted,
+            tilted.flipped(),
+            Plane::from_three_points(Point::new(0.0, 1.0, 0.0), Point::new(2.0, 1.0, 1.0), Point::new(-1.0, 3.0, 0.5)).unwrap(),
+        ];
+        for plane in planes.iter(){
+            let (axis_u, axis_v) = plane.get_axes();
+            assert!((axis_u.cross(&axis_v) - plane.get_normal()).length() < 1e-12);
+            for (u, v) in [(0.0, 0.0), (2.5, -1.0), (-7.0, 0.125)]{
+                let point = plane.from_plane_coordinates(u, v);
+                let (back_u, back_v) = plane.to_plane_coordinates(&point);
+                assert!((back_u - u).abs() < 1e-9 && (back_v - v).abs() < 1e-9);
+                assert!(plane.signed_distance(&point).abs() < 1e-9);
+                //a point off the plane comes back as its projection
+                let above = point + plane.get_normal() * 1.5;
+                let (above_u, above_v) = plane.to_plane_coordinates(&above);
+                assert!(plane.from_plane_coordinates(above_u, above_v).approx_eq(&plane.project_point(&above), 1e-9));
+                assert!(plane.to_world_transform().apply_point(&Point::new(u, v, 1.5)).approx_eq(&above, 1e-9));
+            }
+        }
+    }
+}
@@ -0,0 +1,562 @@
+//Geometric queries between points, lines (as segments or infinite lines), rays and planar surfaces.
+//Degenerate input (zero length lines, collapsed surfaces) and parallel configurations are reported
+//through the result types rather than producing NaNs. "Zero" is the model tolerance throughout.
+use super::*;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Ray{
+    pub origin: Point,
+    pub direction: Direction,
+}
+impl Ray{
+    pub fn new(origin: Point, direction: Direction) -> Self{
+        Ray{
+            origin: origin,
+            direction: direction,
+        }
+    }
+    pub fn point_at(&self, distance: f64) -> Point{
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct ClosestPoints{
+    pub on_first: Point,
+    pub on_second: Point,
+    pub distance: f64,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum LineLineResult{
+    Closest(ClosestPoints),
+    //infinite parallel lines have no single closest pair, only the distance between them
+    Parallel{distance: f64},
+    //one of the lines has no length so it has no direction
+    Degenerate,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum LinePlaneResult{
+    //parameter is 0 at the start of the line and 1 at its end
+    Intersection{point: Point, parameter: f64},
+    Parallel,
+    //the line lies in the plane
+    Contained,
+    Degenerate,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct RayHit{
+    pub distance: f64,
+    pub point: Point,
+    //weights of the three triangle corners, they sum to 1
+    pub barycentric: na::Vector3<f64>,
+}
+
+#[derive(Clone,Debug)]
+pub enum PolygonIntersection{
+    None,
+    //the surfaces cross, giving the segments where both overlap along the line their planes share
+    Crossing(Vec<Line>),
+    //the surfaces lie in the same plane, giving the overlapping region (only exact for convex
+    //surfaces)
+    Coplanar(Option<Surface>),
+}
+
+fn tolerance() -> f64{
+    model_tolerance()
+}
+
+//The closest points between two infinite lines through the given Lines' end points
+pub fn line_line_closest_points(first: &Line, second: &Line) -> LineLineResult{
+    let (start_one, start_two) = (first.point_a.xyz(), second.point_a.xyz());
+    let direction_one = first.point_b.xyz() - start_one;
+    let direction_two = second.point_b.xyz() - start_two;
+    if direction_one.norm() <= tolerance() || direction_two.norm() <= tolerance(){
+        return LineLineResult::Degenerate;
+    }
+    let between = start_one - start_two;
+    let a = direction_one.dot(&direction_one);
+    let b = direction_one.dot(&direction_two);
+    let c = direction_two.dot(&direction_two);
+    let d = direction_one.dot(&between);
+    let e = direction_two.dot(&between);
+    let denominator = a * c - b * b;
+    if denominator <= f64::EPSILON * a * c{
+        let offset = between - direction_two * (e / c);
+        return LineLineResult::Parallel{distance: offset.norm()};
+    }
+    let parameter_one = (b * e - c * d) / denominator;
+    let parameter_two = (a * e - b * d) / denominator;
+    let on_first = start_one + direction_one * parameter_one;
+    let on_second = start_two + direction_two * parameter_two;
+    LineLineResult::Closest(ClosestPoints{
+        on_first: Point::from_xyz(on_first),
+        on_second: Point::from_xyz(on_second),
+        distance: (on_first - on_second).norm(),
+    })
+}
+
+//Closest points between two finite segments. Always has an answer: parallel and zero length
+//segments pick one of the equally close pairs.
+pub fn segment_segment_closest_points(first: &Line, second: &Line) -> ClosestPoints{
+    let (start_one, start_two) = (first.point_a.xyz(), second.point_a.xyz());
+    let direction_one = first.point_b.xyz() - start_one;
+    let direction_two = second.point_b.xyz() - start_two;
+    let between = start_one - start_two;
+    let a = direction_one.dot(&direction_one);
+    let e = direction_two.dot(&direction_two);
+    let f = direction_two.dot(&between);
+    let epsilon = tolerance() * tolerance();
+
+    let (parameter_one, parameter_two);
+    if a <= epsilon && e <= epsilon{
+        parameter_one = 0.0;
+        parameter_two = 0.0;
+    }else if a <= epsilon{
+        parameter_one = 0.0;
+        parameter_two = (f / e).clamp(0.0, 1.0);
+    }else{
+        let c = direction_one.dot(&between);
+        if e <= epsilon{
+            parameter_two = 0.0;
+            parameter_one = (-c / a).clamp(0.0, 1.0);
+        }else{
+            let b = direction_one.dot(&direction_two);
+            let denominator = a * e - b * b;
+            //parallel segments: any start will do, the clamping below finds the right partner
+            let mut first_guess = if denominator > f64::EPSILON * a * e {((b * f - c * e) / denominator).clamp(0.0, 1.0)} else {0.0};
+            let mut second_guess = (b * first_guess + f) / e;
+            if second_guess < 0.0{
+                second_guess = 0.0;
+                first_guess = (-c / a).clamp(0.0, 1.0);
+            }else if second_guess > 1.0{
+                second_guess = 1.0;
+                first_guess = ((b - c) / a).clamp(0.0, 1.0);
+            }
+            parameter_one = first_guess;
+            parameter_two = second_guess;
+        }
+    }
+    let on_first = start_one + direction_one * parameter_one;
+    let on_second = start_two + direction_two * parameter_two;
+    ClosestPoints{
+        on_first: Point::from_xyz(on_first),
+        on_second: Point::from_xyz(on_second),
+        distance: (on_first - on_second).norm(),
+    }
+}
+
+//closest point on the segment and the distance to it
+pub fn point_segment_closest(point: &Point, segment: &Line) -> (Point, f64){
+    let start = segment.point_a.xyz();
+    let direction = segment.point_b.xyz() - start;
+    let length_squared = direction.dot(&direction);
+    let parameter = if length_squared <= f64::EPSILON {0.0} else {((point.xyz() - start).dot(&direction) / length_squared).clamp(0.0, 1.0)};
+    let closest = start + direction * parameter;
+    (Point::from_xyz(closest), (point.xyz() - closest).norm())
+}
+
+pub fn point_segment_distance(point: &Point, segment: &Line) -> f64{
+    point_segment_closest(point, segment).1
+}
+
+pub fn line_plane_intersection(line: &Line, plane_point: &Point, plane_normal: &Direction) -> LinePlaneResult{
+    let normal = match plane_normal.normalize(){
+        Some(normal) => normal.to_vector(),
+        None => return LinePlaneResult::Degenerate,
+    };
+    let start = line.point_a.xyz();
+    let direction = line.point_b.xyz() - start;
+    if direction.norm() <= tolerance(){
+        return LinePlaneResult::Degenerate;
+    }
+    let start_height = normal.dot(&(start - plane_point.xyz()));
+    let along = normal.dot(&direction);
+    if along.abs() <= f64::EPSILON * direction.norm(){
+        if start_height.abs() <= tolerance(){
+            return LinePlaneResult::Contained;
+        }
+        return LinePlaneResult::Parallel;
+    }
+    let parameter = -start_height / along;
+    LinePlaneResult::Intersection{
+        point: Point::from_xyz(start + direction * parameter),
+        parameter: parameter,
+    }
+}
+
+//Point on the (planar) surface closest to the given point, with the distance. The point is
+//projected into the surface's plane, if that lands outside the polygon the closest point on its
+//boundary is used instead.
+pub fn point_surface_closest(point: &Point, surface: &Surface) -> Option<(Point, f64)>{
+    let corners = surface.get_key_points();
+    if corners.is_empty(){
+        return None;
+    }
+    let normal = surface.normal();
+    if normal.norm() > 0.5 && corners.len() >= 3{
+        let height = normal.dot(&(point.xyz() - corners[0].xyz()));
+        let projected = Point::from_xyz(point.xyz() - normal * height);
+        if polygon_contains(surface, &projected){
+            return Some((projected, height.abs()));
+        }
+    }
+    let mut best: Option<(Point, f64)> = None;
+    for i in 0..corners.len(){
+        let edge = Line::new(corners[i], corners[(i + 1) % corners.len()]);
+        let (closest, distance) = point_segment_closest(point, &edge);
+        if best.map_or(true, |(_, best_distance)| distance < best_distance){
+            best = Some((closest, distance));
+        }
+    }
+    best
+}
+
+pub fn point_surface_distance(point: &Point, surface: &Surface) -> Option<f64>{
+    point_surface_closest(point, surface).map(|(_, distance)| distance)
+}
+
+//Whether a point already in the surface's plane lies inside the polygon (boundary counts as
+//inside). Even-odd rule in the plane, so it works for non convex surfaces.
+pub fn polygon_contains(surface: &Surface, point: &Point) -> bool{
+    let corners = surface.get_key_points();
+    let normal = surface.normal();
+    if corners.len() < 3 || normal.norm() < 0.5{
+        return false;
+    }
+    let axis_u = any_perpendicular(&normal);
+    let axis_v = normal.cross(&axis_u);
+    let flatten = |position: na::Vector3<f64>| na::Vector2::new(position.dot(&axis_u), position.dot(&axis_v));
+    let target = flatten(point.xyz());
+    let flat: Vec<na::Vector2<f64>> = corners.iter().map(|corner| flatten(corner.xyz())).collect();
+
+    let mut inside = false;
+    for i in 0..flat.len(){
+        let (one, two) = (flat[i], flat[(i + 1) % flat.len()]);
+        let edge = two - one;
+        let along = if edge.norm_squared() <= f64::EPSILON {0.0} else {((target - one).dot(&edge) / edge.norm_squared()).clamp(0.0, 1.0)};
+        if (target - (one + edge * along)).norm() <= tolerance(){
+            return true;
+        }
+        if (one.y > target.y) != (two.y > target.y){
+            let crossing = one.x + (target.y - one.y) / (two.y - one.y) * (two.x - one.x);
+            if target.x < crossing{
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+//Moller-Trumbore. Hits behind the ray origin are ignored, as are hits on triangles seen exactly
+//edge on.
+pub fn ray_triangle_intersection(ray: &Ray, triangle: &[Point; 3]) -> Option<RayHit>{
+    let (a, b, c) = (triangle[0].xyz(), triangle[1].xyz(), triangle[2].xyz());
+    let edge_one = b - a;
+    let edge_two = c - a;
+    let direction = ray.direction.to_vector();
+    let p = direction.cross(&edge_two);
+    let determinant = edge_one.dot(&p);
+    if determinant.abs() <= f64::EPSILON * edge_one.norm() * edge_two.norm() * direction.norm(){
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let from_corner = ray.origin.xyz() - a;
+    let u = from_corner.dot(&p) * inverse;
+    if u < 0.0 || u > 1.0{
+        return None;
+    }
+    let q = from_corner.cross(&edge_one);
+    let v = direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0{
+        return None;
+    }
+    let distance = edge_two.dot(&q) * inverse;
+    if distance < 0.0{
+        return None;
+    }
+    Some(RayHit{
+        distance: distance,
+        point: ray.point_at(distance),
+        barycentric: na::Vector3::new(1.0 - u - v, u, v),
+    })
+}
+
+//fan triangulation of a surface, exact for convex surfaces
+pub fn surface_triangles(surface: &Surface) -> Vec<[Point; 3]>{
+    let corners = surface.get_key_points();
+    let mut triangles = vec![];
+    for i in 1..corners.len().saturating_sub(1){
+        triangles.push([corners[0], corners[i], corners[i + 1]]);
+    }
+    triangles
+}
+
+//Where the ray first meets the (planar) surface and how far along it, in multiples of the ray's
+//direction. The plane is hit first and the point kept only if it is inside the polygon, so non
+//convex surfaces are exact. A ray lying in the surface's plane does not hit it.
+pub fn ray_surface_intersection(ray: &Ray, surface: &Surface) -> Option<(Point, f64)>{
+    let corners = surface.get_key_points();
+    let normal = surface.normal();
+    if corners.len() < 3 || normal.norm() < 0.5{
+        return None;
+    }
+    let line = Line::new(ray.origin, ray.origin + ray.direction);
+    match line_plane_intersection(&line, &corners[0], &Direction::from_vector(normal)){
+        LinePlaneResult::Intersection{point, parameter} if parameter >= 0.0 && polygon_contains(surface, &point) => Some((point, parameter)),
+        _ => None,
+    }
+}
+
+//closest points between a point and a triangle
+pub fn point_triangle_closest(point: &Point, triangle: &[Point; 3]) -> (Point, f64){
+    match point_surface_closest(point, &Surface::new(triangle.to_vec())){
+        Some(result) => result,
+        None => (triangle[0], (point.xyz() - triangle[0].xyz()).norm()),
+    }
+}
+
+pub fn segment_triangle_closest_points(segment: &Line, triangle: &[Point; 3]) -> ClosestPoints{
+    let surface = Surface::new(triangle.to_vec());
+    //crossing the triangle means touching it
+    if let LinePlaneResult::Intersection{point, parameter} = line_plane_intersection(segment, &triangle[0], &Direction::from_vector(surface.normal())){
+        if (0.0..=1.0).contains(&parameter) && polygon_contains(&surface, &point){
+            return ClosestPoints{
+                on_first: point,
+                on_second: point,
+                distance: 0.0,
+            };
+        }
+    }
+    let mut best: Option<ClosestPoints> = None;
+    let mut consider = |candidate: ClosestPoints|{
+        if best.map_or(true, |current| candidate.distance < current.distance){
+            best = Some(candidate);
+        }
+    };
+    for i in 0..3{
+        consider(segment_segment_closest_points(segment, &Line::new(triangle[i], triangle[(i + 1) % 3])));
+    }
+    for end in [segment.point_a, segment.point_b]{
+        let (closest, distance) = point_triangle_closest(&end, triangle);
+        consider(ClosestPoints{
+            on_first: end,
+            on_second: closest,
+            distance: distance,
+        });
+    }
+    best.unwrap()
+}
+
+pub fn segment_triangle_distance(segment: &Line, triangle: &[Point; 3]) -> f64{
+    segment_triangle_closest_points(segment, triangle).distance
+}
+
+//parameters along the line where it is inside the (planar) polygon, as sorted (enter, exit) pairs.
+//The line has to lie in the polygon's plane.
+pub(super) fn polygon_line_intervals(surface: &Surface, origin: &na::Vector3<f64>, direction: &na::Vector3<f64>, plane_normal: &na::Vector3<f64>) -> Vec<(f64, f64)>{
+    let corners = surface.get_key_points();
+    //side of the line each corner is on, measured in the polygon plane
+    let across = plane_normal.cross(direction);
+    let side = |corner: &Point| across.dot(&(corner.xyz() - origin));
+    let mut crossings: Vec<f64> = vec![];
+    for i in 0..corners.len(){
+        let (one, two) = (corners[i], corners[(i + 1) % corners.len()]);
+        let (side_one, side_two) = (side(&one), side(&two));
+        if (side_one > 0.0) != (side_two > 0.0){
+            let fraction = side_one / (side_one - side_two);
+            let crossing = one.xyz() + (two.xyz() - one.xyz()) * fraction;
+            crossings.push((crossing - origin).dot(direction) / direction.norm_squared());
+        }
+    }
+    crossings.sort_by(|a, b| a.total_cmp(b));
+    crossings.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+//Sutherland-Hodgman, clips the subject by a convex clipping polygon lying in the same plane
+fn clip_coplanar(subject: &Surface, clip: &Surface) -> Option<Surface>{
+    let normal = clip.normal();
+    let corners = clip.get_key_points();
+    let mut output: Vec<Point> = subject.get_key_points().clone();
+    for i in 0..corners.len(){
+        let (edge_start, edge_end) = (corners[i].xyz(), corners[(i + 1) % corners.len()].xyz());
+        //inside is to the left of the edge looking down the normal
+        let inward = normal.cross(&(edge_end - edge_start));
+        let height = |point: &Point| inward.dot(&(point.xyz() - edge_start));
+        let input = output;
+        output = vec![];
+        for j in 0..input.len(){
+            let (current, next) = (input[j], input[(j + 1) % input.len()]);
+            let (height_current, height_next) = (height(&current), height(&next));
+            if height_current >= 0.0{
+                output.push(current);
+            }
+            if (height_current >= 0.0) != (height_next >= 0.0){
+                let fraction = height_current / (height_current - height_next);
+                output.push(Point::from_xyz(current.xyz() + (next.xyz() - current.xyz()) * fraction));
+            }
+        }
+        if output.is_empty(){
+            return None;
+        }
+    }
+    let welded = weld_points(&output, tolerance());
+    if welded.len() < 3{
+        return None;
+    }
+    Some(Surface::new(welded))
+}
+
+//a point on both planes: solve for the combination of normals that satisfies both. line_direction
+//is the cross product of the normals and must not be zero
+fn plane_plane_origin(anchor_one: &na::Vector3<f64>, normal_one: &na::Vector3<f64>, anchor_two: &na::Vector3<f64>, normal_two: &na::Vector3<f64>, line_direction: &na::Vector3<f64>) -> na::Vector3<f64>{
+    let offset_one = normal_one.dot(anchor_one);
+    let offset_two = normal_two.dot(anchor_two);
+    (normal_two.cross(line_direction) * offset_one + line_direction.cross(normal_one) * offset_two) / line_direction.norm_squared()
+}
+
+//The line two planes share as a point on it and a direction, None for parallel planes
+pub fn plane_plane_intersection(point_one: &Point, normal_one: &Direction, point_two: &Point, normal_two: &Direction) -> Option<(Point, Direction)>{
+    let line_direction = normal_one.cross(normal_two);
+    if line_direction.length() <= 1e-12 * normal_one.length() * normal_two.length(){
+        return None;
+    }
+    let origin = plane_plane_origin(&point_one.xyz(), &normal_one.to_vector(), &point_two.xyz(), &normal_two.to_vector(), &line_direction.to_vector());
+    Some((Point::from_xyz(origin), line_direction))
+}
+
+pub fn polygon_polygon_intersection(first: &Surface, second: &Surface) -> PolygonIntersection{
+    let (normal_one, normal_two) = (first.normal(), second.normal());
+    if first.get_key_points().len() < 3 || second.get_key_points().len() < 3 || normal_one.norm() < 0.5 || normal_two.norm() < 0.5{
+        return PolygonIntersection::None;
+    }
+    let anchor_one = first.get_key_points()[0].xyz();
+    let anchor_two = second.get_key_points()[0].xyz();
+    let line_direction = normal_one.cross(&normal_two);
+
+    if line_direction.norm() <= 1e-12{
+        if normal_one.dot(&(anchor_two - anchor_one)).abs() > tolerance(){
+            return PolygonIntersection::None;
+        }
+        //same plane: clip with the clipping polygon wound the same way as the subject
+        let mut clip = second.clone();
+        if normal_one.dot(&normal_two) < 0.0{
+            clip.key_points.reverse();
+        }
+        return PolygonIntersection::Coplanar(clip_coplanar(first, &clip));
+    }
+
+    let origin = plane_plane_origin(&anchor_one, &normal_one, &anchor_two, &normal_two, &line_direction);
+
+    let intervals_one = polygon_line_intervals(first, &origin, &line_direction, &normal_one);
+    let intervals_two = polygon_line_intervals(second, &origin, &line_direction, &normal_two);
+    let mut segments = vec![];
+    for (enter_one, exit_one) in intervals_one.iter(){
+        for (enter_two, exit_two) in intervals_two.iter(){
+            let (enter, exit) = (enter_one.max(*enter_two), exit_one.min(*exit_two));
+            if exit >= enter{
+                segments.push(Line::new(
+                    Point::from_xyz(origin + line_direction * enter),
+                    Point::from_xyz(origin + line_direction * exit),
+                ));
+            }
+        }
+    }
+    if segments.is_empty(){
+        PolygonIntersection::None
+    }else{
+        PolygonIntersection::Crossing(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Point{
+        Point::new(x, y, z)
+    }
+
+    #[test]
+    fn parallel_lines_report_their_separation(){
+        let first = Line::new(point(0.0, 0.0, 0.0), point(1.0, 0.0, 0.0));
+        let second = Line::new(point(5.0, 3.0, 4.0), point(2.0, 3.0, 4.0));
+        match line_line_closest_points(&first, &second){
+            LineLineResult::Parallel{distance} => assert!((distance - 5.0).abs() < 1e-12),
+            other => panic!("expected parallel lines, got {:?}", other),
+        }
+        //the segments still have a closest pair, the overlap gives a distance of 5 too
+        let segments = segment_segment_closest_points(&first, &Line::new(point(0.5, 3.0, 4.0), point(3.0, 3.0, 4.0)));
+        assert!((segments.distance - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn skew_lines_meet_at_their_common_perpendicular(){
+        let first = Line::new(point(0.0, 0.0, 0.0), point(1.0, 0.0, 0.0));
+        let second = Line::new(point(3.0, -1.0, 2.0), point(3.0, 1.0, 2.0));
+        match line_line_closest_points(&first, &second){
+            LineLineResult::Closest(closest) => {
+                assert!(closest.on_first.coincident(&point(3.0, 0.0, 0.0)));
+                assert!(closest.on_second.coincident(&point(3.0, 0.0, 2.0)));
+                assert!((closest.distance - 2.0).abs() < 1e-12);
+            },
+            other => panic!("expected skew lines, got {:?}", other),
+        }
+        //as segments the first stops short at x = 1
+        let segments = segment_segment_closest_points(&first, &second);
+        assert!(segments.on_first.coincident(&point(1.0, 0.0, 0.0)));
+        assert!((segments.distance - 8.0_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn segment_short_of_a_plane_misses_it(){
+        let segment = Line::new(point(0.0, 0.0, 1.0), point(0.0, 0.0, 3.0));
+        match line_plane_intersection(&segment, &point(0.0, 0.0, 0.0), &Direction::new(0.0, 0.0, 1.0)){
+            LinePlaneResult::Intersection{point: crossing, parameter} => {
+                //the infinite line crosses behind the start, outside the segment
+                assert!((parameter + 0.5).abs() < 1e-12);
+                assert!(crossing.coincident(&point(0.0, 0.0, 0.0)));
+                assert!(!(0.0..=1.0).contains(&parameter));
+            },
+            other => panic!("expected the line to cross the plane, got {:?}", other),
+        }
+        let triangle = [point(-1.0, -1.0, 0.0), point(1.0, -1.0, 0.0), point(0.0, 1.0, 0.0)];
+        assert!((segment_triangle_distance(&segment, &triangle) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn point_on_a_triangle_edge_is_on_the_triangle(){
+        let triangle = [point(0.0, 0.0, 0.0), point(2.0, 0.0, 0.0), point(0.0, 2.0, 0.0)];
+        let on_edge = point(1.0, 1.0, 0.0);
+        assert!(polygon_contains(&Surface::new(triangle.to_vec()), &on_edge));
+        let (closest, distance) = point_triangle_closest(&on_edge, &triangle);
+        assert!(closest.coincident(&on_edge));
+        assert!(distance < 1e-12);
+        //a ray straight down through the edge counts as a hit
+        let hit = ray_triangle_intersection(&Ray::new(point(1.0, 1.0, 5.0), Direction::new(0.0, 0.0, -1.0)), &triangle).unwrap();
+        assert!((hit.distance - 5.0).abs() < 1e-12);
+        assert!((hit.barycentric.sum() - 1.0).abs() < 1e-12);
+        assert!(hit.barycentric.x.abs() < 1e-12);
+    }
+
+    #[test]
+    fn ray_into_the_notch_of_a_u_shaped_side_misses(){
+        //a U in the z = 0 plane open towards +y, the notch is 1 < x < 2, 1 < y < 3
+        let u_shape = Surface::new(vec![
+            point(0.0, 0.0, 0.0), point(3.0, 0.0, 0.0), point(3.0, 3.0, 0.0), point(2.0, 3.0, 0.0),
+            point(2.0, 1.0, 0.0), point(1.0, 1.0, 0.0), point(1.0, 3.0, 0.0), point(0.0, 3.0, 0.0),
+        ]);
+        let down = Direction::new(0.0, 0.0, -1.0);
+        assert!(ray_surface_intersection(&Ray::new(point(1.5, 2.0, 4.0), down), &u_shape).is_none());
+        //both arms and the base are still hit
+        for (x, y) in [(0.5, 2.5), (2.5, 2.5), (1.5, 0.5)]{
+            let (hit, distance) = ray_surface_intersection(&Ray::new(point(x, y, 4.0), down), &u_shape).unwrap();
+            assert!(hit.coincident(&point(x, y, 0.0)));
+            assert!((distance - 4.0).abs() < 1e-12);
+        }
+        //pointing away
+        assert!(ray_surface_intersection(&Ray::new(point(0.5, 2.5, 4.0), Direction::new(0.0, 0.0, 1.0)), &u_shape).is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//Scene graph: a hierarchy of nodes each carrying a transform relative to its parent. Objects hang
+//off nodes in their node's local coordinates and their world space copies are produced by walking
+//the hierarchy, so moving a node moves everything underneath it.
+use super::*;
+use std::cell::RefCell;
+
+pub type NodeId = usize;
+
+#[derive(Clone,Debug)]
+pub enum SceneGraphError{
+    NodeNotFound(NodeId),
+    //the new parent is the node itself or one of its descendants
+    WouldCreateCycle(NodeId, NodeId),
+    //the new parent's world transform has no inverse so the world position cannot be kept
+    SingularTransform(NodeId),
+}
+
+#[derive(Clone,Debug)]
+pub struct SceneNode{
+    pub name: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    //local coordinates to parent coordinates
+    local_transform: Transform,
+    //local coordinates to world coordinates, cleared whenever this node or an ancestor changes
+    world_transform: RefCell<Option<Transform>>,
+    object: Option<coordinate_object>,
+}
+impl SceneNode{
+    pub fn get_parent(&self) -> Option<NodeId>{
+        self.parent
+    }
+    pub fn get_children(&self) -> &Vec<NodeId>{
+        &self.children
+    }
+    pub fn get_local_transform(&self) -> Transform{
+        self.local_transform
+    }
+    //the object in the node's local coordinates
+    pub fn get_object(&self) -> Option<&coordinate_object>{
+        self.object.as_ref()
+    }
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct SceneGraph{
+    //removed nodes leave a None behind so ids stay stable
+    nodes: Vec<Option<SceneNode>>,
+}
+
+impl SceneGraph{
+    pub fn new() -> Self{
+        SceneGraph::default()
+    }
+
+    pub fn get_node(&self, id: NodeId) -> Result<&SceneNode, SceneGraphError>{
+        match self.nodes.get(id){
+            Some(Some(node)) => Ok(node),
+            _ => Err(SceneGraphError::NodeNotFound(id)),
+        }
+    }
+
+    fn get_node_mut(&mut self, id: NodeId) -> Result<&mut SceneNode, SceneGraphError>{
+        match self.nodes.get_mut(id){
+            Some(Some(node)) => Ok(node),
+            _ => Err(SceneGraphError::NodeNotFound(id)),
+        }
+    }
+
+    pub fn node_ids(&self) -> Vec<NodeId>{
+        (0..self.nodes.len()).filter(|id| self.nodes[*id].is_some()).collect()
+    }
+
+    pub fn roots(&self) -> Vec<NodeId>{
+        self.node_ids().into_iter().filter(|id| self.nodes[*id].as_ref().unwrap().parent.is_none()).collect()
+    }
+
+    //object is given in the new node's local coordinates, which are placed in the parent's
+    //coordinates (or the world for a root) by local_transform
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, local_transform: Transform, object: Option<coordinate_object>) -> Result<NodeId, SceneGraphError>{
+        if let Some(parent_id) = parent{
+            self.get_node(parent_id)?;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Some(SceneNode{
+            name: name.to_string(),
+            parent: parent,
+            children: vec![],
+            local_transform: local_transform,
+            world_transform: RefCell::new(None),
+            object: object,
+        }));
+        if let Some(parent_id) = parent{
+            self.get_node_mut(parent_id)?.children.push(id);
+        }
+        Ok(id)
+    }
+
+    //removes the node and everything under it
+    pub fn remove_subtree(&mut self, id: NodeId) -> Result<(), SceneGraphError>{
+        let parent = self.get_node(id)?.parent;
+        if let Some(parent_id) = parent{
+            self.get_node_mut(parent_id)?.children.retain(|child| *child != id);
+        }
+        let mut to_remove = vec![id];
+        while let Some(current) = to_remove.pop(){
+            if let Some(node) = self.nodes[current].take(){
+                to_remove.extend(node.children);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_local_transform(&mut self, id: NodeId, local_transform: Transform) -> Result<(), SceneGraphError>{
+        self.get_node_mut(id)?.local_transform = local_transform;
+        self.invalidate_subtree(id);
+        Ok(())
+    }
+
+    //applies the change on top of the current local transform, in the parent's coordinates
+    pub fn apply_transform(&mut self, id: NodeId, change: Transform) -> Result<(), SceneGraphError>{
+        let current = self.get_node(id)?.local_transform;
+        self.set_local_transform(id, change.compose(&current))
+    }
+
+    pub fn set_object(&mut self, id: NodeId, object: Option<coordinate_object>) -> Result<(), SceneGraphError>{
+        self.get_node_mut(id)?.object = object;
+        Ok(())
+    }
+
+    fn invalidate_subtree(&self, id: NodeId){
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop(){
+            if let Some(node) = &self.nodes[current]{
+                *node.world_transform.borrow_mut() = None;
+                stack.extend(node.children.iter().cloned());
+            }
+        }
+    }
+
+    //cached, only recomputed up the chain of ancestors that changed
+    pub fn world_transform(&self, id: NodeId) -> Result<Transform, SceneGraphError>{
+        let node = self.get_node(id)?;
+        if let Some(cached) = *node.world_transform.borrow(){
+            return Ok(cached);
+        }
+        let world = match node.parent{
+            Some(parent_id) => self.world_transform(parent_id)?.compose(&node.local_transform),
+            None => node.local_transform,
+        };
+        *node.world_transform.borrow_mut() = Some(world);
+        Ok(world)
+    }
+
+    //places the node in the world by working out the local transform its parent needs
+    pub fn set_world_transform(&mut self, id: NodeId, world: Transform) -> Result<(), SceneGraphError>{
+        let parent_world = match self.get_node(id)?.parent{
+            Some(parent_id) => self.world_transform(parent_id)?,
+            None => Transform::identity(),
+        };
+        match parent_world.inverse(){
+            Some(parent_inverse) => self.set_local_transform(id, parent_inverse.compose(&world)),
+            None => Err(SceneGraphError::SingularTransform(id)),
+        }
+    }
+
+    pub fn is_descendant(&self, id: NodeId, possible_ancestor: NodeId) -> bool{
+        let mut current = Some(id);
+        while let Some(node_id) = current{
+            if node_id == possible_ancestor{
+                return true;
+            }
+            current = self.nodes.get(node_id).and_then(|node| node.as_ref()).and_then(|node| node.parent);
+        }
+        false
+    }
+
+    //Moves the node under a new parent (None for the top level) without moving it in the world:
+    //the local transform is recomputed against the new parent
+    pub fn reparent(&mut self, id: NodeId, new_parent: Option<NodeId>) -> Result<(), SceneGraphError>{
+        let world = self.world_transform(id)?;
+        let parent_world = match new_parent{
+            Some(parent_id) => {
+                if self.is_descendant(parent_id, id){
+                    return Err(SceneGraphError::WouldCreateCycle(id, parent_id));
+                }
+                self.world_transform(parent_id)?
+            },
+            None => Transform::identity(),
+        };
+        let parent_inverse = match parent_world.inverse(){
+            Some(inverse) => inverse,
+            None => return Err(SceneGraphError::SingularTransform(new_parent.unwrap_or(id))),
+        };
+
+        if let Some(old_parent) = self.get_node(id)?.parent{
+            self.get_node_mut(old_parent)?.children.retain(|child| *child != id);
+        }
+        if let Some(parent_id) = new_parent{
+            self.get_node_mut(parent_id)?.children.push(id);
+        }
+        let node = self.get_node_mut(id)?;
+        node.parent = new_parent;
+        node.local_transform = parent_inverse.compose(&world);
+        self.invalidate_subtree(id);
+        Ok(())
+    }
+
+    //the node's object in world coordinates
+    pub fn world_object(&self, id: NodeId) -> Result<Option<coordinate_object>, SceneGraphError>{
+        let world = self.world_transform(id)?;
+        Ok(self.get_node(id)?.object.as_ref().map(|object| object.transformed(&world)))
+    }
+
+    //every object in world coordinates along with the node it hangs off
+    pub fn world_objects(&self) -> Vec<(NodeId, coordinate_object)>{
+        let mut objects = vec![];
+        for id in self.node_ids(){
+            let node = self.nodes[id].as_ref().unwrap();
+            if let Some(object) = &node.object{
+                let world = self.world_transform(id).unwrap();
+                objects.push((id, object.transformed(&world)));
+            }
+        }
+        objects
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn same_transform(one: &Transform, other: &Transform) -> bool{
+        (one.to_matrix() - other.to_matrix()).norm() < 1e-9
+    }
+
+    #[test]
+    fn reparenting_keeps_the_world_pose(){
+        let mut scene = SceneGraph::new();
+        let arm = scene.add_node("arm", None, Transform::translation(Direction::new(1.0, 2.0, 0.0))
+            .compose(&Transform::rotation_about_axis(Direction::new(0.0, 0.0, 1.0), 0.7)), None).unwrap();
+        let table = scene.add_node("table", None, Transform::rotation_about_axis(Direction::new(1.0, 0.0, 0.0), -0.4)
+            .compose(&Transform::uniform_scale(2.0)), None).unwrap();
+        let hand = scene.add_node("hand", Some(arm), Transform::translation(Direction::new(0.5, 0.0, 0.3)), None).unwrap();
+        let finger = scene.add_node("finger", Some(hand), Transform::rotation_about_axis(Direction::new(0.0, 1.0, 0.0), 1.1), None).unwrap();
+        let (hand_world, finger_world) = (scene.world_transform(hand).unwrap(), scene.world_transform(finger).unwrap());
+
+        for new_parent in [Some(table), None, Some(arm)]{
+            scene.reparent(hand, new_parent).unwrap();
+            assert_eq!(scene.get_node(hand).unwrap().get_parent(), new_parent);
+            assert!(same_transform(&scene.world_transform(hand).unwrap(), &hand_world));
+            assert!(same_transform(&scene.world_transform(finger).unwrap(), &finger_world));
+        }
+        assert_eq!(scene.get_node(arm).unwrap().get_children(), &vec![hand]);
+        assert!(scene.get_node(table).unwrap().get_children().is_empty());
+        assert_eq!(scene.roots(), vec![arm, table]);
+    }
+
+    #[test]
+    fn reparenting_under_a_descendant_or_a_flat_parent_is_refused(){
+        let mut scene = SceneGraph::new();
+        let arm = scene.add_node("arm", None, Transform::identity(), None).unwrap();
+        let hand = scene.add_node("hand", Some(arm), Transform::translation(Direction::new(1.0, 0.0, 0.0)), None).unwrap();
+        let flat = scene.add_node("flat", None, Transform::scale(1.0, 1.0, 0.0), None).unwrap();
+        assert!(matches!(scene.reparent(arm, Some(hand)), Err(SceneGraphError::WouldCreateCycle(_, _))));
+        assert!(matches!(scene.reparent(arm, Some(arm)), Err(SceneGraphError::WouldCreateCycle(_, _))));
+        assert!(matches!(scene.reparent(hand, Some(flat)), Err(SceneGraphError::SingularTransform(_))));
+        //nothing moved
+        assert_eq!(scene.get_node(hand).unwrap().get_parent(), Some(arm));
+        assert!(same_transform(&scene.world_transform(hand).unwrap(), &Transform::translation(Direction::new(1.0, 0.0, 0.0))));
+    }
+}
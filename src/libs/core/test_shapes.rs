@@ -0,0 +1,16 @@
+//Solids shared by the tests of several modules
+use super::*;
+
+//the outline (anticlockwise seen from +z) extruded from z0 up to z1, sides wound outwards
+pub(crate) fn prism(outline: &[(f64, f64)], z0: f64, z1: f64) -> PerspectiveObject{
+    let at = |(x, y): (f64, f64), z: f64| Point::from_xyz(na::Vector3::new(x, y, z));
+    let mut sides = vec![
+        Surface::new(outline.iter().rev().map(|corner| at(*corner, z0)).collect()),
+        Surface::new(outline.iter().map(|corner| at(*corner, z1)).collect()),
+    ];
+    for i in 0..outline.len(){
+        let (one, two) = (outline[i], outline[(i + 1) % outline.len()]);
+        sides.push(Surface::new(vec![at(one, z0), at(two, z0), at(two, z1), at(one, z1)]));
+    }
+    PerspectiveObject::new(sides)
+}
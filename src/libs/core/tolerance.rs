@@ -0,0 +1,170 @@
+//Model tolerance and tolerant point comparison. Exact f64 comparison treats points that went
+//through different arithmetic as different even when they are the same corner of a part, so
+//anything that asks "is this the same point" should go through here instead of ==.
+use super::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+//1e-9 in model units, stored as bits so it can be changed at runtime without threading a
+//tolerance through every call
+static MODEL_TOLERANCE_BITS: AtomicU64 = AtomicU64::new(0x3E112E0BE826D695);
+
+pub fn model_tolerance() -> f64{
+    f64::from_bits(MODEL_TOLERANCE_BITS.load(AtomicOrdering::Relaxed))
+}
+
+//non positive or non finite tolerances are ignored
+pub fn set_model_tolerance(tolerance: f64){
+    if tolerance > 0.0 && tolerance.is_finite(){
+        MODEL_TOLERANCE_BITS.store(tolerance.to_bits(), AtomicOrdering::Relaxed);
+    }
+}
+
+pub fn approx_eq(one: f64, other: f64, tolerance: f64) -> bool{
+    (one - other).abs() <= tolerance
+}
+
+impl Point{
+    pub fn approx_eq(&self, other: &Point, tolerance: f64) -> bool{
+        (self.xyz() - other.xyz()).norm() <= tolerance
+    }
+    //same point within the model tolerance
+    pub fn coincident(&self, other: &Point) -> bool{
+        self.approx_eq(other, model_tolerance())
+    }
+}
+impl Direction{
+    pub fn approx_eq(&self, other: &Direction, tolerance: f64) -> bool{
+        (self.to_vector() - other.to_vector()).norm() <= tolerance
+    }
+    //parallel or anti parallel within an angular tolerance in radians
+    pub fn is_parallel(&self, other: &Direction, angular_tolerance: f64) -> bool{
+        let lengths = self.length() * other.length();
+        if lengths < f64::EPSILON{
+            return false;
+        }
+        self.cross(other).length() / lengths <= angular_tolerance.sin()
+    }
+}
+
+type Cell = (i64, i64, i64);
+
+fn cell_of(position: &na::Vector3<f64>, cell_size: f64) -> Cell{
+    (
+        (position.x / cell_size).floor() as i64,
+        (position.y / cell_size).floor() as i64,
+        (position.z / cell_size).floor() as i64,
+    )
+}
+
+//Merges points closer than the tolerance using a spatial hash with cells the size of the
+//tolerance, so only the 27 cells around each point need checking. Returns the welded points (the
+//first point of each cluster is kept, in input order) and for every input point the index of the
+//welded point it became.
+pub fn weld_points_indexed(points: &Vec<Point>, tolerance: f64) -> (Vec<Point>, Vec<usize>){
+    let cell_size = tolerance.max(f64::MIN_POSITIVE);
+    let mut grid: HashMap<Cell, Vec<usize>> = HashMap::new();
+    let mut welded: Vec<Point> = vec![];
+    let mut remap: Vec<usize> = Vec::with_capacity(points.len());
+
+    for point in points.iter(){
+        let position = point.xyz();
+        let (x, y, z) = cell_of(&position, cell_size);
+        let mut found: Option<usize> = None;
+        'search: for dx in -1..=1{
+            for dy in -1..=1{
+                for dz in -1..=1{
+                    //tiny tolerances give cells at the edge of i64, the neighbours there are the cell itself
+                    if let Some(candidates) = grid.get(&(x.saturating_add(dx), y.saturating_add(dy), z.saturating_add(dz))){
+                        for candidate in candidates.iter(){
+                            if welded[*candidate].approx_eq(point, tolerance){
+                                found = Some(*candidate);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        match found{
+            Some(index) => remap.push(index),
+            None => {
+                grid.entry((x, y, z)).or_default().push(welded.len());
+                remap.push(welded.len());
+                welded.push(*point);
+            },
+        }
+    }
+    (welded, remap)
+}
+
+pub fn weld_points(points: &Vec<Point>, tolerance: f64) -> Vec<Point>{
+    weld_points_indexed(points, tolerance).0
+}
+
+impl PerspectiveObject{
+    //Snaps every key point onto the first point within tolerance of it so sides that should share
+    //corners share them exactly, then drops corners a side visits twice in a row
+    pub fn weld_vertices(&mut self, tolerance: f64){
+        let all_points = sides_to_points(&self.sides);
+        let (welded, remap) = weld_points_indexed(&all_points, tolerance);
+        let mut next = 0;
+        for side in self.sides.iter_mut(){
+            let mut new_key_points: Vec<Point> = vec![];
+            for _ in 0..side.key_points.len(){
+                let point = welded[remap[next]];
+                next += 1;
+                if new_key_points.last() != Some(&point){
+                    new_key_points.push(point);
+                }
+            }
+            while new_key_points.len() > 1 && new_key_points.first() == new_key_points.last(){
+                new_key_points.pop();
+            }
+            side.key_points = new_key_points;
+        }
+        self.sides.retain(|side| side.key_points.len() >= 3);
+        self.bounds_cache.invalidate();
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn points_within_tolerance_are_welded_to_the_first(){
+        let tolerance = 1e-6;
+        let points = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            //just over a cell boundary from the first point but still within tolerance
+            Point::new(-0.4e-6, 0.3e-6, 0.0),
+            Point::new(1.0, 0.0, 2e-6),
+            Point::new(0.0, 0.0, 0.9e-6),
+        ];
+        let (welded, remap) = weld_points_indexed(&points, tolerance);
+        assert_eq!(welded, vec![points[0], points[1], points[3]]);
+        assert_eq!(remap, vec![0, 1, 0, 2, 0]);
+        assert_eq!(weld_points(&points, tolerance), welded);
+    }
+
+    #[test]
+    fn welding_closes_sides_that_missed_each_other(){
+        let tolerance = 1e-6;
+        let mut part = super::super::test_shapes::prism(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)], 0.0, 1.0);
+        //nudge the corners of every side a little differently, plus a doubled corner on the top
+        let sides: Vec<Surface> = part.sides.iter().enumerate().map(|(index, side)| Surface::new(side.get_points().iter().map(|point|
+            *point + Direction::new(1e-7 * index as f64, -2e-8 * index as f64, 3e-8)
+        ).collect())).collect();
+        part.sides = sides;
+        assert!(weld_points(&sides_to_points(&part.sides), 0.0).len() > 8);
+        let top = part.sides[1].get_points()[0];
+        part.sides[1].key_points.insert(1, top + Direction::new(0.0, 1e-7, 0.0));
+        part.weld_vertices(tolerance);
+        assert_eq!(part.sides.len(), 6);
+        assert!(part.sides.iter().all(|side| side.get_points().len() == 4));
+        let corners = weld_points(&sides_to_points(&part.sides), 0.0);
+        assert_eq!(corners.len(), 8);
+    }
+}
@@ -0,0 +1,298 @@
+//One affine transform type for everything that moves geometry around: translation, rotation,
+//uniform and non uniform scale, mirroring and any composition of them. Stored as a homogeneous
+//matrix like the camera extrinsics, with the bottom row always 0 0 0 1.
+use super::*;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Transform{
+    matrix: na::Matrix4<f64>,
+}
+
+pub trait Transformable{
+    fn transform(&mut self, transform: &Transform);
+
+    fn transformed(&self, transform: &Transform) -> Self where Self: Sized + Clone{
+        let mut copy = self.clone();
+        copy.transform(transform);
+        copy
+    }
+}
+
+impl Default for Transform{
+    fn default() -> Self{
+        Transform::identity()
+    }
+}
+
+impl Transform{
+    pub fn identity() -> Self{
+        Transform{
+            matrix: na::Matrix4::<f64>::identity(),
+        }
+    }
+    //None if the bottom row is not 0 0 0 1, i.e. the matrix is a projection rather than affine
+    pub fn from_matrix(matrix: na::Matrix4<f64>) -> Option<Self>{
+        let bottom = matrix.row(3);
+        let affine = bottom[0].abs() < f64::EPSILON && bottom[1].abs() < f64::EPSILON &&
+            bottom[2].abs() < f64::EPSILON && (bottom[3] - 1.0).abs() < f64::EPSILON;
+        if affine{
+            Some(Transform{
+                matrix: matrix,
+            })
+        }else{
+            None
+        }
+    }
+    pub fn from_parts(linear: na::Matrix3<f64>, translation: Direction) -> Self{
+        let mut matrix = linear.to_homogeneous();
+        matrix.fixed_view_mut::<3, 1>(0, 3).copy_from(&translation.to_vector());
+        Transform{
+            matrix: matrix,
+        }
+    }
+    pub fn translation(offset: Direction) -> Self{
+        Transform::from_parts(na::Matrix3::identity(), offset)
+    }
+    pub fn rotation(rotation: na::Matrix3<f64>) -> Self{
+        Transform::from_parts(rotation, Direction::new(0.0, 0.0, 0.0))
+    }
+    //right handed rotation by angle radians about the axis through the origin
+    pub fn rotation_about_axis(axis: Direction, angle: f64) -> Self{
+        match axis.normalize(){
+            Some(unit) => Transform::rotation(
+                na::Rotation3::from_axis_angle(&na::Unit::new_unchecked(unit.to_vector()), angle).into_inner()
+                ),
+            None => Transform::identity(),
+        }
+    }
+    //rotation about an axis through an arbitrary point
+    pub fn rotation_about_line(point: Point, axis: Direction, angle: f64) -> Self{
+        let to_origin = Point::origin() - point;
+        Transform::translation(-to_origin)
+            .compose(&Transform::rotation_about_axis(axis, angle))
+            .compose(&Transform::translation(to_origin))
+    }
+    pub fn uniform_scale(factor: f64) -> Self{
+        Transform::scale(factor, factor, factor)
+    }
+    pub fn scale(x: f64, y: f64, z: f64) -> Self{
+        Transform::from_parts(na::Matrix3::from_diagonal(&na::Vector3::new(x, y, z)), Direction::new(0.0, 0.0, 0.0))
+    }
+    //reflection in the plane through point with the given normal
+    pub fn mirror(point: Point, normal: Direction) -> Self{
+        let unit = match normal.normalize(){
+            Some(unit) => unit.to_vector(),
+            None => return Transform::identity(),
+        };
+        let linear = na::Matrix3::<f64>::identity() - unit * unit.transpose() * 2.0;
+        let offset = unit * (2.0 * unit.dot(&point.xyz()));
+        Transform::from_parts(linear, Direction::from_vector(offset))
+    }
+
+    pub fn to_matrix(&self) -> na::Matrix4<f64>{
+        self.matrix
+    }
+    pub fn linear_part(&self) -> na::Matrix3<f64>{
+        self.matrix.fixed_view::<3, 3>(0, 0).into_owned()
+    }
+    pub fn translation_part(&self) -> Direction{
+        Direction::from_vector(self.matrix.fixed_view::<3, 1>(0, 3).into_owned())
+    }
+    //true when the transform turns right handed into left handed, which flips normals and winding
+    pub fn is_mirroring(&self) -> bool{
+        self.linear_part().determinant() < 0.0
+    }
+    pub fn is_rigid(&self, tolerance: f64) -> bool{
+        let linear = self.linear_part();
+        (linear.transpose() * linear - na::Matrix3::identity()).norm() <= tolerance && !self.is_mirroring()
+    }
+
+    //self after other, so applying the result is the same as applying other then self
+    pub fn compose(&self, other: &Transform) -> Transform{
+        Transform{
+            matrix: self.matrix * other.matrix,
+        }
+    }
+    //None for transforms that collapse space (zero scale)
+    pub fn inverse(&self) -> Option<Transform>{
+        self.matrix.try_inverse().map(|matrix| Transform{
+            matrix: matrix,
+        })
+    }
+
+    //Splits the linear part into a proper rotation and the stretch that is left (scale, shear and
+    //any mirroring), linear = rotation * stretch
+    pub fn decompose(&self) -> (na::Matrix3<f64>, na::Matrix3<f64>, Direction){
+        let linear = self.linear_part();
+        let svd = linear.svd(true, true);
+        let mut u = svd.u.unwrap();
+        let v_t = svd.v_t.unwrap();
+        if (u * v_t).determinant() < 0.0{
+            let flipped = -u.column(2);
+            u.set_column(2, &flipped);
+        }
+        let rotation = u * v_t;
+        let stretch = rotation.transpose() * linear;
+        (rotation, stretch, self.translation_part())
+    }
+    pub fn rotation_part(&self) -> na::Matrix3<f64>{
+        self.decompose().0
+    }
+
+    //Blends between two transforms: translation and stretch linearly, rotation along the shortest
+    //arc so a half way rigid transform is still rigid
+    pub fn interpolate(&self, other: &Transform, amount: f64) -> Transform{
+        let (rotation_one, stretch_one, translation_one) = self.decompose();
+        let (rotation_two, stretch_two, translation_two) = other.decompose();
+        let quaternion_one = na::UnitQuaternion::from_matrix(&rotation_one);
+        let quaternion_two = na::UnitQuaternion::from_matrix(&rotation_two);
+        let rotation = match quaternion_one.try_slerp(&quaternion_two, amount, f64::EPSILON){
+            Some(blended) => blended,
+            //opposite rotations have no unique shortest arc, either way round will do
+            None => quaternion_one.nlerp(&quaternion_two, amount),
+        };
+        let stretch = stretch_one * (1.0 - amount) + stretch_two * amount;
+        let translation = translation_one * (1.0 - amount) + translation_two * amount;
+        Transform::from_parts(rotation.to_rotation_matrix().into_inner() * stretch, translation)
+    }
+
+    //transforms are affine so w stays 1 and there is nothing to divide
+    pub fn apply_point(&self, point: &Point) -> Point{
+        Point::from_xyz((self.matrix * point.point_to_vector()).xyz())
+    }
+    //directions ignore the translation
+    pub fn apply_direction(&self, direction: &Direction) -> Direction{
+        self.linear_part() * *direction
+    }
+    //normals need the inverse transpose to stay perpendicular under non uniform scale, the result
+    //is unit length
+    pub fn apply_normal(&self, normal: &Direction) -> Direction{
+        let inverse_transpose = match self.linear_part().try_inverse(){
+            Some(inverse) => inverse.transpose(),
+            None => return *normal,
+        };
+        let mapped = inverse_transpose * *normal;
+        mapped.normalize().unwrap_or(mapped)
+    }
+}
+impl Mul for Transform{
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        self.compose(&other)
+    }
+}
+
+impl Transformable for Point{
+    fn transform(&mut self, transform: &Transform){
+        *self = transform.apply_point(self);
+    }
+}
+impl Transformable for Direction{
+    fn transform(&mut self, transform: &Transform){
+        *self = transform.apply_direction(self);
+    }
+}
+impl Transformable for Line{
+    fn transform(&mut self, transform: &Transform){
+        self.point_a.transform(transform);
+        self.point_b.transform(transform);
+    }
+}
+impl Transformable for Surface{
+    //a mirror reverses the winding, putting it back keeps the normal pointing out of the object
+    fn transform(&mut self, transform: &Transform){
+        for point in self.key_points.iter_mut(){
+            point.transform(transform);
+        }
+        if transform.is_mirroring(){
+            self.key_points.reverse();
+        }
+    }
+}
+impl Transformable for PerspectiveObject{
+    //The rigid part of the transform goes into the pose. Whatever is left over (scale, shear,
+    //mirroring) has to change the shape itself so it is baked into the local sides.
+    fn transform(&mut self, transform: &Transform){
+        let old_pose = self.pose();
+        let new_centre = transform.apply_point(&self.centre);
+        let new_orientation = transform.rotation_part() * self.orientation;
+        let new_pose = Transform::translation(new_centre - Point::origin()).compose(&Transform::rotation(new_orientation));
+        if let Some(new_pose_inverse) = new_pose.inverse(){
+            let local_change = new_pose_inverse.compose(transform).compose(&old_pose);
+            //a rigid transform leaves nothing over, skip the rounding noise of applying it
+            if (local_change.to_matrix() - na::Matrix4::<f64>::identity()).norm() > 1e-12{
+                for side in self.sides.iter_mut(){
+                    side.transform(&local_change);
+                }
+            }
+        }
+        self.centre = new_centre;
+        self.orientation = new_orientation;
+        self.bounds_cache.invalidate();
+    }
+}
+impl Transformable for Camera{
+    //a camera only has a position and orientation, scale and mirroring have nothing to act on
+    fn transform(&mut self, transform: &Transform){
+        let new_centre = transform.apply_point(&self.centre);
+        let new_orientation = transform.rotation_part() * self.orientation;
+        self.update_extrinsics(new_centre, new_orientation);
+    }
+}
+impl Transformable for coordinate_object{
+    fn transform(&mut self, transform: &Transform){
+        match self{
+            Self::Camera_object(camera) => camera.transform(transform),
+            Self::Point_object(point) => point.transform(transform),
+            Self::Perspective_object(perspective_object) => perspective_object.transform(transform),
+            Self::Line_object(line) => line.transform(transform),
+            Self::Plane_object(plane) => plane.transform(transform),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn mirroring_keeps_sides_facing_out(){
+        let square = Surface::new(vec![
+            Point::new(0.0, 0.0, 1.0), Point::new(1.0, 0.0, 1.0), Point::new(1.0, 1.0, 1.0), Point::new(0.0, 1.0, 1.0),
+        ]);
+        let flipped = square.transformed(&Transform::mirror(Point::origin(), Direction::new(0.0, 0.0, 1.0)));
+        assert!((flipped.normal() - na::Vector3::new(0.0, 0.0, -1.0)).norm() < 1e-12);
+
+        let part = super::super::test_shapes::prism(&[(0.0, 0.0), (3.0, 0.0), (3.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)], 0.0, 1.5);
+        let mirror = Transform::mirror(Point::new(0.3, 0.0, 0.0), Direction::new(1.0, 1.0, 0.0));
+        assert!(mirror.is_mirroring());
+        let mirrored = part.transformed(&mirror);
+        for (side, mirrored_side) in part.get_sides().iter().zip(mirrored.get_sides().iter()){
+            let expected = mirror.apply_normal(&Direction::from_vector(side.normal())).to_vector();
+            assert!((mirrored_side.normal() - expected).norm() < 1e-9);
+        }
+        let volume = |object: &PerspectiveObject| object.mass_properties(1.0).unwrap().volume;
+        assert!((volume(&mirrored) - volume(&part)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn decompose_puts_the_transform_back_together(){
+        let shear = Transform::from_parts(na::Matrix3::new(1.0, 0.4, 0.0, 0.0, 1.0, 0.0, 0.2, 0.0, 1.0), Direction::new(0.0, 0.0, 0.0));
+        let transforms = [
+            Transform::identity(),
+            Transform::rotation_about_line(Point::new(1.0, -2.0, 0.5), Direction::new(1.0, 2.0, 3.0), 2.3),
+            Transform::translation(Direction::new(4.0, 5.0, -6.0)).compose(&Transform::scale(2.0, 0.5, 3.0))
+                .compose(&Transform::rotation_about_axis(Direction::new(0.0, 1.0, 0.0), -0.8)),
+            Transform::mirror(Point::new(0.0, 1.0, 0.0), Direction::new(0.0, 1.0, 1.0)).compose(&shear),
+        ];
+        for transform in transforms.iter(){
+            let (rotation, stretch, translation) = transform.decompose();
+            assert!((rotation.transpose() * rotation - na::Matrix3::identity()).norm() < 1e-9);
+            assert!((rotation.determinant() - 1.0).abs() < 1e-9);
+            let rebuilt = Transform::from_parts(rotation * stretch, translation);
+            assert!((rebuilt.to_matrix() - transform.to_matrix()).norm() < 1e-9);
+            assert_eq!(stretch.determinant() < 0.0, transform.is_mirroring());
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//Reference geometry drawn by the camera itself rather than stored in the scene: the ground grid,
+//the world axes at the origin and the orientation gizmo in the corner of the screen. Up is +y, the
+//way the starting camera shows the world.
+use super::*;
+
+const AXIS_COLOURS: [[u8; 3]; 3] = [[0xe0, 0x40, 0x40], [0x40, 0xc0, 0x40], [0x40, 0x70, 0xf0]];
+const AXIS_NAMES: [&str; 3] = ["X", "Y", "Z"];
+const GIZMO_RADIUS: f64 = 30.0;
+//distance in pixels a click has to land within to hit a gizmo axis end
+const GIZMO_HIT_RADIUS: f64 = 8.0;
+//pieces each ground grid line is drawn in, each with its own brightness
+const GRID_FADE_PIECES: usize = 8;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum StandardView{
+    //looking along +z, the starting view
+    Front,
+    Back,
+    //from the +x side
+    Right,
+    Left,
+    //from above, looking down -y
+    Top,
+    Bottom,
+}
+impl StandardView{
+    //the way the camera looks and the way the top of the screen points
+    fn directions(&self) -> (na::Vector3<f64>, na::Vector3<f64>){
+        let (x, y, z) = (na::Vector3::x(), na::Vector3::y(), na::Vector3::z());
+        match self{
+            Self::Front => (z, y),
+            Self::Back => (-z, y),
+            Self::Right => (-x, y),
+            Self::Left => (x, y),
+            Self::Top => (-y, z),
+            Self::Bottom => (y, -z),
+        }
+    }
+    //the view seen when clicking the positive or negative end of a gizmo axis
+    fn from_axis_end(axis: usize, positive: bool) -> Self{
+        match (axis, positive){
+            (0, true) => Self::Right,
+            (0, false) => Self::Left,
+            (1, true) => Self::Top,
+            (1, false) => Self::Bottom,
+            (2, true) => Self::Back,
+            _ => Self::Front,
+        }
+    }
+}
+
+fn faded(colour: [u8; 3], intensity: f64) -> [u8; 4]{
+    let scale = |channel: u8| (channel as f64 * intensity.clamp(0.0, 1.0)) as u8;
+    [scale(colour[0]), scale(colour[1]), scale(colour[2]), 0xff]
+}
+
+impl Camera{
+    //where the eye is in world space, in the convention screen_projection uses
+    pub fn eye_position(&self) -> Point{
+        self.screen_to_ray(0.0, 0.0).origin
+    }
+    //the way the middle of the screen looks
+    pub fn view_direction(&self) -> Direction{
+        self.screen_to_ray(self.calibration_matrix.m13, self.calibration_matrix.m23).direction
+    }
+
+    //Places the eye at the given point looking along forward with up pointing to the top of the
+    //screen. The renderer maps world to camera coordinates with the extrinsics directly, so the
+    //orientation stored is the transpose of the camera's frame and the centre is the eye moved
+    //into that frame.
+    pub fn look_along(&mut self, eye: Point, forward: Direction, up: Direction){
+        let forward = match forward.normalize(){
+            Some(unit) => unit.to_vector(),
+            None => return,
+        };
+        let up = up.to_vector() - forward * forward.dot(&up.to_vector());
+        let up = match up.try_normalize(f64::EPSILON){
+            Some(unit) => unit,
+            None => any_perpendicular(&forward),
+        };
+        //camera y runs down the screen unless the focal length is negative, which turns the
+        //picture upside down
+        let camera_y = if self.calibration_matrix.m22 < 0.0 {up} else {-up};
+        let camera_x = camera_y.cross(&forward);
+        let world_to_camera = na::Matrix3::from_columns(&[camera_x, camera_y, forward]).transpose();
+        let centre = -(world_to_camera * eye.xyz());
+        self.update_extrinsics(Point::from_xyz(centre), world_to_camera);
+        self.update_camera();
+    }
+
+    //Looks at the target from the standard direction, keeping the eye as far from it as it is now
+    pub fn snap_to_view(&mut self, view: StandardView, target: Point){
+        let (forward, up) = view.directions();
+        let distance = (self.eye_position() - target).length().max(self.min_depth_difference * 10.0);
+        let eye = target - Direction::from_vector(forward) * distance;
+        self.look_along(eye, Direction::from_vector(forward), Direction::from_vector(up));
+    }
+
+    //Grid on the plane centred under the eye so it never runs out, fading out with the distance
+    //from the eye until it is gone at the plane's half extent. Each line is cut once to the circle
+    //on the plane where it is still visible and drawn in a few pieces of falling brightness.
+    pub fn draw_ground_grid(&self, pixel_buffer: &mut Vec<[u8;4]>, ground: &Plane){
+        let spacing = ground.get_grid_spacing();
+        let fade_distance = ground.get_half_extent();
+        let eye = self.eye_position();
+        let (eye_u, eye_v) = ground.to_plane_coordinates(&eye);
+        let height = ground.signed_distance(&eye);
+        //radius of that circle, around the point under the eye
+        let reach_squared = fade_distance * fade_distance - height * height;
+        if reach_squared <= 0.0{
+            return;
+        }
+        let (centre_u, centre_v) = ((eye_u / spacing).round() * spacing, (eye_v / spacing).round() * spacing);
+        let count = (reach_squared.sqrt() / spacing).ceil() as i32;
+        let brightness = |point: &Point| 1.0 - (*point - eye).length() / fade_distance;
+
+        for line in -count..=count{
+            let across = line as f64 * spacing;
+            for along_v in [true, false]{
+                //how far the line passes from under the eye and where along it that is
+                let (offset, middle) = if along_v {(centre_u + across - eye_u, eye_v)} else {(centre_v + across - eye_v, eye_u)};
+                let half_length_squared = reach_squared - offset * offset;
+                if half_length_squared <= 0.0{
+                    continue;
+                }
+                let half_length = half_length_squared.sqrt();
+                let point_at = |along: f64| if along_v {ground.from_plane_coordinates(centre_u + across, along)} else {ground.from_plane_coordinates(along, centre_v + across)};
+                let piece_length = 2.0 * half_length / GRID_FADE_PIECES as f64;
+                for piece in 0..GRID_FADE_PIECES{
+                    let start = middle - half_length + piece as f64 * piece_length;
+                    let (one, two) = (point_at(start), point_at(start + piece_length));
+                    let intensity = brightness(&one).max(brightness(&two));
+                    if intensity > 0.0{
+                        self.draw_line(pixel_buffer, &Line::new(one, two), faded([0x50, 0x50, 0x50], intensity));
+                    }
+                }
+            }
+        }
+    }
+
+    //X, Y and Z from the origin in red, green and blue
+    pub fn draw_origin_axes(&self, pixel_buffer: &mut Vec<[u8;4]>, length: f64){
+        for axis in 0..3{
+            let mut end = na::Vector3::<f64>::zeros();
+            end[axis] = length;
+            let colour = faded(AXIS_COLOURS[axis], 1.0);
+            self.draw_line(pixel_buffer, &Line::new(Point::origin(), Point::from_xyz(end)), colour);
+            self.draw_label(pixel_buffer, AXIS_NAMES[axis], &Point::from_xyz(end), colour);
+        }
+    }
+
+    fn gizmo_centre(&self) -> (f64, f64){
+        (GIZMO_RADIUS + 12.0, self.screen_y as f64 - GIZMO_RADIUS - 12.0)
+    }
+
+    //Where each world axis points on screen from the gizmo centre, shortened as it turns towards
+    //or away from the viewer. Worked out by projecting a step along the axis in front of the eye
+    //so it always agrees with the main view.
+    fn gizmo_axis_ends(&self) -> [(f64, f64); 3]{
+        let forward = self.view_direction();
+        let in_front = self.eye_position() + forward * (self.min_depth_difference * 10.0);
+        let (centre_x, centre_y) = self.gizmo_centre();
+        let mut ends = [(centre_x, centre_y); 3];
+        let start = match self.project_to_screen(&in_front){
+            Some(position) => position,
+            None => return ends,
+        };
+        for axis in 0..3{
+            let mut unit = na::Vector3::<f64>::zeros();
+            unit[axis] = 1.0;
+            let step = Direction::from_vector(unit) * (self.min_depth_difference * 0.1);
+            if let Some(stepped) = self.project_to_screen(&(in_front + step)){
+                let (delta_x, delta_y) = (stepped.0 - start.0, stepped.1 - start.1);
+                let length = (delta_x * delta_x + delta_y * delta_y).sqrt();
+                if length > f64::EPSILON{
+                    let along_view = forward.to_vector()[axis];
+                    let shown = GIZMO_RADIUS * (1.0 - along_view * along_view).max(0.0).sqrt();
+                    ends[axis] = (centre_x + delta_x / length * shown, centre_y + delta_y / length * shown);
+                }
+            }
+        }
+        ends
+    }
+
+    //The world axes as seen by this camera, in the bottom left corner. Positive ends are labelled,
+    //negative ends get a dim stub. Axes pointing away are drawn first so the nearer ones cover them.
+    pub fn draw_orientation_gizmo(&self, pixel_buffer: &mut Vec<[u8;4]>){
+        let centre = self.gizmo_centre();
+        let ends = self.gizmo_axis_ends();
+        let forward = self.view_direction().to_vector();
+        let mut order = [0, 1, 2];
+        order.sort_by(|one, two| forward[*two].total_cmp(&forward[*one]));
+        for axis in order.iter(){
+            let (end_x, end_y) = ends[*axis];
+            let opposite = (2.0 * centre.0 - end_x, 2.0 * centre.1 - end_y);
+            self.draw_screen_line(pixel_buffer, centre, opposite, faded(AXIS_COLOURS[*axis], 0.35));
+            self.draw_screen_line(pixel_buffer, centre, (end_x, end_y), faded(AXIS_COLOURS[*axis], 1.0));
+            self.draw_text(pixel_buffer, AXIS_NAMES[*axis], (end_x as i32 - 2, end_y as i32 - 3), 1, faded(AXIS_COLOURS[*axis], 1.0));
+        }
+    }
+
+    //The standard view for the gizmo axis end under the screen position, if any
+    pub fn gizmo_hit(&self, screen_x: f64, screen_y: f64) -> Option<StandardView>{
+        let centre = self.gizmo_centre();
+        let ends = self.gizmo_axis_ends();
+        let mut best: Option<(f64, StandardView)> = None;
+        for axis in 0..3{
+            let (end_x, end_y) = ends[axis];
+            for (positive, (x, y)) in [(true, (end_x, end_y)), (false, (2.0 * centre.0 - end_x, 2.0 * centre.1 - end_y))]{
+                let distance = ((x - screen_x).powi(2) + (y - screen_y).powi(2)).sqrt();
+                if distance <= GIZMO_HIT_RADIUS && best.map_or(true, |(closest, _)| distance < closest){
+                    best = Some((distance, StandardView::from_axis_end(axis, positive)));
+                }
+            }
+        }
+        best.map(|(_, view)| view)
+    }
+}
@@ -0,0 +1,215 @@
+//Text and simple controls for the viewport. Glyphs come from an embedded 5x7 bitmap font covering
+//printable ASCII and are written straight into the RGBA pixel buffer the cameras render into, one
+//row of screen_width pixels after another. Anything outside the buffer is clipped.
+
+pub const GLYPH_WIDTH: i32 = 5;
+pub const GLYPH_HEIGHT: i32 = 7;
+//one blank column between characters and two blank rows between lines
+pub const GLYPH_ADVANCE: i32 = 6;
+pub const LINE_HEIGHT: i32 = 9;
+
+//Five columns per glyph from left to right, the lowest bit of each column is the top row
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x56, 0x20, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x14, 0x08, 0x3e, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x10, 0x08, 0x08, 0x10, 0x08], // ~
+];
+const DEGREE: [u8; 5] = [0x00, 0x06, 0x09, 0x09, 0x06];
+
+//characters the font does not cover are drawn as a question mark
+pub fn glyph(character: char) -> [u8; 5]{
+    match character{
+        ' '..='~' => FONT[character as usize - ' ' as usize],
+        '°' => DEGREE,
+        _ => FONT['?' as usize - ' ' as usize],
+    }
+}
+
+//width and height in pixels the text takes up, lines are split on '\n'
+pub fn text_size(text: &str, scale: i32) -> (i32, i32){
+    let lines: Vec<&str> = text.split('\n').collect();
+    let longest = lines.iter().map(|line| line.chars().count() as i32).max().unwrap_or(0);
+    let width = if longest > 0 {(longest * GLYPH_ADVANCE - 1) * scale} else {0};
+    let height = ((lines.len() as i32 - 1) * LINE_HEIGHT + GLYPH_HEIGHT) * scale;
+    (width, height)
+}
+
+//Writes the text with its top left corner at (x, y). Each font pixel becomes a scale by scale
+//block, scales below 1 are drawn at 1.
+pub fn draw_text(pixel_buffer: &mut Vec<[u8;4]>, screen_width: i32, screen_height: i32, text: &str, x: i32, y: i32, scale: i32, colour: [u8;4]){
+    let scale = scale.max(1);
+    for (line_number, line) in text.split('\n').enumerate(){
+        let top = y + line_number as i32 * LINE_HEIGHT * scale;
+        for (index, character) in line.chars().enumerate(){
+            let left = x + index as i32 * GLYPH_ADVANCE * scale;
+            for (column, bits) in glyph(character).iter().enumerate(){
+                for row in 0..GLYPH_HEIGHT{
+                    if bits & (1 << row) == 0{
+                        continue;
+                    }
+                    for y_i in top + row * scale .. top + (row + 1) * scale{
+                        for x_i in left + column as i32 * scale .. left + (column as i32 + 1) * scale{
+                            if 0 <= y_i && y_i < screen_height && 0 <= x_i && x_i < screen_width{
+                                pixel_buffer[(y_i * screen_width + x_i) as usize] = colour;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn fill_rect(pixel_buffer: &mut Vec<[u8;4]>, screen_width: i32, screen_height: i32, x: i32, y: i32, width: i32, height: i32, colour: [u8;4]){
+    for y_i in y.max(0)..(y + height).min(screen_height){
+        for x_i in x.max(0)..(x + width).min(screen_width){
+            pixel_buffer[(y_i * screen_width + x_i) as usize] = colour;
+        }
+    }
+}
+
+//pixels from the top of one slider to the top of the next when they are stacked
+pub const SLIDER_HEIGHT: i32 = 18;
+
+//A horizontal slider: the label and current value on one line, the track with its knob under it
+#[derive(Clone,Debug)]
+pub struct Slider{
+    pub label: String,
+    pub min: f64,
+    pub max: f64,
+    pub value: f64,
+    //top left corner and track length in pixels
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+}
+impl Slider{
+    pub fn new(label: &str, min: f64, max: f64, value: f64, x: i32, y: i32, width: i32) -> Self{
+        Slider{
+            label: label.to_string(),
+            min: min,
+            max: max,
+            value: value,
+            x: x,
+            y: y,
+            width: width,
+        }
+    }
+    fn track_y(&self) -> i32{
+        self.y + LINE_HEIGHT + 2
+    }
+    //values outside the range sit the knob at the nearer end
+    fn knob_x(&self) -> i32{
+        let span = self.max - self.min;
+        let fraction = if span > 0.0 {((self.value - self.min) / span).clamp(0.0, 1.0)} else {0.0};
+        self.x + (fraction * self.width as f64).round() as i32
+    }
+    pub fn draw(&self, pixel_buffer: &mut Vec<[u8;4]>, screen_width: i32, screen_height: i32, colour: [u8;4]){
+        let text = format!("{} {:.2}", self.label, self.value);
+        draw_text(pixel_buffer, screen_width, screen_height, &text, self.x, self.y, 1, colour);
+        fill_rect(pixel_buffer, screen_width, screen_height, self.x, self.track_y(), self.width + 1, 1, [0x80, 0x80, 0x80, 0xff]);
+        fill_rect(pixel_buffer, screen_width, screen_height, self.knob_x() - 1, self.track_y() - 3, 3, 7, colour);
+    }
+    //the track and the label above it, with a little room either side for the knob
+    pub fn contains(&self, x: f64, y: f64) -> bool{
+        let (x, y) = (x as i32, y as i32);
+        self.x - 3 <= x && x <= self.x + self.width + 3 && self.y <= y && y <= self.track_y() + 4
+    }
+    //the value for a cursor at screen x, held to the range
+    pub fn value_at(&self, x: f64) -> f64{
+        let fraction = if self.width > 0 {((x - self.x as f64) / self.width as f64).clamp(0.0, 1.0)} else {0.0};
+        self.min + fraction * (self.max - self.min)
+    }
+}
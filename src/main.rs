@@ -14,7 +14,7 @@ mod libs{
 #[deny(clippy::all)]
 #[forbid(unsafe_code)]
 use std::{thread, env};
-use std::time::{Duration};
+use std::time::{Duration, Instant};
 use error_iter::ErrorIter as _;
 use log::error;
 use pixels::{Error, Pixels, SurfaceTexture};
@@ -39,19 +39,70 @@ fn main() -> Result<(), Error> {
 }
 
 struct WorldSpace {
+    //world space copies of the objects in the scene graph, rebuilt by sync_scene
     all_independents: Vec<coordinate_object>,
+    //the scene node each entry of all_independents came from
+    independent_nodes: Vec<NodeId>,
+    scene: SceneGraph,
     cameras: Vec<Camera>,
+    //bounding volume hierarchy over all_independents, indices match that vector
+    spatial_index: Bvh,
+    //scene node of the plane new sketches are drawn on, the world XY plane when None
+    active_workplane: Option<NodeId>,
+    //temporary geometry drawn over the view, e.g. the current measurement, not part of the scene
+    overlay_lines: Vec<Line>,
+    overlay_points: Vec<Point>,
+    //dimensions and notes, resolved against all_independents whenever they are drawn
+    annotations: Vec<Annotation>,
+    //status text in the top left corner of the view
+    hud_lines: Vec<String>,
+    //the plane the reference grid is drawn on, its half extent is how far the grid reaches
+    ground: Plane,
+    //joints between parts, each moves its child's node
+    joints: Vec<Joint>,
+    //part definitions and the instances of them placed in the scene
+    assembly: Assembly,
+    //a slider per joint degree of freedom down the right side of the view
+    show_joint_sliders: bool,
+    //joint values held fixed when closing loops, kept by joint so they follow joints being removed
+    driving_values: Vec<JointValue>,
+    //gears, belts, racks and screws tying joint values to one another
+    couplings: Vec<JointCoupling>,
+    //the last motion study run and the frame of it being shown
+    motion_study: Option<MotionStudy>,
+    motion_frame: usize,
 }
 impl WorldSpace{
     fn new() -> Self{
         WorldSpace{
             all_independents: vec![],
+            independent_nodes: vec![],
+            scene: SceneGraph::new(),
             cameras: vec![],
+            spatial_index: Bvh::new(),
+            active_workplane: None,
+            overlay_lines: vec![],
+            overlay_points: vec![],
+            annotations: vec![],
+            hud_lines: vec![],
+            ground: WorldSpace::default_ground(),
+            joints: vec![],
+            assembly: Assembly::new(),
+            show_joint_sliders: false,
+            driving_values: vec![],
+            couplings: vec![],
+            motion_study: None,
+            motion_frame: 0,
         }
     }
+    fn default_ground() -> Plane{
+        let mut ground = Plane::zx();
+        ground.set_grid(40.0, 1.0);
+        ground
+    }
     fn setup(&mut self) {
         let mut camera = Camera::new();
-        camera.update_extrinsics_centre(Point::new(0.0,0.0,0.0,1.0));
+        camera.update_extrinsics_centre(Point::new(0.0,0.0,0.0));
         //camera.rotate(na::Matrix3::new(0.707107, 0.0, 0.707107,
         //    0.0, 1.0, 0.0,
         //    -0.707107, 0.0, 0.707107));
@@ -61,15 +112,461 @@ impl WorldSpace{
         //  1.0, 0.0, 0.0)); 
         //camera.rotate_degrees_y(90.0);
         self.register_object(coordinate_object::Camera_object(camera));
-        self.register_object(coordinate_object::Point_object(Point::new(0.0,0.0,5.0,1.0)));
-        let mut visible_objects: Vec<&coordinate_object>;
+        self.register_object(coordinate_object::Point_object(Point::new(0.0,0.0,5.0)));
+        //a workplane just behind the demo linkage so its grid shows where the parts sit
+        let mut workplane = Plane::xy().transformed(&Transform::translation(Direction::new(0.0, 0.0, 8.5)));
+        workplane.set_grid(6.0, 0.5);
+        if let Some(id) = self.register_object(coordinate_object::Plane_object(workplane)){
+            let _ = self.set_active_workplane(id);
+        }
+        if self.add_demo_linkage().is_none(){
+            self.set_hud(vec!["demo linkage could not be built".to_string()]);
+        }
         self.update_cameras();
     }
-    fn register_object(&mut self, object: coordinate_object){
+    //A crank and rocker four bar in front of the camera to try the joint sliders, limits, motion
+    //playback and collision checks on. The crank drives the loop and turns once in the motion
+    //study, the rocker's limit is only flagged so it lights up as the crank comes round.
+    fn add_demo_linkage(&mut self) -> Option<()>{
+        let (crank_length, coupler_length, rocker_length, ground_length) = (1.0, 2.5, 2.0, 3.0);
+        let base = Transform::translation(Direction::new(-1.5, -1.0, 8.0));
+        let mut nodes = vec![];
+        for (name, length) in [("ground", ground_length), ("crank", crank_length), ("coupler", coupler_length), ("rocker", rocker_length)]{
+            let definition = self.add_part_definition(name, WorldSpace::demo_bar(length, 0.2));
+            let instance = self.add_part_instance(definition, name, None, base).ok()?;
+            nodes.push(self.assembly.get_instance(instance).ok()?.get_node());
+        }
+        let (ground, crank, coupler, rocker) = (nodes[0], nodes[1], nodes[2], nodes[3]);
+        let at = |x: f64| Transform::translation(Direction::new(x, 0.0, 0.0));
+
+        let crank_joint = self.add_joint(Joint::new("crank", JointType::Revolute, ground, at(0.0), crank, at(0.0)).ok()?).ok()?;
+        let coupler_joint = self.add_joint(Joint::new("coupler", JointType::Revolute, crank, at(crank_length), coupler, at(0.0)).ok()?).ok()?;
+        let rocker_joint = self.add_joint(Joint::new("rocker", JointType::Revolute, ground, at(ground_length), rocker, at(0.0)).ok()?).ok()?;
+        //roughly assembled with the crank straight up, closing the loop brings the pin together
+        self.set_joint_values(crank_joint, vec![std::f64::consts::FRAC_PI_2]).ok()?;
+        self.set_joint_values(coupler_joint, vec![-1.2]).ok()?;
+        self.set_joint_values(rocker_joint, vec![1.9]).ok()?;
+        self.add_joint(Joint::new("pin", JointType::Revolute, coupler, at(coupler_length), rocker, at(rocker_length)).ok()?).ok()?;
+        self.set_driving_values(vec![JointValue::new(crank_joint, 0)]).ok()?;
+
+        self.set_joint_limit(rocker_joint, 0, Some(DofLimit::new(1.7, 2.5))).ok()?;
+        self.set_joint_limit_policy(rocker_joint, LimitPolicy::Flag).ok()?;
+
+        let mut study = MotionStudy::new(4.0, 0.05);
+        study.add_driver(JointValue::new(crank_joint, 0), MotionProfile::ConstantVelocity{start: std::f64::consts::FRAC_PI_2, velocity: std::f64::consts::FRAC_PI_2});
+        self.run_motion_study(study).ok()
+    }
+    //a bar along x with the pivots at 0 and length, square in section
+    fn demo_bar(length: f64, width: f64) -> PerspectiveObject{
+        let half = width / 2.0;
+        let corner = |index: usize| Point::new(
+            if index & 1 == 0 {-half} else {length + half},
+            if index & 2 == 0 {-half} else {half},
+            if index & 4 == 0 {-half} else {half});
+        let faces = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        PerspectiveObject::new(faces.iter().map(|face| Surface::new(face.iter().map(|index| corner(*index)).collect())).collect())
+    }
+    //objects other than cameras go in as top level scene nodes, the node id is returned so they
+    //can be moved or parented later
+    fn register_object(&mut self, object: coordinate_object) -> Option<NodeId>{
         match object{
-            Camera_object(camera) => self.cameras.push(camera),
-            _ => self.all_independents.push(object),
+            Camera_object(camera) => {
+                self.cameras.push(camera);
+                None
+            },
+            _ => self.register_object_under(None, object, Transform::identity()).ok(),
+        }
+    }
+    //object is in the new node's local coordinates
+    fn register_object_under(&mut self, parent: Option<NodeId>, object: coordinate_object, local_transform: Transform) -> Result<NodeId, SceneGraphError>{
+        let id = self.scene.add_node("", parent, local_transform, Some(object))?;
+        //a new node has no children and the newest id, so it goes on the end just as sync_scene
+        //would place it
+        if let Some(world_object) = self.scene.world_object(id)?{
+            self.spatial_index.insert_object(world_object.axis_aligned_bounds());
+            self.independent_nodes.push(id);
+            self.all_independents.push(world_object);
+            if self.spatial_index.needs_rebuild(){
+                self.spatial_index = Bvh::build(&self.all_independents);
+            }
+        }
+        Ok(id)
+    }
+    //a node with no object of its own, used to group a subassembly so it moves as one
+    fn add_group(&mut self, name: &str, parent: Option<NodeId>, local_transform: Transform) -> Result<NodeId, SceneGraphError>{
+        self.scene.add_node(name, parent, local_transform, None)
+    }
+    fn move_node(&mut self, id: NodeId, change: Transform) -> Result<(), SceneGraphError>{
+        self.scene.apply_transform(id, change)?;
+        self.apply_all_joints()?;
+        self.sync_scene();
+        Ok(())
+    }
+    fn reparent_node(&mut self, id: NodeId, new_parent: Option<NodeId>) -> Result<(), SceneGraphError>{
+        self.scene.reparent(id, new_parent)?;
+        self.apply_all_joints()?;
+        self.sync_scene();
+        Ok(())
+    }
+    fn add_part_definition(&mut self, name: &str, geometry: PerspectiveObject) -> DefinitionId{
+        self.assembly.add_definition(name, geometry)
+    }
+    fn add_part_instance(&mut self, definition: DefinitionId, name: &str, parent: Option<NodeId>, pose: Transform) -> Result<InstanceId, AssemblyError>{
+        let id = self.assembly.instantiate(&mut self.scene, definition, name, parent, pose)?;
+        self.sync_scene();
+        Ok(id)
+    }
+    fn edit_part_definition(&mut self, definition: DefinitionId, edit: impl FnOnce(&mut PerspectiveObject)) -> Result<Vec<InstanceId>, AssemblyError>{
+        let updated = self.assembly.edit_definition(&mut self.scene, definition, edit)?;
+        self.sync_scene();
+        Ok(updated)
+    }
+    //joints that lost a part along with the instance are removed too, and couplings and driving
+    //values on those joints
+    fn remove_part_instance(&mut self, instance: InstanceId) -> Result<(), AssemblyError>{
+        self.assembly.remove_instance(&mut self.scene, instance)?;
+        let scene = &self.scene;
+        let kept: Vec<bool> = self.joints.iter().map(|joint| scene.get_node(joint.get_parent()).is_ok() && scene.get_node(joint.get_child()).is_ok()).collect();
+        //where each kept joint ends up once the others are gone
+        let new_index = |joint: usize| if kept[joint] {Some(kept[..joint].iter().filter(|kept| **kept).count())} else {None};
+        self.couplings = self.couplings.drain(..).filter_map(|mut coupling| {
+            coupling.follower.joint = new_index(coupling.follower.joint)?;
+            for (leader, _) in coupling.leaders.iter_mut(){
+                leader.joint = new_index(leader.joint)?;
+            }
+            Some(coupling)
+        }).collect();
+        self.driving_values = self.driving_values.drain(..).filter_map(|mut driving| {
+            driving.joint = new_index(driving.joint)?;
+            Some(driving)
+        }).collect();
+        let mut index = 0;
+        self.joints.retain(|_| {
+            index += 1;
+            kept[index - 1]
+        });
+        if self.active_workplane.map_or(false, |id| scene.get_node(id).is_err()){
+            self.active_workplane = None;
+        }
+        if kept.contains(&false){
+            self.clear_motion_study();
+        }
+        self.sync_scene();
+        Ok(())
+    }
+    //The child is moved straight away so the two joint frames line up. A joint on a part another
+    //joint already moves (or that leads back round to its own parent) closes a loop instead, the
+    //loop is solved for it and the joint is refused if the parts cannot be assembled.
+    fn add_joint(&mut self, joint: Joint) -> Result<usize, JointError>{
+        self.scene.get_node(joint.get_parent())?;
+        self.scene.get_node(joint.get_child())?;
+        self.joints.push(joint);
+        if let Err(error) = self.set_configuration(&self.get_configuration()){
+            self.joints.pop();
+            return Err(error.into());
+        }
+        self.clear_motion_study();
+        Ok(self.joints.len() - 1)
+    }
+    //Ties joint values together from now on, the followers are moved to match their leaders
+    //straight away. The coupling is refused if it is invalid or the loops cannot be closed with it.
+    fn add_coupling(&mut self, coupling: JointCoupling) -> Result<usize, KinematicsError>{
+        self.couplings.push(coupling);
+        if let Err(error) = self.set_configuration(&self.get_configuration()){
+            self.couplings.pop();
+            return Err(error);
+        }
+        self.clear_motion_study();
+        Ok(self.couplings.len() - 1)
+    }
+    //The values it set stay where they are but are checked again without it, like add_coupling the
+    //change is refused if the loops can no longer be closed
+    fn remove_coupling(&mut self, index: usize) -> Result<JointCoupling, KinematicsError>{
+        if index >= self.couplings.len(){
+            return Err(KinematicsError::InvalidCoupling(index));
+        }
+        let removed = self.couplings.remove(index);
+        if let Err(error) = self.set_configuration(&self.get_configuration()){
+            self.couplings.insert(index, removed);
+            return Err(error);
+        }
+        self.clear_motion_study();
+        Ok(removed)
+    }
+    //the tree of the current joints with the couplings resolved against them
+    fn kinematic_tree(&self) -> Result<KinematicTree, KinematicsError>{
+        KinematicTree::new(&self.joints).with_couplings(&self.joints, &self.couplings)
+    }
+    //the joint's values drive any loop it is on, along with the driving values
+    fn set_joint_values(&mut self, index: usize, values: Vec<f64>) -> Result<Option<LoopSolution>, JointError>{
+        let mut configuration = self.get_configuration();
+        let tree = self.kinematic_tree()?;
+        let joint = self.joints.get(index).ok_or(JointError::JointNotFound(index))?;
+        let range = tree.value_range(&self.joints, index);
+        configuration[range.clone()].copy_from_slice(&joint.limited_values(&values)?);
+        Ok(self.set_configuration_driven(&configuration, &range.collect::<Vec<usize>>())?)
+    }
+    //every joint's values one after the other in joint order, the vector set_configuration takes
+    fn get_configuration(&self) -> Vec<f64>{
+        self.joints.iter().flat_map(|joint| joint.get_values().iter().cloned()).collect()
+    }
+    //values the loop closure solver leaves alone, the rest follow them. Refused if any of them
+    //does not exist.
+    fn set_driving_values(&mut self, driving: Vec<JointValue>) -> Result<(), KinematicsError>{
+        if let Some(invalid) = driving.iter().find(|value| value.index(&self.joints).is_none()){
+            return Err(KinematicsError::InvalidDrivingValue(*invalid));
+        }
+        self.driving_values = driving;
+        self.clear_motion_study();
+        Ok(())
+    }
+    fn set_configuration(&mut self, values: &[f64]) -> Result<Option<LoopSolution>, KinematicsError>{
+        self.set_configuration_driven(values, &[])
+    }
+    //Poses every part in the joint tree for the combined joint values in one forward kinematics
+    //pass, then refreshes only the objects that moved. The values go through each joint's limit
+    //policy first and coupled values are set from their leaders after it. A coupled value that
+    //ends up outside its range is refused unless its joint's policy is Flag, clamping it would
+    //break the coupling. When joints close loops the values not driving (the driving values plus
+    //those in also_driving) are solved to close them, and the solution is returned.
+    fn set_configuration_driven(&mut self, values: &[f64], also_driving: &[usize]) -> Result<Option<LoopSolution>, KinematicsError>{
+        let tree = self.kinematic_tree()?;
+        let mut values = values.to_vec();
+        tree.apply_couplings(&mut values);
+        let mut values = tree.apply_limit_policies(&self.joints, &values)?;
+        tree.apply_couplings(&mut values);
+        let mut solution = None;
+        if !tree.get_closures().is_empty(){
+            let driving: Vec<usize> = self.driving_values.iter().filter_map(|value| value.index(&self.joints)).chain(also_driving.iter().cloned()).collect();
+            let scene = &self.scene;
+            let solved = solve_loop_closure(&tree, &self.joints, &self.get_configuration(), &values, &driving, &LoopSettings::default(), |node| scene.world_transform(node).ok())?;
+            values = solved.values.clone();
+            solution = Some(solved);
+        }
+        //flagged joints have no limits here
+        if let Some(follower) = tree.followers_beyond_limits(&values, &tree.limits(&self.joints)).first(){
+            let joint = (0..self.joints.len()).find(|joint| tree.value_range(&self.joints, *joint).contains(follower)).unwrap_or(0);
+            return Err(KinematicsError::BeyondLimit{
+                joint: joint,
+                dof: follower - tree.value_range(&self.joints, joint).start,
+                value: values[*follower],
+            });
+        }
+        tree.scatter_values(&mut self.joints, &values)?;
+        self.pose_tree(&tree)?;
+        Ok(solution)
+    }
+    //puts every part the tree joints move where their current values say
+    fn pose_tree(&mut self, tree: &KinematicTree) -> Result<(), KinematicsError>{
+        let scene = &self.scene;
+        let world = tree.forward(&self.joints, &self.get_configuration(), |node| scene.world_transform(node).ok())?;
+        let mut moved = vec![];
+        for index in tree.get_order().iter(){
+            let child = self.joints[*index].get_child();
+            self.scene.set_world_transform(child, world[&child])?;
+            moved.push(child);
+        }
+        self.sync_moved(&moved);
+        Ok(())
+    }
+    //Solves the joints from the base to the end part for the tool (a frame in the end part's local
+    //coordinates) to reach the target, starting from the current values. The parts are only posed
+    //when the solver converges, otherwise everything stays where it was. Settings without limits
+    //get the joints' own.
+    fn solve_inverse_kinematics(&mut self, end: NodeId, tool: Transform, target: IkTarget, settings: &IkSettings) -> Result<IkSolution, IkError>{
+        let tree = self.kinematic_tree()?;
+        let mut settings = settings.clone();
+        if settings.limits.is_empty(){
+            settings.limits = tree.limits(&self.joints);
+        }
+        let scene = &self.scene;
+        let solution = solve_inverse_kinematics(&tree, &self.joints, &self.get_configuration(), end, &tool, &target, &settings, |node| scene.world_transform(node).ok())?;
+        self.set_configuration(&solution.values)?;
+        Ok(solution)
+    }
+    //The current values are put through the new limit straight away, so Clamp pulls them inside
+    //it and anything coupled or looped to them follows. A limit the assembly cannot meet, such as
+    //one the values are outside of under Reject, is refused and the old one kept.
+    fn set_joint_limit(&mut self, index: usize, dof: usize, limit: Option<DofLimit>) -> Result<(), JointError>{
+        let joint = self.joints.get_mut(index).ok_or(JointError::JointNotFound(index))?;
+        let before = *joint.get_limits().get(dof).ok_or(JointError::NoSuchDegreeOfFreedom(dof))?;
+        joint.set_limit(dof, limit)?;
+        if let Err(error) = self.set_configuration(&self.get_configuration()){
+            self.joints[index].set_limit(dof, before)?;
+            return Err(error.into());
+        }
+        self.clear_motion_study();
+        Ok(())
+    }
+    //re-applies the current values under the new policy like set_joint_limit
+    fn set_joint_limit_policy(&mut self, index: usize, policy: LimitPolicy) -> Result<(), JointError>{
+        let joint = self.joints.get_mut(index).ok_or(JointError::JointNotFound(index))?;
+        let before = joint.get_limit_policy();
+        joint.set_limit_policy(policy);
+        if let Err(error) = self.set_configuration(&self.get_configuration()){
+            self.joints[index].set_limit_policy(before);
+            return Err(error.into());
+        }
+        self.clear_motion_study();
+        Ok(())
+    }
+    //the joints at or beyond their limits as they stand now
+    fn limit_report(&self) -> Result<Vec<LimitReport>, KinematicsError>{
+        KinematicTree::new(&self.joints).limit_report(&self.joints, &self.get_configuration())
+    }
+    //Steps the assembly through the study frame by frame from the current configuration and keeps
+    //the frames for playback, showing the first. If a frame cannot be posed the assembly goes back
+    //to where it started.
+    fn run_motion_study(&mut self, mut study: MotionStudy) -> Result<(), MotionError>{
+        let start = self.get_configuration();
+        let tree = KinematicTree::new(&self.joints);
+        let parts: Vec<(NodeId, String)> = tree.get_order().iter().map(|index| {
+            let node = self.joints[*index].get_child();
+            let name = self.scene.get_node(node).map(|scene_node| scene_node.name.clone()).unwrap_or_default();
+            (node, if name.is_empty() {format!("part {}", node)} else {name})
+        }).collect();
+        let driven = study.driven_values(&self.joints)?;
+        let mut samples = vec![];
+        for time in study.frame_times()?.into_iter(){
+            let values = study.driven_configuration(&self.joints, &self.get_configuration(), time)?;
+            if let Err(error) = self.set_configuration_driven(&values, &driven){
+                let _ = self.set_configuration(&start);
+                return Err(MotionError::Kinematics{time: time, error: error});
+            }
+            let positions = parts.iter()
+                .flat_map(|(node, _)| self.scene.world_transform(*node).map(|world| world.translation_part().to_vector()).unwrap_or_default().iter().cloned().collect::<Vec<f64>>())
+                .collect();
+            samples.push((time, self.get_configuration(), positions));
+        }
+        study.record(self.value_labels(), parts, samples);
+        study.check_velocities(&tree, &self.joints)?;
+        self.motion_study = Some(study);
+        self.show_motion_frame(0);
+        Ok(())
+    }
+    //the recorded frames only fit the joints, couplings, driving values and limits they were
+    //recorded with, so any change to those throws the study away
+    fn clear_motion_study(&mut self){
+        self.motion_study = None;
+        self.motion_frame = 0;
+    }
+    //poses the assembly as recorded in the frame, its loops were solved when it was recorded
+    fn show_motion_frame(&mut self, frame: usize){
+        let values = match self.motion_study.as_ref().and_then(|study| study.get_frames().get(frame)){
+            Some(recorded) => recorded.values.clone(),
+            None => return,
         };
+        let tree = KinematicTree::new(&self.joints);
+        if tree.scatter_values(&mut self.joints, &values).is_ok(){
+            self.motion_frame = frame;
+            let _ = self.pose_tree(&tree);
+        }
+    }
+    //name of every value in the configuration
+    fn value_labels(&self) -> Vec<String>{
+        self.joints.iter().flat_map(|joint| joint.get_type().axes().iter().map(|axis| format!("{} {}", joint.name, axis.label())).collect::<Vec<String>>()).collect()
+    }
+    //time slider along the bottom of the view, right of the orientation gizmo
+    fn motion_scrubber(&self) -> Option<Slider>{
+        let study = self.motion_study.as_ref()?;
+        let time = study.get_frames().get(self.motion_frame)?.time;
+        let (screen_width, screen_height) = self.cameras[0].get_screen_size();
+        Some(Slider::new("time", 0.0, study.duration, time, 96, screen_height - 24, screen_width - 112))
+    }
+    //how freely the jointed parts can move in their current pose, see analyse_mobility
+    fn mobility_report(&self, grounded: &[NodeId]) -> Result<MobilityReport, MobilityError>{
+        let scene = &self.scene;
+        analyse_mobility(&self.joints, &self.couplings, grounded, |node| scene.world_transform(node).ok())
+    }
+    //the part instances as collision bodies, instances without geometry are left out
+    fn collision_bodies(&self) -> Vec<CollisionBody>{
+        self.assembly.instance_ids().into_iter().filter_map(|id| {
+            let node = self.assembly.get_instance(id).ok()?.get_node();
+            match self.scene.get_node(node).ok()?.get_object(){
+                Some(coordinate_object::Perspective_object(object)) => CollisionBody::new(node, object),
+                _ => None,
+            }
+        }).collect()
+    }
+    //parts joined directly by a joint, they overlap at the joint so are not checked against each other
+    fn jointed_pairs(&self) -> Vec<(NodeId, NodeId)>{
+        self.joints.iter().map(|joint| (joint.get_parent(), joint.get_child())).collect()
+    }
+    //part instances touching or going into one another where they are now
+    fn find_collisions(&self) -> Vec<Contact>{
+        let scene = &self.scene;
+        find_contacts(&self.collision_bodies(), |node| scene.world_transform(node).ok(), &self.jointed_pairs())
+    }
+    //The first frame of the motion study where part instances go into one another, with its time.
+    //None without a study or if the whole study is clear.
+    fn first_collision_in_study(&self) -> Result<Option<(f64, SweepCollision)>, KinematicsError>{
+        let study = match self.motion_study.as_ref(){
+            Some(study) => study,
+            None => return Ok(None),
+        };
+        let configurations: Vec<Vec<f64>> = study.get_frames().iter().map(|frame| frame.values.clone()).collect();
+        let scene = &self.scene;
+        let collision = first_collision(&KinematicTree::new(&self.joints), &self.joints, &self.collision_bodies(), &configurations, &self.jointed_pairs(), |node| scene.world_transform(node).ok())?;
+        Ok(collision.map(|collision| (study.get_frames()[collision.step].time, collision)))
+    }
+    //After a part moves outside of the joints, puts everything jointed to it back in place. Loops
+    //stay closed as the joint values do not change.
+    fn apply_all_joints(&mut self) -> Result<(), SceneGraphError>{
+        let tree = KinematicTree::new(&self.joints);
+        match self.pose_tree(&tree){
+            Err(KinematicsError::Scene(error)) => Err(error),
+            Err(KinematicsError::MissingBase(node)) => Err(SceneGraphError::NodeNotFound(node)),
+            //the values are the joints' own so nothing else can go wrong
+            _ => Ok(()),
+        }
+    }
+    //the node has to hold a Plane_object, anything else is refused and leaves the workplane as it
+    //was
+    fn set_active_workplane(&mut self, id: NodeId) -> Result<bool, SceneGraphError>{
+        match self.scene.get_node(id)?.get_object(){
+            Some(coordinate_object::Plane_object(_)) => {
+                self.active_workplane = Some(id);
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+    //the active workplane in world coordinates
+    fn get_active_workplane(&self) -> Plane{
+        let index = self.active_workplane
+            .and_then(|id| self.independent_nodes.iter().position(|node| *node == id));
+        match index.map(|index| &self.all_independents[index]){
+            Some(coordinate_object::Plane_object(plane)) => *plane,
+            _ => Plane::xy(),
+        }
+    }
+    //regenerates the world space objects from the scene graph after it changes
+    fn sync_scene(&mut self){
+        let (nodes, objects): (Vec<NodeId>, Vec<coordinate_object>) = self.scene.world_objects().into_iter().unzip();
+        self.independent_nodes = nodes;
+        self.all_independents = objects;
+        self.refit_spatial_index();
+    }
+    //Cheaper than sync_scene when only some nodes moved: just the objects on those nodes and below
+    //them are transformed again and only their leaves of the spatial index refitted
+    fn sync_moved(&mut self, moved: &[NodeId]){
+        for index in 0..self.independent_nodes.len(){
+            let node = self.independent_nodes[index];
+            if !moved.iter().any(|id| self.scene.is_descendant(node, *id)){
+                continue;
+            }
+            if let Ok(Some(object)) = self.scene.world_object(node){
+                self.spatial_index.update_object(index, object.axis_aligned_bounds());
+                self.all_independents[index] = object;
+            }
+        }
+        if self.spatial_index.needs_rebuild(){
+            self.spatial_index = Bvh::build(&self.all_independents);
+        }
+    }
+    //must be called after objects in all_independents are moved so the spatial index stays valid
+    fn refit_spatial_index(&mut self){
+        self.spatial_index.refit(&self.all_independents);
     }
     fn reference_to_cameras(&self) -> Vec<&Camera>{
         let mut vec: Vec<&Camera> = vec![];
@@ -99,12 +596,115 @@ impl WorldSpace{
     //With the cameras Extrinsics matrix, we can use the inverse to effectively translate to a new
     //coordinate system around the camera, allowing for easier and clearer logic.
     fn get_visible_objects<'a>(& 'a self, camera: & 'a Camera) -> Vec<&coordinate_object>{
-        camera.return_visible_objects(&self.all_independents)
+        camera.return_visible_objects_indexed(&self.all_independents, &self.spatial_index)
     }
     fn get_screen_values(&self, camera: & Camera) -> Vec<[u8;4]>{
     //We want to order these as local points by their depth (greatest to smallest and apply all in
     //that order).
-    camera.get_screen_values(&self.all_independents)
+    let mut pixel_buffer = camera.new_pixel_buffer();
+    camera.draw_ground_grid(&mut pixel_buffer, &self.ground);
+    camera.draw_origin_axes(&mut pixel_buffer, 2.0 * self.ground.get_grid_spacing());
+    camera.render_objects_into(&mut pixel_buffer, self.get_visible_objects(camera));
+    for annotation in self.annotations.iter(){
+        //annotations whose geometry is gone are skipped, not deleted
+        if let Some(resolved) = annotation.resolve(&self.independent_nodes, &self.all_independents){
+            camera.draw_annotation(&mut pixel_buffer, &resolved, [0x4e, 0xc9, 0xb0, 0xff]);
+        }
+    }
+    for line in self.overlay_lines.iter(){
+        camera.draw_line(&mut pixel_buffer, line, [0xf2, 0xc1, 0x2e, 0xff]);
+    }
+    for point in self.overlay_points.iter(){
+        camera.draw_marker(&mut pixel_buffer, point, [0xf2, 0xc1, 0x2e, 0xff]);
+    }
+    //named nodes get their name next to their object
+    for (node, object) in self.independent_nodes.iter().zip(self.all_independents.iter()){
+        let name = match self.scene.get_node(*node){
+            Ok(scene_node) => &scene_node.name,
+            Err(_) => continue,
+        };
+        if let (false, Some(bounds)) = (name.is_empty(), object.axis_aligned_bounds()){
+            camera.draw_label(&mut pixel_buffer, name, &Point::from_xyz(bounds.centre()), [0xdd, 0xdd, 0xdd, 0xff]);
+        }
+    }
+    //Joints at their limits are marked where their frames sit, joints beyond them in red. While a
+    //motion study frame is shown, joints that got there faster than their velocity limit are too.
+    let mut reports = self.limit_report().unwrap_or_default();
+    if let Some(frame) = self.motion_study.as_ref().and_then(|study| study.get_frames().get(self.motion_frame)){
+        reports.extend(frame.too_fast.iter().cloned());
+    }
+    for report in reports.iter(){
+        let joint = &self.joints[report.joint];
+        let origin = match self.scene.world_transform(joint.get_parent()){
+            Ok(parent_world) => parent_world.compose(&joint.get_parent_frame()).apply_point(&Point::origin()),
+            Err(_) => continue,
+        };
+        let colour = if report.state.is_beyond() {[0xe0, 0x40, 0x40, 0xff]} else {[0xf0, 0x90, 0x30, 0xff]};
+        camera.draw_marker(&mut pixel_buffer, &origin, colour);
+        let label = format!("{} {}", joint.name, joint.get_type().axes()[report.dof].label());
+        let label = match report.state{
+            LimitState::TooFast(speed) => format!("{} too fast ({:.3}/s)", label, speed),
+            _ => label,
+        };
+        camera.draw_label(&mut pixel_buffer, &label, &origin, colour);
+    }
+    camera.draw_orientation_gizmo(&mut pixel_buffer);
+    camera.draw_text(&mut pixel_buffer, &self.hud_lines.join("\n"), (4, 4), 1, [0xff, 0xff, 0xff, 0xff]);
+    let (screen_width, screen_height) = camera.get_screen_size();
+    if self.show_joint_sliders{
+        for slider in self.joint_sliders().iter(){
+            slider.draw(&mut pixel_buffer, screen_width, screen_height, [0xf2, 0xc1, 0x2e, 0xff]);
+        }
+    }
+    if let Some(scrubber) = self.motion_scrubber(){
+        scrubber.draw(&mut pixel_buffer, screen_width, screen_height, [0xdd, 0xdd, 0xdd, 0xff]);
+    }
+    pixel_buffer
+    }
+    //One slider per joint degree of freedom, in the same order as get_configuration. Each runs over
+    //its limits, or without them a full circle for turns and five grid squares either way for
+    //slides.
+    fn joint_sliders(&self) -> Vec<Slider>{
+        let (screen_width, _) = self.cameras[0].get_screen_size();
+        let width = 100;
+        let reach = 5.0 * self.ground.get_grid_spacing();
+        let labels = self.value_labels();
+        let mut sliders = vec![];
+        for joint in self.joints.iter(){
+            for ((axis, value), limit) in joint.get_type().axes().iter().zip(joint.get_values().iter()).zip(joint.get_limits().iter()){
+                let (min, max) = match limit{
+                    Some(limit) => (limit.min, limit.max),
+                    None if axis.is_rotation() => (-std::f64::consts::PI, std::f64::consts::PI),
+                    None => (-reach, reach),
+                };
+                let label = &labels[sliders.len()];
+                let y = 4 + sliders.len() as i32 * SLIDER_HEIGHT;
+                sliders.push(Slider::new(label, min, max, *value, screen_width - width - 8, y, width));
+            }
+        }
+        sliders
+    }
+    fn set_hud(&mut self, lines: Vec<String>){
+        self.hud_lines = lines;
+    }
+    //what is under the screen position in the main camera's view
+    fn pick(&self, screen_position: (f64, f64), kinds: &[PickKind]) -> Option<Pick>{
+        self.cameras[0].pick(&self.all_independents, &self.spatial_index, screen_position.0, screen_position.1, kinds, 6.0)
+    }
+    fn add_annotation(&mut self, annotation: Annotation) -> usize{
+        self.annotations.push(annotation);
+        self.annotations.len() - 1
+    }
+    fn remove_annotation(&mut self, index: usize) -> Option<Annotation>{
+        if index < self.annotations.len(){
+            Some(self.annotations.remove(index))
+        }else{
+            None
+        }
+    }
+    fn set_overlay(&mut self, lines: Vec<Line>, points: Vec<Point>){
+        self.overlay_lines = lines;
+        self.overlay_points = points;
     }
     fn update_size(&mut self, size:PhysicalSize<u32>){
         self.cameras[0].update_screen_size(size.width as i32, size.height as i32);
@@ -154,6 +754,17 @@ struct Subhandler{
     window: Window,
     worldspace: WorldSpace,
     right_mouse_button: bool,
+    //last cursor position in window pixels
+    cursor_position: (f64, f64),
+    //None when clicks are not measuring anything
+    measure_mode: Option<MeasureMode>,
+    measure_picks: Vec<Pick>,
+    //the joint slider being dragged, by its position in the configuration
+    slider_drag: Option<usize>,
+    //motion study playback: when it started on the wall clock and at which study time, None
+    //while paused
+    playing_since: Option<(Instant, f64)>,
+    scrubbing: bool,
 }
 impl Subhandler{
     pub fn new(window: Window, pixels: Pixels) -> Self{ 
@@ -162,11 +773,237 @@ impl Subhandler{
             window: window,
             worldspace: WorldSpace::new(),
             right_mouse_button: true,
+            cursor_position: (0.0, 0.0),
+            measure_mode: None,
+            measure_picks: vec![],
+            slider_drag: None,
+            playing_since: None,
+            scrubbing: false,
         }
     }
     pub fn redraw(&self){
         self.window.request_redraw();
     }
+    //switching modes (or leaving measuring with None) throws away the picks and the overlay
+    pub fn set_measure_mode(&mut self, mode: Option<MeasureMode>){
+        self.measure_mode = mode;
+        self.measure_picks.clear();
+        self.worldspace.set_overlay(vec![], vec![]);
+        self.worldspace.set_hud(match mode{
+            Some(mode) => vec![format!("measure: {:?}", mode)],
+            None => vec![],
+        });
+        self.redraw();
+    }
+    //d distance, m minimum distance, a angle, r radius, f face area, pressing the key of the
+    //current mode again stops measuring
+    fn measure_key(&mut self, key: &str){
+        let mode = match key{
+            "d" => MeasureMode::Distance,
+            "m" => MeasureMode::MinimumDistance,
+            "a" => MeasureMode::Angle,
+            "r" => MeasureMode::Radius,
+            "f" => MeasureMode::Area,
+            _ => return,
+        };
+        if self.measure_mode == Some(mode){
+            self.set_measure_mode(None);
+        }else{
+            self.set_measure_mode(Some(mode));
+        }
+    }
+    fn toggle_joint_sliders(&mut self){
+        self.worldspace.show_joint_sliders = !self.worldspace.show_joint_sliders;
+        self.slider_drag = None;
+        self.redraw();
+    }
+    //the orientation gizmo takes the click first, then the motion study's time slider and the
+    //joint sliders, anything else goes to measuring
+    fn left_click(&mut self){
+        let (x, y) = self.cursor_position;
+        if let Some(view) = self.worldspace.cameras[0].gizmo_hit(x, y){
+            self.worldspace.cameras[0].snap_to_view(view, Point::origin());
+            self.redraw();
+            return;
+        }
+        if self.worldspace.motion_scrubber().map_or(false, |scrubber| scrubber.contains(x, y)){
+            self.playing_since = None;
+            self.scrubbing = true;
+            self.scrub();
+            return;
+        }
+        if self.worldspace.show_joint_sliders{
+            if let Some(index) = self.worldspace.joint_sliders().iter().position(|slider| slider.contains(x, y)){
+                self.slider_drag = Some(index);
+                self.drag_slider();
+                return;
+            }
+        }
+        self.measure_click();
+    }
+    //p plays or pauses the motion study, playing again from the end starts over
+    fn toggle_playback(&mut self){
+        let study = match self.worldspace.motion_study.as_ref(){
+            Some(study) => study,
+            None => return,
+        };
+        if self.playing_since.is_some(){
+            self.playing_since = None;
+        }else{
+            let last = study.get_frames().len().saturating_sub(1);
+            let frame = if self.worldspace.motion_frame >= last {0} else {self.worldspace.motion_frame};
+            let time = study.get_frames().get(frame).map_or(0.0, |recorded| recorded.time);
+            self.playing_since = Some((Instant::now(), time));
+        }
+        self.redraw();
+    }
+    //steps one frame back or forward, pausing playback
+    fn step_motion(&mut self, forward: bool){
+        self.playing_since = None;
+        let frame = self.worldspace.motion_frame;
+        self.worldspace.show_motion_frame(if forward {frame + 1} else {frame.saturating_sub(1)});
+        self.redraw();
+    }
+    fn scrub(&mut self){
+        let time = match self.worldspace.motion_scrubber(){
+            Some(scrubber) => scrubber.value_at(self.cursor_position.0),
+            None => return,
+        };
+        if let Some(frame) = self.worldspace.motion_study.as_ref().and_then(|study| study.frame_at(time)){
+            self.worldspace.show_motion_frame(frame);
+        }
+        self.redraw();
+    }
+    //While playing, shows the frame for the time passed since playback started and asks for the
+    //next redraw straight away. Stops at the end of the study.
+    fn advance_playback(&mut self){
+        let (started, start_time) = match self.playing_since{
+            Some(playing) => playing,
+            None => return,
+        };
+        let time = start_time + started.elapsed().as_secs_f64();
+        let (frame, finished) = match self.worldspace.motion_study.as_ref(){
+            Some(study) => (study.frame_at(time), time >= study.duration),
+            None => (None, true),
+        };
+        if let Some(frame) = frame{
+            self.worldspace.show_motion_frame(frame);
+        }
+        if finished{
+            self.playing_since = None;
+        }else{
+            self.redraw();
+        }
+    }
+    //writes the motion study to motion_study.csv in the working directory
+    fn export_motion(&mut self){
+        let result = match self.worldspace.motion_study.as_ref(){
+            Some(study) => study.write_csv(std::path::Path::new("motion_study.csv")),
+            None => return,
+        };
+        self.worldspace.set_hud(vec![match result{
+            Ok(()) => "motion study written to motion_study.csv".to_string(),
+            Err(error) => format!("export failed: {}", error),
+        }]);
+        self.redraw();
+    }
+    //puts the mobility of the jointed parts in the HUD, with the parts no joint moves as ground
+    fn show_mobility(&mut self){
+        let hud = match self.worldspace.mobility_report(&[]){
+            Ok(report) => {
+                let mut lines = vec![report.summary()];
+                for (joint, repeated) in report.redundant_joints.iter(){
+                    lines.push(format!("{} repeats {} constraints", self.worldspace.joints[*joint].name, repeated));
+                }
+                lines
+            },
+            Err(error) => vec![format!("mobility: {:?}", error)],
+        };
+        self.worldspace.set_hud(hud);
+        self.redraw();
+    }
+    //With a motion study, jumps to the first frame where parts collide, otherwise checks the parts
+    //where they are. Contacts are listed in the HUD and marked at their points.
+    fn show_collisions(&mut self){
+        let (mut lines, contacts) = if self.worldspace.motion_study.is_some(){
+            match self.worldspace.first_collision_in_study(){
+                Ok(Some((time, collision))) => {
+                    let frame = self.worldspace.motion_study.as_ref().and_then(|study| study.frame_at(time)).unwrap_or(0);
+                    self.playing_since = None;
+                    self.worldspace.show_motion_frame(frame);
+                    (vec![format!("first collision at {:.3}s", time)], collision.contacts)
+                },
+                Ok(None) => (vec!["no collisions during the motion study".to_string()], vec![]),
+                Err(error) => (vec![format!("collisions: {:?}", error)], vec![]),
+            }
+        }else{
+            let contacts = self.worldspace.find_collisions();
+            (vec![format!("{} contacts", contacts.len())], contacts)
+        };
+        let name = |node: NodeId| self.worldspace.scene.get_node(node).map(|scene_node| scene_node.name.clone()).unwrap_or_default();
+        for contact in contacts.iter(){
+            lines.push(format!("{} / {}: depth {:.4}", name(contact.first), name(contact.second), contact.depth));
+        }
+        let points = contacts.iter().map(|contact| Point::from_xyz(contact.point)).collect();
+        self.worldspace.set_overlay(vec![], points);
+        self.worldspace.set_hud(lines);
+        self.redraw();
+    }
+    //sets the dragged slider's joint value from the cursor and poses the parts to match
+    fn drag_slider(&mut self){
+        let index = match self.slider_drag{
+            Some(index) => index,
+            None => return,
+        };
+        let slider = match self.worldspace.joint_sliders().get(index){
+            Some(slider) => slider.clone(),
+            None => {
+                self.slider_drag = None;
+                return;
+            },
+        };
+        let mut values = self.worldspace.get_configuration();
+        values[index] = slider.value_at(self.cursor_position.0);
+        match self.worldspace.set_configuration_driven(&values, &[index]){
+            Ok(Some(LoopSolution{branch_flipped: Some(true), ..})) => self.worldspace.set_hud(vec!["joints: branch flipped".to_string()]),
+            Ok(_) => {},
+            Err(error) => self.worldspace.set_hud(vec![format!("joints: {:?}", error)]),
+        }
+        self.redraw();
+    }
+    //Adds whatever is under the cursor to the current measurement. Once there are enough picks the
+    //result is reported and drawn, the next pick starts a new measurement.
+    fn measure_click(&mut self){
+        let mode = match self.measure_mode{
+            Some(mode) => mode,
+            None => return,
+        };
+        let pick = match self.worldspace.pick(self.cursor_position, mode.accepts()){
+            Some(pick) => pick,
+            None => return,
+        };
+        if mode.is_complete(&self.measure_picks){
+            self.measure_picks.clear();
+        }
+        self.measure_picks.push(pick);
+        let picked_points: Vec<Point> = self.measure_picks.iter().map(|pick| pick.point).collect();
+        let mut lines = vec![];
+        let mut hud = vec![format!("measure: {:?}", mode)];
+        if mode.is_complete(&self.measure_picks){
+            match measure(mode, &self.measure_picks){
+                Ok(measurement) => {
+                    hud.push(measurement.report());
+                    lines = measurement.overlay_lines();
+                },
+                Err(error) => {
+                    hud.push(format!("failed: {:?}", error));
+                },
+            }
+        }
+        self.worldspace.set_overlay(lines, picked_points);
+        self.worldspace.set_hud(hud);
+        self.redraw();
+    }
 
 
 }
@@ -188,6 +1025,7 @@ impl ApplicationHandler for Subhandler{
             },
            WindowEvent::RedrawRequested => {
                 print!("REQ");
+                self.advance_playback();
                 self.worldspace.get_new_pixels(&mut self.pixels,self.window.inner_size());
                 self.pixels.render();
             },
@@ -195,13 +1033,21 @@ impl ApplicationHandler for Subhandler{
                 device_id,
                 position,
             } => {
-                self.worldspace.cameras[0].rotate_degrees_y(1.0);
-                self.worldspace.cameras[0].update_camera();
+                self.cursor_position = (position.x, position.y);
+                //the view has to hold still while picking or using the sliders
+                if self.scrubbing{
+                    self.scrub();
+                }else if self.slider_drag.is_some(){
+                    self.drag_slider();
+                }else if self.measure_mode.is_none() && !self.worldspace.show_joint_sliders && self.worldspace.motion_study.is_none(){
+                    self.worldspace.cameras[0].rotate_degrees_y(1.0);
+                    self.worldspace.cameras[0].update_camera();
 
 
-                thread::sleep(Duration::new(0,10));
+                    thread::sleep(Duration::new(0,10));
 
-                self.redraw();
+                    self.redraw();
+                }
             },
             WindowEvent::MouseInput{
                 device_id: device_id,
@@ -212,6 +1058,13 @@ impl ApplicationHandler for Subhandler{
                             winit::event::ElementState::Pressed => self.right_mouse_button = true, 
                             winit::event::ElementState::Released => self.right_mouse_button = false,
                             },
+                        winit::event::MouseButton::Left => match state{
+                            winit::event::ElementState::Pressed => self.left_click(),
+                            winit::event::ElementState::Released => {
+                                self.slider_drag = None;
+                                self.scrubbing = false;
+                            },
+                            },
                         _ => {},
                     };
                 },
@@ -262,11 +1115,32 @@ impl ApplicationHandler for Subhandler{
                             self.worldspace.cameras[0].update_camera();
                             self.window.request_redraw();
                         },
+                        ( winit::keyboard::NamedKey::Escape,  winit::event::ElementState::Pressed) => {
+                            self.set_measure_mode(None);
+                        },
 
 
                         (_,_) => {},
                   };
                 },
+            WindowEvent::KeyboardInput{
+                event: KeyEvent{
+                    logical_key: winit::keyboard::Key::Character(text),
+                    state: winit::event::ElementState::Pressed,
+                    ..},
+                    ..
+            } => {
+                match text.as_str(){
+                    "j" => self.toggle_joint_sliders(),
+                    "k" => self.show_mobility(),
+                    "p" => self.toggle_playback(),
+                    "," => self.step_motion(false),
+                    "." => self.step_motion(true),
+                    "e" => self.export_motion(),
+                    "c" => self.show_collisions(),
+                    key => self.measure_key(key),
+                }
+            },
             _ =>{},
         }
     